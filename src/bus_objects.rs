@@ -1,9 +1,11 @@
 use std::cmp::Ordering;
 use std::option::Option;
+use std::str::FromStr;
 use std::vec::Vec;
 pub mod exit;
 pub mod memory;
 pub mod printer;
+pub mod timer;
 #[derive(Copy, Clone, Debug)]
 pub struct MemoryMapping {
     pub start: u32,
@@ -20,6 +22,46 @@ impl MemoryMapping {
     }
 }
 
+//Byte order of the program. The bus moves halfwords and words as the bytes in memory order, the
+//processor and the devices convert them to values in the byte order of the program.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Endianness {
+    #[default]
+    Big,
+    Little,
+}
+
+impl Endianness {
+    //Converts between a word on the bus and its value, in both directions
+    #[inline]
+    pub fn word(self, w: u32) -> u32 {
+        match self {
+            Endianness::Big => u32::from_be(w),
+            Endianness::Little => u32::from_le(w),
+        }
+    }
+
+    #[inline]
+    pub fn halfword(self, hw: u16) -> u16 {
+        match self {
+            Endianness::Big => u16::from_be(hw),
+            Endianness::Little => u16::from_le(hw),
+        }
+    }
+}
+
+impl FromStr for Endianness {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "big" => Ok(Endianness::Big),
+            "little" => Ok(Endianness::Little),
+            _ => Err(format!("Unknown byte order {}", s)),
+        }
+    }
+}
+
 pub trait BusObject {
     fn mapping(&self) -> MemoryMapping;
 
@@ -30,6 +72,13 @@ pub trait BusObject {
     fn write_byte(&mut self, addr: u32, val: u8);
     fn write_hw(&mut self, addr: u32, val: u16);
     fn write_w(&mut self, addr: u32, val: u32);
+
+    //Advances the device by one clock of the processor
+    fn tick(&mut self) {}
+    //Hardware interrupt lines asserted by the device, bit 0 is line 0 which is IP2 in Cause
+    fn interrupt_lines(&self) -> u8 {
+        0
+    }
 }
 
 pub struct Bus {
@@ -76,6 +125,13 @@ impl Bus {
         panic!("Invalid Bus Address {:#04X?}", addr);
     }
 
+    pub fn is_mapped(&self, addr: u32) -> bool {
+        self.bus_objects.iter().any(|x| {
+            let mapping = x.mapping();
+            (mapping.start <= addr) && (mapping.start + mapping.size > addr)
+        })
+    }
+
     fn get_mut_bus_obj(&mut self, addr: u32) -> &mut Box<dyn BusObject> {
         let index = Bus::get_bus_obj_index_vec(&self.bus_objects, addr);
         &mut self.bus_objects[index]
@@ -122,4 +178,16 @@ impl BusObject for Bus {
         let bus_obj = self.get_mut_bus_obj(addr);
         bus_obj.write_w(addr - bus_obj.mapping().start, val);
     }
+
+    fn tick(&mut self) {
+        for x in self.bus_objects.iter_mut() {
+            x.tick();
+        }
+    }
+    //The lines of all devices are wired together
+    fn interrupt_lines(&self) -> u8 {
+        self.bus_objects
+            .iter()
+            .fold(0, |lines, x| lines | x.interrupt_lines())
+    }
 }
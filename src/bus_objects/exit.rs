@@ -4,13 +4,16 @@ use crate::bus_objects;
 
 pub struct Exit {
     pub exit: bool,
+    //Halfwords and words are seen in the byte order of the program
+    endianness: bus_objects::Endianness,
     mapping: bus_objects::MemoryMapping,
 }
 
 impl Exit {
-    pub fn new(addr: u32) -> Self {
+    pub fn new(addr: u32, endianness: bus_objects::Endianness) -> Self {
         Exit {
             exit: false,
+            endianness,
             mapping: bus_objects::MemoryMapping {
                 start: addr,
                 size: 1,
@@ -27,10 +30,10 @@ impl bus_objects::BusObject for Exit {
         self.exit as u8
     }
     fn read_hw(&self, _addr: u32) -> u16 {
-        self.exit as u16
+        self.endianness.halfword(self.exit as u16)
     }
     fn read_w(&self, _addr: u32) -> u32 {
-        self.exit as u32
+        self.endianness.word(self.exit as u32)
     }
 
     fn write_byte(&mut self, _addr: u32, val: u8) {
@@ -39,12 +42,12 @@ impl bus_objects::BusObject for Exit {
         }
     }
     fn write_hw(&mut self, _addr: u32, val: u16) {
-        if val > 0 {
+        if self.endianness.halfword(val) > 0 {
             self.exit = true;
         }
     }
     fn write_w(&mut self, _addr: u32, val: u32) {
-        if val > 0 {
+        if self.endianness.word(val) > 0 {
             self.exit = true;
         }
     }
@@ -20,11 +20,18 @@ impl bus_objects::BusObject for Memory {
     }
 
     fn read_hw(&self, addr: u32) -> u16 {
-        unsafe { *((&self.mem[addr as usize] as *const u8) as *const u16) }
+        let addr = addr as usize;
+        u16::from_ne_bytes([self.mem[addr], self.mem[addr + 1]])
     }
 
     fn read_w(&self, addr: u32) -> u32 {
-        unsafe { *((&self.mem[addr as usize] as *const u8) as *const u32) }
+        let addr = addr as usize;
+        u32::from_ne_bytes([
+            self.mem[addr],
+            self.mem[addr + 1],
+            self.mem[addr + 2],
+            self.mem[addr + 3],
+        ])
     }
 
     fn write_byte(&mut self, addr: u32, val: u8) {
@@ -32,11 +39,13 @@ impl bus_objects::BusObject for Memory {
     }
 
     fn write_hw(&mut self, addr: u32, val: u16) {
-        unsafe { *((&mut self.mem[addr as usize] as *mut u8) as *mut u16) = val }
+        let addr = addr as usize;
+        self.mem[addr..addr + 2].copy_from_slice(&val.to_ne_bytes());
     }
 
     fn write_w(&mut self, addr: u32, val: u32) {
-        unsafe { *((&mut self.mem[addr as usize] as *mut u8) as *mut u32) = val }
+        let addr = addr as usize;
+        self.mem[addr..addr + 4].copy_from_slice(&val.to_ne_bytes());
     }
 }
 
@@ -69,7 +78,7 @@ mod test {
             },
         );
         for i in 1..255 {
-            mem.write_hw(i as u32, i as u16);
+            mem.write_hw(i, i as u16);
             assert_eq!(mem.read_hw(i), i as u16, "Read wrong halfword");
         }
     }
@@ -84,8 +93,8 @@ mod test {
             },
         );
         for i in 1..255 {
-            mem.write_w(i, i as u32);
-            assert_eq!(mem.read_w(i), i as u32, "Read wrong word");
+            mem.write_w(i, i);
+            assert_eq!(mem.read_w(i), i, "Read wrong word");
         }
     }
 }
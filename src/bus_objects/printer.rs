@@ -2,13 +2,16 @@ use crate::bus_objects;
 
 pub struct Printer {
     str: String,
+    //Halfwords and words are seen in the byte order of the program
+    endianness: bus_objects::Endianness,
     mapping: bus_objects::MemoryMapping,
 }
 
 impl Printer {
-    pub fn new(addr: u32) -> Self {
+    pub fn new(addr: u32, endianness: bus_objects::Endianness) -> Self {
         Printer {
             str: String::new(),
+            endianness,
             mapping: bus_objects::MemoryMapping {
                 start: addr,
                 size: 2,
@@ -44,6 +47,7 @@ impl bus_objects::BusObject for Printer {
         }
     }
     fn write_hw(&mut self, addr: u32, val: u16) {
+        let val = self.endianness.halfword(val);
         match addr {
             0 => self.str.push(val as u8 as char),
             1 => self.flush(),
@@ -51,6 +55,7 @@ impl bus_objects::BusObject for Printer {
         }
     }
     fn write_w(&mut self, addr: u32, val: u32) {
+        let val = self.endianness.word(val);
         match addr {
             0 => self.str.push(val as u8 as char),
             1 => self.flush(),
@@ -58,3 +63,22 @@ impl bus_objects::BusObject for Printer {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bus_objects::{BusObject, Endianness};
+
+    #[test]
+    fn characters_in_program_byte_order() {
+        let mut printer = Printer::new(0, Endianness::Big);
+        printer.write_w(0, (b'a' as u32).to_be());
+        printer.write_hw(0, (b'b' as u16).to_be());
+        printer.write_byte(0, b'c');
+        assert_eq!(printer.str, "abc");
+        let mut printer = Printer::new(0, Endianness::Little);
+        printer.write_w(0, (b'a' as u32).to_le());
+        printer.write_hw(0, (b'b' as u16).to_le());
+        assert_eq!(printer.str, "ab");
+    }
+}
@@ -0,0 +1,152 @@
+//Interval timer raising an interrupt line every compare clocks, a clock is a pipeline cycle or an
+//instruction without the pipeline
+//Word registers: 0 counter, 4 compare, 8 control (bit 0 counts, bit 1 enables the interrupt,
+//bit 2 is the pending interrupt and writing 1 to it acknowledges it). Byte and halfword writes
+//are ignored.
+
+use crate::bus_objects;
+
+const COUNTER: u32 = 0;
+const COMPARE: u32 = 4;
+const CONTROL: u32 = 8;
+
+const CONTROL_RUN: u32 = 1;
+const CONTROL_INTERRUPT: u32 = 2;
+const CONTROL_PENDING: u32 = 4;
+
+pub struct Timer {
+    counter: u32,
+    compare: u32,
+    control: u32,
+    pending: bool,
+    //Hardware interrupt line 0-5, which is IP2-IP7 in Cause
+    line: u8,
+    //The registers are seen in the byte order of the program
+    endianness: bus_objects::Endianness,
+    mapping: bus_objects::MemoryMapping,
+}
+
+impl Timer {
+    pub fn new(addr: u32, line: u8, endianness: bus_objects::Endianness) -> Self {
+        Timer {
+            counter: 0,
+            compare: 0,
+            control: 0,
+            pending: false,
+            line,
+            endianness,
+            mapping: bus_objects::MemoryMapping {
+                start: addr,
+                size: 12,
+            },
+        }
+    }
+
+    fn register(&self, addr: u32) -> u32 {
+        match addr & !3 {
+            COUNTER => self.counter,
+            COMPARE => self.compare,
+            CONTROL => self.control | if self.pending { CONTROL_PENDING } else { 0 },
+            _ => 0,
+        }
+    }
+}
+
+impl bus_objects::BusObject for Timer {
+    fn mapping(&self) -> bus_objects::MemoryMapping {
+        self.mapping
+    }
+    fn read_byte(&self, addr: u32) -> u8 {
+        self.read_w(addr).to_ne_bytes()[(addr & 3) as usize]
+    }
+    fn read_hw(&self, addr: u32) -> u16 {
+        let bytes = self.read_w(addr).to_ne_bytes();
+        let i = (addr & 2) as usize;
+        u16::from_ne_bytes([bytes[i], bytes[i + 1]])
+    }
+    //Words on the bus are in memory order
+    fn read_w(&self, addr: u32) -> u32 {
+        self.endianness.word(self.register(addr))
+    }
+
+    fn write_byte(&mut self, _addr: u32, _val: u8) {}
+    fn write_hw(&mut self, _addr: u32, _val: u16) {}
+    fn write_w(&mut self, addr: u32, val: u32) {
+        let val = self.endianness.word(val);
+        match addr & !3 {
+            COUNTER => self.counter = val,
+            COMPARE => self.compare = val,
+            CONTROL => {
+                self.control = val & (CONTROL_RUN | CONTROL_INTERRUPT);
+                if val & CONTROL_PENDING != 0 {
+                    self.pending = false;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn tick(&mut self) {
+        if self.control & CONTROL_RUN == 0 {
+            return;
+        }
+        self.counter = self.counter.wrapping_add(1);
+        if self.counter >= self.compare {
+            self.counter = 0;
+            self.pending = true;
+        }
+    }
+
+    fn interrupt_lines(&self) -> u8 {
+        match self.pending && self.control & CONTROL_INTERRUPT != 0 {
+            true => 1 << self.line,
+            false => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::BusObject;
+    use super::*;
+
+    #[test]
+    fn periodic_interrupt() {
+        let mut timer = Timer::new(0, 2, bus_objects::Endianness::Big);
+        timer.write_w(COMPARE, 3u32.to_be());
+        timer.write_w(CONTROL, (CONTROL_RUN | CONTROL_INTERRUPT).to_be());
+        timer.tick();
+        timer.tick();
+        assert_eq!(timer.interrupt_lines(), 0);
+        timer.tick();
+        assert_eq!(timer.interrupt_lines(), 0b100);
+        assert_eq!(u32::from_be(timer.read_w(CONTROL)), 0b111);
+        //acknowledged, the counter already restarted
+        timer.write_w(
+            CONTROL,
+            (CONTROL_RUN | CONTROL_INTERRUPT | CONTROL_PENDING).to_be(),
+        );
+        assert_eq!(timer.interrupt_lines(), 0);
+        timer.tick();
+        assert_eq!(u32::from_be(timer.read_w(COUNTER)), 1);
+    }
+
+    #[test]
+    fn little_endian_registers() {
+        let mut timer = Timer::new(0, 0, bus_objects::Endianness::Little);
+        timer.write_w(COMPARE, 0x0102_0304u32.to_le());
+        assert_eq!(timer.read_byte(COMPARE), 0x04);
+        assert_eq!(timer.read_byte(COMPARE + 3), 0x01);
+        assert_eq!(u16::from_le(timer.read_hw(COMPARE + 2)), 0x0102);
+    }
+
+    #[test]
+    fn partial_writes_are_ignored() {
+        let mut timer = Timer::new(0, 0, bus_objects::Endianness::Big);
+        timer.write_w(COMPARE, 5u32.to_be());
+        timer.write_byte(COMPARE + 3, 7);
+        timer.write_hw(CONTROL, 1);
+        assert_eq!(u32::from_be(timer.read_w(COMPARE)), 5);
+        assert_eq!(timer.read_w(CONTROL), 0);
+    }
+}
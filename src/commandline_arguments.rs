@@ -1,5 +1,31 @@
 extern crate clap;
+use crate::bus_objects::Endianness;
+use crate::cpu::branch_prediction::{BranchPredictorKind, MAX_TABLE_BITS};
+use crate::cpu::cache::CacheConfig;
+use crate::cpu::diagram::DiagramSelection;
+use crate::cpu::division::DivideByZero;
+use crate::cpu::forwarding::Forwarding;
+use crate::cpu::isa::IsaLevel;
+use crate::cpu::load_delay::LoadDelay;
+use crate::cpu::syscall::SyscallEmulation;
 use clap::{App, Arg};
+use std::str::FromStr;
+
+//The tables of the dynamic predictors are allocated with 2^bits entries
+fn predictor_bits(v: String) -> Result<(), String> {
+    match v.parse::<u32>() {
+        Ok(bits) if bits <= MAX_TABLE_BITS => Ok(()),
+        _ => Err(format!(
+            "The predictor bits have to be a number from 0 to {}",
+            MAX_TABLE_BITS
+        )),
+    }
+}
+
+//Values with their own syntax report the error of their parser
+fn parses<T: FromStr<Err = String>>(v: String) -> Result<(), String> {
+    v.parse::<T>().map(|_| ())
+}
 
 #[derive(Debug)]
 pub struct CommandLineArguments {
@@ -11,10 +37,64 @@ pub struct CommandLineArguments {
 
     exit_pos: u32,
     printer_pos: u32,
+    //Address of the interval timer, none if not attached
+    timer_pos: Option<u32>,
+    //Hardware interrupt line of the timer. Default: 5
+    timer_line: u8,
 
     disassemble: bool,
+
+    //Deliver exceptions to the handler of the program instead of stopping
+    cp0: bool,
+    //Attach the R3010 floating point coprocessor
+    fpu: bool,
+    //Translate addresses through the TLB and the kernel segments
+    mmu: bool,
+    //Instruction set the decoder accepts. Default: mips1
+    isa: IsaLevel,
+    //Byte order of the program. Default: taken from the ELF header
+    endianness: Option<Endianness>,
+    //Services provided by the simulator behind syscall. Default: none
+    syscalls: SyscallEmulation,
+    //Heap size for sbrk in Kilobytes. Default: 1024
+    heap_size: u32,
+
+    //Run the cycle driven pipeline model instead of executing one instruction per step
+    pipeline: bool,
+    //Print the stall cycles caused by every data hazard at exit
+    hazards: bool,
+    //Bypass paths available to the ALU inputs. Default: full
+    forwarding: Forwarding,
+    //Visibility of load results to the instruction in the load delay slot. Default: interlocked
+    load_delay: LoadDelay,
+    //Handling of div and divu with a zero divisor. Default: silent
+    divide_by_zero: DivideByZero,
+    //Instructions shown in the pipeline diagram printed at exit
+    diagram: Option<DiagramSelection>,
+    //Cycles of the multiply/divide unit. Default: 12 and 35 like the R3000
+    mult_latency: u64,
+    div_latency: u64,
+    //Branch predictor steering the fetch. Default: nottaken
+    predictor: BranchPredictorKind,
+    //Number of pc bits indexing the tables of the dynamic predictors. Default: 10
+    predictor_bits: u32,
+    //Entries of the branch target buffer, 0 computes direct targets at fetch. Default: 0
+    btb_entries: usize,
+    //Entries of the return address stack. Default: 0
+    ras_entries: usize,
+    //Caches in front of the bus, none if not given
+    icache: Option<CacheConfig>,
+    dcache: Option<CacheConfig>,
+    //Cycles of a bus transaction caused by a cache. Default: 10
+    miss_penalty: u64,
+    //Entries of the write buffer, 0 disables it. Default: 0
+    write_buffer: usize,
+    //Cycles the bus needs to write one write buffer entry. Default: 10
+    drain_rate: u64,
     //Path to a statically linked freestanding ELF Binary with MIPS R3000 machine code
     executable: String,
+    //Passed to the program in argv with Linux syscalls
+    arguments: Vec<String>,
 }
 
 impl CommandLineArguments {
@@ -24,11 +104,178 @@ impl CommandLineArguments {
             .author("Calvin Katt")
             .about("Simulates a MIPS R3000 CPU")
             .arg(Arg::with_name("Disassemble").long("disassemble").short("d").help("Disassemble the executed instructions"))
+            .arg(Arg::with_name("CP0").long("cp0").help("Delivers exceptions to the exception vector of the program instead of stopping the simulation"))
+            .arg(Arg::with_name("FPU").long("fpu").help("Attaches an R3010 floating point coprocessor"))
+            .arg(Arg::with_name("MMU").long("mmu").help("Translates addresses through the R3000 TLB and segments. The program, heap and stack are placed at the physical address of their kseg0 or kseg1 addresses, mapped ones like the default stack in kseg2 need TLB entries the program writes itself"))
+            .arg(
+                Arg::with_name("ISA")
+                    .long("isa")
+                    .value_name("LEVEL")
+                    .help("Instruction set level, instructions of later levels raise a Reserved Instruction exception")
+                    .takes_value(true)
+                    .possible_values(&["mips1", "mips2", "mips32r1", "mips32r2"])
+                    .default_value("mips1"),
+            )
+            .arg(
+                Arg::with_name("Endianness")
+                    .long("endianness")
+                    .value_name("ORDER")
+                    .help("Byte order of fetches, loads and stores, overrides the byte order of the ELF header")
+                    .takes_value(true)
+                    .possible_values(&["big", "little"]),
+            )
+            .arg(
+                Arg::with_name("Syscalls")
+                    .long("syscalls")
+                    .value_name("SERVICES")
+                    .help("Emulates the syscall services of another environment instead of raising a System Call exception")
+                    .takes_value(true)
+                    .possible_values(&["none", "spim", "linux"])
+                    .default_value("none"),
+            )
+            .arg(
+                Arg::with_name("Heap Size")
+                    .long("heapsize")
+                    .value_name("SIZE")
+                    .help("Sets the size of the heap behind the program handed out by sbrk in Kilobytes")
+                    .takes_value(true)
+                    .default_value("1024"),
+            )
+            .arg(Arg::with_name("Pipeline").long("pipeline").help("Simulates the five stage pipeline cycle by cycle").conflicts_with("Disassemble"))
+            .arg(
+                Arg::with_name("Forwarding")
+                    .long("forwarding")
+                    .value_name("MODE")
+                    .help("Sets the forwarding paths of the pipeline")
+                    .takes_value(true)
+                    .possible_values(&["none", "ex", "full"])
+                    .default_value("full"),
+            )
+            .arg(
+                Arg::with_name("Load Delay")
+                    .long("loaddelay")
+                    .value_name("MODE")
+                    .help("Sets how the instruction in a load delay slot sees the loaded register")
+                    .takes_value(true)
+                    .possible_values(&["interlocked", "strict", "warn"])
+                    .default_value("interlocked"),
+            )
+            .arg(
+                Arg::with_name("Divide By Zero")
+                    .long("divzero")
+                    .value_name("MODE")
+                    .help("Sets whether div and divu by zero and overflowing div silently give the R3000 results, warn or raise the Breakpoint (mips1) or Trap exception of the compiler's check")
+                    .takes_value(true)
+                    .possible_values(&["silent", "warn", "trap"])
+                    .default_value("silent"),
+            )
+            .arg(
+                Arg::with_name("Diagram")
+                    .long("diagram")
+                    .value_name("RANGE")
+                    .help("Prints the pipeline diagram of the instructions fetched from the pc range 0xSTART-0xEND or of the instruction window FIRST-LAST")
+                    .takes_value(true)
+                    .validator(parses::<DiagramSelection>)
+                    .requires("Pipeline"),
+            )
+            .arg(
+                Arg::with_name("Mult Latency")
+                    .long("multlatency")
+                    .value_name("CYCLES")
+                    .help("Sets the cycles until the result of mult and multu is available in HI and LO")
+                    .takes_value(true)
+                    .default_value("12"),
+            )
+            .arg(
+                Arg::with_name("Div Latency")
+                    .long("divlatency")
+                    .value_name("CYCLES")
+                    .help("Sets the cycles until the result of div and divu is available in HI and LO")
+                    .takes_value(true)
+                    .default_value("35"),
+            )
+            .arg(
+                Arg::with_name("Predictor")
+                    .long("predictor")
+                    .value_name("PREDICTOR")
+                    .help("Sets the branch predictor steering the fetch, the accuracy of all predictors is reported")
+                    .takes_value(true)
+                    .possible_values(&["nottaken", "backward", "1bit", "2bit", "gshare", "tournament"])
+                    .default_value("nottaken"),
+            )
+            .arg(
+                Arg::with_name("Predictor Bits")
+                    .long("predictorbits")
+                    .value_name("BITS")
+                    .help("Sets the number of pc bits indexing the tables of the dynamic branch predictors, at most 24")
+                    .takes_value(true)
+                    .validator(predictor_bits)
+                    .default_value("10"),
+            )
+            .arg(
+                Arg::with_name("BTB")
+                    .long("btb")
+                    .value_name("ENTRIES")
+                    .help("Sets the entries of the branch target buffer, without one the targets of direct branches are known at fetch")
+                    .takes_value(true)
+                    .default_value("0"),
+            )
+            .arg(
+                Arg::with_name("RAS")
+                    .long("ras")
+                    .value_name("ENTRIES")
+                    .help("Sets the entries of the return address stack")
+                    .takes_value(true)
+                    .default_value("0"),
+            )
+            .arg(
+                Arg::with_name("ICache")
+                    .long("icache")
+                    .value_name("CONFIG")
+                    .help("Adds an instruction cache configured as SIZE,LINE,WAYS[,lru|fifo|random[,wb|wt[,alloc|noalloc]]]")
+                    .takes_value(true)
+                    .validator(parses::<CacheConfig>)
+                    .requires("Pipeline"),
+            )
+            .arg(
+                Arg::with_name("DCache")
+                    .long("dcache")
+                    .value_name("CONFIG")
+                    .help("Adds a data cache configured as SIZE,LINE,WAYS[,lru|fifo|random[,wb|wt[,alloc|noalloc]]]")
+                    .takes_value(true)
+                    .validator(parses::<CacheConfig>)
+                    .requires("Pipeline"),
+            )
+            .arg(
+                Arg::with_name("Miss Penalty")
+                    .long("misspenalty")
+                    .value_name("CYCLES")
+                    .help("Sets the cycles of every line fill, write back and write through of the caches")
+                    .takes_value(true)
+                    .default_value("10"),
+            )
+            .arg(
+                Arg::with_name("Write Buffer")
+                    .long("writebuffer")
+                    .value_name("ENTRIES")
+                    .help("Adds a write buffer for the stores, the R3000 has 4 entries")
+                    .takes_value(true)
+                    .default_value("0"),
+            )
+            .arg(
+                Arg::with_name("Drain Rate")
+                    .long("drainrate")
+                    .value_name("CYCLES")
+                    .help("Sets the cycles the bus needs to write one entry of the write buffer")
+                    .takes_value(true)
+                    .default_value("10"),
+            )
+            .arg(Arg::with_name("Hazards").long("hazards").help("Reports the stall cycles caused by each data hazard").requires("Pipeline"))
             .arg(
                 Arg::with_name("Stack Overwrite")
                     .long("stackoverwrite")
                     .value_name("ADDR")
-                    .help("Sets the address of the stack pointer at the start of the program.")
+                    .help("Sets the address of the stack pointer at the start of the program. With --mmu the default lies in the mapped kseg2, use a kseg0 address instead")
                     .takes_value(true)
                     .default_value("0xFFFFFFFF"),
             )
@@ -43,11 +290,25 @@ impl CommandLineArguments {
             .arg(
                 Arg::with_name("Printer Position").long("printerpos").short("p").help("Sets the memory address of the two byte printer device").takes_value(true).default_value("0x2")
             )
+            .arg(Arg::with_name("Timer Position").long("timerpos").help("Attaches an interval timer at the given memory address, it counts cycles with --pipeline and instructions otherwise").takes_value(true))
+            .arg(
+                Arg::with_name("Timer Line")
+                    .long("timerline")
+                    .help("Hardware interrupt line 0-5 of the timer, shown as IP2-IP7 in Cause. Below MIPS II the R3010 interrupts on line 3")
+                    .takes_value(true)
+                    .possible_values(&["0", "1", "2", "3", "4", "5"])
+                    .default_value("5"),
+            )
             .arg(Arg::with_name("Exit Position").long("exitpos").short("e").help("Sets the memory address of the one byte exit device used to signal the exit of the programm to the simulator").takes_value(true).default_value("0x1"))
             .arg(Arg::with_name("EXECUTABLE").help(
                 "Path to a statically linked freestanding ELF Binary with MIPS R3000 machine code",
-            ).required(true)).get_matches();
+            ).required(true))
+            .arg(Arg::with_name("ARGS").help("Arguments passed to the program with --syscalls linux").multiple(true))
+            .get_matches();
         let exec_path = matches.value_of("EXECUTABLE").unwrap();
+        let arguments = matches
+            .values_of("ARGS")
+            .map_or(vec![], |x| x.map(String::from).collect());
         let stack_overwrite = u32::from_str_radix(
             matches
                 .value_of("Stack Overwrite")
@@ -70,6 +331,14 @@ impl CommandLineArguments {
             16,
         )
         .unwrap();
+        let timer_pos = matches
+            .value_of("Timer Position")
+            .map(|x| u32::from_str_radix(x.trim_start_matches("0x"), 16).unwrap());
+        let timer_line = matches
+            .value_of("Timer Line")
+            .unwrap()
+            .parse::<u8>()
+            .unwrap();
         let printer_pos = u32::from_str_radix(
             matches
                 .value_of("Printer Position")
@@ -79,13 +348,124 @@ impl CommandLineArguments {
         )
         .unwrap();
         let disassemble = matches.is_present("Disassemble");
+        let cp0 = matches.is_present("CP0");
+        let fpu = matches.is_present("FPU");
+        let mmu = matches.is_present("MMU");
+        let isa = matches
+            .value_of("ISA")
+            .unwrap()
+            .parse::<IsaLevel>()
+            .unwrap();
+        let endianness = matches
+            .value_of("Endianness")
+            .map(|x| x.parse::<Endianness>().unwrap());
+        let syscalls = matches
+            .value_of("Syscalls")
+            .unwrap()
+            .parse::<SyscallEmulation>()
+            .unwrap();
+        let heap_size = matches
+            .value_of("Heap Size")
+            .unwrap()
+            .parse::<u32>()
+            .unwrap();
+        let pipeline = matches.is_present("Pipeline");
+        let hazards = matches.is_present("Hazards");
+        let forwarding = matches
+            .value_of("Forwarding")
+            .unwrap()
+            .parse::<Forwarding>()
+            .unwrap();
+        let diagram = matches
+            .value_of("Diagram")
+            .map(|x| x.parse::<DiagramSelection>().unwrap());
+        let mult_latency = matches
+            .value_of("Mult Latency")
+            .unwrap()
+            .parse::<u64>()
+            .unwrap();
+        let div_latency = matches
+            .value_of("Div Latency")
+            .unwrap()
+            .parse::<u64>()
+            .unwrap();
+        let predictor = matches
+            .value_of("Predictor")
+            .unwrap()
+            .parse::<BranchPredictorKind>()
+            .unwrap();
+        let predictor_bits = matches
+            .value_of("Predictor Bits")
+            .unwrap()
+            .parse::<u32>()
+            .unwrap();
+        let btb_entries = matches.value_of("BTB").unwrap().parse::<usize>().unwrap();
+        let ras_entries = matches.value_of("RAS").unwrap().parse::<usize>().unwrap();
+        let icache = matches
+            .value_of("ICache")
+            .map(|x| x.parse::<CacheConfig>().unwrap());
+        let dcache = matches
+            .value_of("DCache")
+            .map(|x| x.parse::<CacheConfig>().unwrap());
+        let miss_penalty = matches
+            .value_of("Miss Penalty")
+            .unwrap()
+            .parse::<u64>()
+            .unwrap();
+        let write_buffer = matches
+            .value_of("Write Buffer")
+            .unwrap()
+            .parse::<usize>()
+            .unwrap();
+        let drain_rate = matches
+            .value_of("Drain Rate")
+            .unwrap()
+            .parse::<u64>()
+            .unwrap();
+        let load_delay = matches
+            .value_of("Load Delay")
+            .unwrap()
+            .parse::<LoadDelay>()
+            .unwrap();
+        let divide_by_zero = matches
+            .value_of("Divide By Zero")
+            .unwrap()
+            .parse::<DivideByZero>()
+            .unwrap();
         CommandLineArguments {
             stack_overwrite,
             stack_size,
             exit_pos,
             printer_pos,
+            timer_pos,
+            timer_line,
             disassemble,
+            cp0,
+            fpu,
+            mmu,
+            isa,
+            endianness,
+            syscalls,
+            heap_size,
+            pipeline,
+            hazards,
+            forwarding,
+            load_delay,
+            divide_by_zero,
+            diagram,
+            mult_latency,
+            div_latency,
+            predictor,
+            predictor_bits,
+            btb_entries,
+            ras_entries,
+            icache,
+            dcache,
+            miss_penalty,
+            write_buffer,
+            drain_rate,
             executable: String::from(exec_path),
+            arguments,
         }
     }
 
@@ -94,6 +474,11 @@ impl CommandLineArguments {
         &self.executable
     }
 
+    #[inline]
+    pub fn arguments(&self) -> &[String] {
+        &self.arguments
+    }
+
     #[inline]
     pub fn stack_size(&self) -> u32 {
         self.stack_size
@@ -114,8 +499,138 @@ impl CommandLineArguments {
         self.printer_pos
     }
 
+    #[inline]
+    pub fn timer_pos(&self) -> Option<u32> {
+        self.timer_pos
+    }
+
+    #[inline]
+    pub fn timer_line(&self) -> u8 {
+        self.timer_line
+    }
+
     #[inline]
     pub fn disassemble(&self) -> bool {
         self.disassemble
     }
+
+    #[inline]
+    pub fn cp0(&self) -> bool {
+        self.cp0
+    }
+
+    #[inline]
+    pub fn fpu(&self) -> bool {
+        self.fpu
+    }
+
+    #[inline]
+    pub fn mmu(&self) -> bool {
+        self.mmu
+    }
+
+    #[inline]
+    pub fn isa(&self) -> IsaLevel {
+        self.isa
+    }
+
+    #[inline]
+    pub fn endianness(&self) -> Option<Endianness> {
+        self.endianness
+    }
+
+    #[inline]
+    pub fn syscalls(&self) -> SyscallEmulation {
+        self.syscalls
+    }
+
+    #[inline]
+    pub fn heap_size(&self) -> u32 {
+        self.heap_size
+    }
+
+    #[inline]
+    pub fn pipeline(&self) -> bool {
+        self.pipeline
+    }
+
+    #[inline]
+    pub fn hazards(&self) -> bool {
+        self.hazards
+    }
+
+    #[inline]
+    pub fn forwarding(&self) -> Forwarding {
+        self.forwarding
+    }
+
+    #[inline]
+    pub fn load_delay(&self) -> LoadDelay {
+        self.load_delay
+    }
+
+    #[inline]
+    pub fn divide_by_zero(&self) -> DivideByZero {
+        self.divide_by_zero
+    }
+
+    #[inline]
+    pub fn diagram(&self) -> Option<DiagramSelection> {
+        self.diagram
+    }
+
+    #[inline]
+    pub fn mult_latency(&self) -> u64 {
+        self.mult_latency
+    }
+
+    #[inline]
+    pub fn div_latency(&self) -> u64 {
+        self.div_latency
+    }
+
+    #[inline]
+    pub fn predictor(&self) -> BranchPredictorKind {
+        self.predictor
+    }
+
+    #[inline]
+    pub fn predictor_bits(&self) -> u32 {
+        self.predictor_bits
+    }
+
+    #[inline]
+    pub fn btb_entries(&self) -> usize {
+        self.btb_entries
+    }
+
+    #[inline]
+    pub fn ras_entries(&self) -> usize {
+        self.ras_entries
+    }
+
+    #[inline]
+    pub fn icache(&self) -> Option<CacheConfig> {
+        self.icache
+    }
+
+    #[inline]
+    pub fn dcache(&self) -> Option<CacheConfig> {
+        self.dcache
+    }
+
+    #[inline]
+    pub fn miss_penalty(&self) -> u64 {
+        self.miss_penalty
+    }
+
+    #[inline]
+    pub fn write_buffer(&self) -> usize {
+        self.write_buffer
+    }
+
+    #[inline]
+    pub fn drain_rate(&self) -> u64 {
+        self.drain_rate
+    }
 }
@@ -1,9 +1,30 @@
+pub mod branch_prediction;
+pub mod branch_target;
+pub mod cache;
+pub mod cp0;
+pub mod cp1;
+pub mod diagram;
+pub mod division;
+pub mod forwarding;
+pub mod fpu;
+pub mod hazard;
 mod instruction_info;
 pub mod instructions;
+pub mod isa;
+pub mod load_delay;
+pub mod mdu;
+pub mod memory_access;
+pub mod mmu;
+pub mod pipeline;
+pub mod syscall;
+pub mod write_buffer;
 use crate::bus_objects;
 use crate::bus_objects::BusObject;
+use cp0::*;
 use instruction_info::*;
 use instructions::*;
+use load_delay::*;
+use syscall::*;
 const MIPS_REGISTER_NAMES: [&str; 32] = [
     "zero", "at", "v0", "v1", "a0", "a1", "a2", "a3", "t0", "t1", "t2", "t3", "t4", "t5", "t6",
     "t7", "s0", "s1", "s2", "s3", "s4", "s5", "s6", "s7", "t8", "t9", "k0", "k1", "gp", "sp",
@@ -11,7 +32,7 @@ const MIPS_REGISTER_NAMES: [&str; 32] = [
 ];
 
 pub struct MipsCpu<'a> {
-    //Always big endian
+    //Depending on host architecture
     general_registers: [u32; 31],
 
     //Depending on host architecture
@@ -23,10 +44,36 @@ pub struct MipsCpu<'a> {
     //Depending on host architecture
     pc: u32,
     pub bus: &'a mut bus_objects::Bus,
+    //Byte order of fetches, loads and stores
+    pub endianness: bus_objects::Endianness,
 
     branch: bool,
+    //The previous instruction was a branch or jump, taken or not, pc is in its delay slot
+    delay_slot: bool,
     //Depending on host architecture
     branch_target: u32,
+    //A branch likely was not taken, its delay slot is skipped
+    nullify: bool,
+    //Set by ll, sc only stores if no exception occurred since then
+    ll_bit: bool,
+
+    pub isa: isa::IsaLevel,
+
+    pub load_delay: LoadDelaySlot,
+    pub division: division::DivisionCheck,
+
+    pub cp0: Cp0,
+    //Raised by the executing instruction, delivered once it has finished
+    exception: Option<Exception>,
+
+    pub cp1: cp1::Cp1,
+
+    pub syscalls: SyscallServices,
+
+    pub pipeline: pipeline::Pipeline,
+
+    //Ends the simulation, the instruction that caused it had no effect
+    pub fatal: Option<Box<dyn std::error::Error>>,
 }
 
 impl<'a> MipsCpu<'a> {
@@ -37,8 +84,21 @@ impl<'a> MipsCpu<'a> {
             lo: 0,
             pc,
             bus,
+            endianness: bus_objects::Endianness::default(),
             branch: false,
+            delay_slot: false,
             branch_target: 0,
+            nullify: false,
+            ll_bit: false,
+            isa: isa::IsaLevel::default(),
+            load_delay: LoadDelaySlot::default(),
+            division: division::DivisionCheck::default(),
+            cp0: Cp0::default(),
+            exception: None,
+            cp1: cp1::Cp1::default(),
+            syscalls: SyscallServices::default(),
+            pipeline: pipeline::Pipeline::new(pc),
+            fatal: None,
         }
     }
 
@@ -49,102 +109,295 @@ impl<'a> MipsCpu<'a> {
     }
 
     pub fn init_gp(&mut self, v: u32) {
-        self.set_register_nc(28, v);
+        self.set_register(28, v);
     }
 
     #[inline]
-    fn get_register(&self, index: u8) -> u32 {
-        match index {
-            0 => 0,
-            r => u32::from_be(self.general_registers[(r as usize) - 1]),
+    pub fn set_endianness(&mut self, endianness: bus_objects::Endianness) {
+        self.endianness = endianness;
+    }
+
+    #[inline]
+    pub fn set_cp0(&mut self, enabled: bool) {
+        self.cp0.enabled = enabled;
+    }
+
+    //Attaches an R3010, the boot code leaves it usable
+    pub fn set_fpu(&mut self, present: bool) {
+        self.cp1.present = present;
+        if present {
+            self.cp0.status |= STATUS_CU1;
         }
     }
+
+    pub fn set_load_delay(&mut self, mode: LoadDelay) {
+        self.load_delay.mode = mode;
+        self.pipeline.hazards.load_delay_slot = mode == LoadDelay::Strict;
+    }
+
     #[inline]
-    fn get_register_nc(&self, index: u8) -> u32 {
+    pub fn set_isa(&mut self, isa: isa::IsaLevel) {
+        self.isa = isa;
+    }
+
+    #[inline]
+    pub fn set_divide_by_zero(&mut self, mode: division::DivideByZero) {
+        self.division.mode = mode;
+    }
+
+    //The instruction in EX of the pipeline reads the results of older instructions that have not
+    //been written back yet through the bypass network
+    #[inline]
+    fn get_register(&self, index: u8) -> u32 {
         match index {
             0 => 0,
-            r => self.general_registers[(r as usize) - 1],
+            r => match self.pipeline.bypass(r) {
+                Some(value) => value,
+                None => self.general_registers[(r as usize) - 1],
+            },
         }
     }
 
+    //The result of the instruction in EX of the pipeline is written back in WB
     #[inline]
     fn set_register(&mut self, index: u8, value: u32) {
-        self.set_register_nc(index, u32::from_be(value));
-    }
-    //Set Register without conversion to Big Endian
-    #[inline]
-    fn set_register_nc(&mut self, index: u8, value: u32) {
+        if let Some(e) = self.pipeline.executing.as_mut() {
+            if index != 0 {
+                e.result = Some((index, value));
+            }
+            return;
+        }
         if index != 0 {
             self.general_registers[(index as usize) - 1] = value;
         }
+        //a write in the load delay slot wins over the delayed load
+        if matches!(self.load_delay.pending, Some((r, _)) if r == index) {
+            self.load_delay.pending = None;
+        }
     }
 
+    //Writes the result of a load, delayed by one instruction in strict mode
     #[inline]
-    fn generic_step(&self) -> instruction_info::InstructionInfos {
-        let i_w = u32::from_be(self.bus.read_w(self.pc));
+    fn set_loaded_register(&mut self, index: u8, value: u32) {
+        match self.load_delay.mode {
+            //the pipeline delays it by writing it back after MEM
+            LoadDelay::Strict if self.pipeline.executing.is_none() => {
+                self.load_delay.issued = Some((index, value))
+            }
+            _ => self.set_register(index, value),
+        }
+    }
+
+    //Value lwl and lwr merge the loaded bytes into, a load still in its delay slot is bypassed
+    #[inline]
+    fn get_merge_register(&self, index: u8) -> u32 {
+        if let Some(value) = self.pipeline.merge_bypass(index) {
+            return value;
+        }
+        match self.load_delay.pending {
+            Some((r, value)) if r == index && index != 0 => value,
+            _ => self.get_register(index),
+        }
+    }
+
+    //Fetches and decodes the instruction at pc, None if the fetch raised an exception instead
+    #[inline]
+    fn generic_step(&mut self) -> Option<instruction_info::InstructionInfos> {
+        let fetched = self
+            .fetch_address(self.pc)
+            .and_then(|addr| match self.bus.is_mapped(addr) {
+                true => Ok(addr),
+                false => Err(Exception::new(ExceptionCode::InstructionBus)),
+            });
+        let i_w = match fetched {
+            Ok(addr) => self.read_word(addr),
+            Err(exception) => {
+                self.pc = self.enter_exception(exception, self.delay_slot);
+                return None;
+            }
+        };
         let first_stage = OpDecodedInstruction::decode(i_w);
-        decode_opcode(first_stage)
+        Some(decode_opcode(first_stage, self.isa))
     }
     #[inline]
     pub fn step_disassemble(&mut self) {
-        let branch = self.branch;
-        match self.generic_step() {
-            InstructionInfos::RType(i) => {
-                println!(
-                    "{:#04X?}: {} {},{},{}",
-                    self.pc,
-                    i.memonic,
-                    MIPS_REGISTER_NAMES[i.decoded_instruction.rd() as usize],
-                    MIPS_REGISTER_NAMES[i.decoded_instruction.rs() as usize],
-                    MIPS_REGISTER_NAMES[i.decoded_instruction.rt() as usize]
-                );
-                self.execute(i);
-            }
-            InstructionInfos::IType(i) => {
-                println!(
-                    "{:#04X?}: {} {},{},{}",
-                    self.pc,
-                    i.memonic,
-                    MIPS_REGISTER_NAMES[i.decoded_instruction.rt() as usize],
-                    MIPS_REGISTER_NAMES[i.decoded_instruction.rs() as usize],
-                    i.decoded_instruction.immediate() as i16
-                );
-                self.execute(i);
-            }
-            InstructionInfos::JType(i) => {
-                println!(
-                    "{:#04X?}: {} {:#04X?}",
-                    self.pc,
-                    i.memonic,
-                    (i.decoded_instruction.target() << 2) as i32
-                );
-                self.execute(i);
-            }
+        if self.interrupt() {
+            println!("{:#04X?}: interrupt", self.pc);
+            return;
         }
-        match branch {
+        let instruction = match self.generic_step() {
+            Some(i) => i,
+            None => return,
+        };
+        println!("{:#04X?}: {}", self.pc, instruction);
+        self.execute_instruction(instruction);
+        self.advance_pc(instruction.is_branch());
+    }
+
+    #[inline]
+    pub fn step(&mut self) {
+        if self.interrupt() {
+            return;
+        }
+        let instruction = match self.generic_step() {
+            Some(i) => i,
+            None => return,
+        };
+        self.execute_instruction(instruction);
+        self.advance_pc(instruction.is_branch());
+    }
+
+    //is_branch is set if the executed instruction was a branch or jump, its successor is in the
+    //delay slot whether the branch was taken or not
+    #[inline]
+    fn advance_pc(&mut self, is_branch: bool) {
+        let delay_slot = std::mem::replace(&mut self.delay_slot, is_branch);
+        if let Some(exception) = self.exception.take() {
+            self.pc = self.enter_exception(exception, delay_slot);
+            return;
+        }
+        match delay_slot && self.branch {
             true => {
                 self.pc = self.branch_target;
                 self.branch = false;
             }
+            false if std::mem::take(&mut self.nullify) => {
+                self.pc += 8;
+                self.delay_slot = false;
+            }
             false => self.pc += 4,
         }
     }
 
+    //Advances the devices and Random by one clock and samples the interrupt lines into Cause
     #[inline]
-    pub fn step(&mut self) {
-        let branch = self.branch;
-        match self.generic_step() {
+    fn clock(&mut self) {
+        self.bus.tick();
+        self.cp0.mmu.tick();
+        let mut lines = self.bus.interrupt_lines();
+        if self.isa < isa::IsaLevel::Mips2 && self.cp1.interrupt() {
+            lines |= 1 << cp1::INTERRUPT_LINE;
+        }
+        self.cp0.set_interrupt_lines(lines);
+    }
+
+    //Takes a pending interrupt before the instruction at pc, returns whether it was taken.
+    //Without the pipeline there are no cycles, the devices advance once per instruction.
+    fn interrupt(&mut self) -> bool {
+        self.clock();
+        if !self.cp0.interrupt_pending() {
+            return false;
+        }
+        //the load of the previous instruction completes
+        if let Some((r, value)) = self.load_delay.pending.take() {
+            self.set_register(r, value);
+        }
+        self.pc = self.enter_exception(Exception::new(ExceptionCode::Interrupt), self.delay_slot);
+        true
+    }
+
+    //Physical address of an instruction, a pc that is not word aligned raises an address error
+    fn fetch_address(&self, pc: u32) -> Result<u32, Exception> {
+        if !pc.is_multiple_of(4) {
+            return Err(Exception::address_error(pc, false));
+        }
+        self.cp0.check_segment(pc, false)?;
+        self.cp0.mmu.translate(pc, mmu::Access::Fetch)
+    }
+
+    //Physical address of a load or store or the address error or TLB exception it raises
+    #[inline]
+    fn physical_address(&self, vaddr: u32, access: mmu::Access) -> Result<u32, Exception> {
+        self.cp0
+            .check_segment(vaddr, access == mmu::Access::Store)
+            .and_then(|_| self.cp0.mmu.translate(vaddr, access))
+    }
+
+    //Physical address of a load or store, None if the access raised an address error or a TLB
+    //exception
+    #[inline]
+    fn translate(&mut self, vaddr: u32, access: mmu::Access) -> Option<u32> {
+        match self.physical_address(vaddr, access) {
+            Ok(addr) => Some(addr),
+            Err(exception) => {
+                self.exception = Some(exception);
+                None
+            }
+        }
+    }
+
+    //Halfwords and words at physical addresses in the byte order of the program
+    #[inline]
+    fn read_word(&self, addr: u32) -> u32 {
+        self.endianness.word(self.bus.read_w(addr))
+    }
+
+    #[inline]
+    fn write_word(&mut self, addr: u32, value: u32) {
+        self.bus.write_w(addr, self.endianness.word(value));
+    }
+
+    #[inline]
+    fn read_halfword(&self, addr: u32) -> u16 {
+        self.endianness.halfword(self.bus.read_hw(addr))
+    }
+
+    #[inline]
+    fn write_halfword(&mut self, addr: u32, value: u16) {
+        self.bus.write_hw(addr, self.endianness.halfword(value));
+    }
+
+    #[inline]
+    pub fn set_mmu(&mut self, enabled: bool) {
+        self.cp0.mmu.enabled = enabled;
+    }
+
+    #[inline]
+    fn raise(&mut self, code: ExceptionCode) {
+        self.exception = Some(Exception::new(code));
+    }
+
+    //Delivers the exception of the instruction at pc and returns the address of its handler.
+    //An instruction in a branch delay slot restarts at the branch. Without coprocessor 0 the
+    //simulation stops at the instruction instead.
+    fn enter_exception(&mut self, exception: Exception, delay_slot: bool) -> u32 {
+        if !self.cp0.enabled {
+            self.fatal = Some(Box::new(UnhandledException {
+                code: exception.code,
+                pc: self.pc,
+                bad_vaddr: exception.bad_vaddr,
+            }));
+            return self.pc;
+        }
+        self.branch = false;
+        self.delay_slot = false;
+        self.ll_bit = false;
+        let epc = if delay_slot {
+            self.pc.wrapping_sub(4)
+        } else {
+            self.pc
+        };
+        self.cp0.enter(exception, epc, delay_slot)
+    }
+
+    //Executes the instruction at pc and retires the load of the previous instruction
+    fn execute_instruction(&mut self, instruction: InstructionInfos) {
+        if self.load_delay.mode == LoadDelay::Warn {
+            self.load_delay.check(self.pc, &instruction);
+        }
+        match instruction {
             InstructionInfos::RType(i) => self.execute(i),
             InstructionInfos::IType(i) => self.execute(i),
             InstructionInfos::JType(i) => self.execute(i),
         }
-        match branch {
-            true => {
-                self.pc = self.branch_target;
-                self.branch = false;
-            }
-            false => self.pc += 4,
+        if let Some((r, value)) = self.load_delay.pending.take() {
+            self.set_register(r, value);
         }
+        self.load_delay.pending = self.load_delay.issued.take();
+        self.load_delay.previous_load = match instruction.destination_register() {
+            r if r != 0 && instruction.is_load() => Some((self.pc, r)),
+            _ => None,
+        };
     }
 
     fn execute<T>(&mut self, i: InstructionInfo<T>) {
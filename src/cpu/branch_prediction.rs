@@ -0,0 +1,379 @@
+//Direction prediction for conditional branches, consulted when a branch is fetched.
+//Every predictor sees the same stream of branches, the active one steers the fetch and the
+//others are evaluated alongside so their accuracy can be compared on the same program.
+use std::fmt;
+use std::str::FromStr;
+
+//The tables of the dynamic predictors have 2^bits entries
+pub const MAX_TABLE_BITS: u32 = 24;
+
+pub trait BranchPredictor {
+    fn name(&self) -> &'static str;
+    //Direction of the conditional branch at pc jumping to target
+    fn predict(&self, pc: u32, target: u32) -> bool;
+    fn update(&mut self, pc: u32, taken: bool);
+}
+
+#[inline]
+fn table_index(pc: u32, mask: u32) -> usize {
+    ((pc >> 2) & mask) as usize
+}
+
+//2 bit saturating counter, taken from 2 upwards
+#[inline]
+fn count(counter: &mut u8, taken: bool) {
+    if taken {
+        *counter = (*counter + 1).min(3);
+    } else {
+        *counter = counter.saturating_sub(1);
+    }
+}
+
+pub struct StaticNotTaken;
+
+impl BranchPredictor for StaticNotTaken {
+    fn name(&self) -> &'static str {
+        "static not taken"
+    }
+    fn predict(&self, _pc: u32, _target: u32) -> bool {
+        false
+    }
+    fn update(&mut self, _pc: u32, _taken: bool) {}
+}
+
+//Backward branches usually close loops
+pub struct StaticBackwardTaken;
+
+impl BranchPredictor for StaticBackwardTaken {
+    fn name(&self) -> &'static str {
+        "static backward taken"
+    }
+    fn predict(&self, pc: u32, target: u32) -> bool {
+        target <= pc
+    }
+    fn update(&mut self, _pc: u32, _taken: bool) {}
+}
+
+//Remembers the last outcome of every branch
+pub struct OneBit {
+    table: Vec<bool>,
+    mask: u32,
+}
+
+impl OneBit {
+    pub fn new(bits: u32) -> Self {
+        OneBit {
+            table: vec![false; 1 << bits],
+            mask: (1 << bits) - 1,
+        }
+    }
+}
+
+impl BranchPredictor for OneBit {
+    fn name(&self) -> &'static str {
+        "1 bit"
+    }
+    fn predict(&self, pc: u32, _target: u32) -> bool {
+        self.table[table_index(pc, self.mask)]
+    }
+    fn update(&mut self, pc: u32, taken: bool) {
+        self.table[table_index(pc, self.mask)] = taken;
+    }
+}
+
+pub struct TwoBit {
+    counters: Vec<u8>,
+    mask: u32,
+}
+
+impl TwoBit {
+    pub fn new(bits: u32) -> Self {
+        TwoBit {
+            //weakly not taken
+            counters: vec![1; 1 << bits],
+            mask: (1 << bits) - 1,
+        }
+    }
+}
+
+impl BranchPredictor for TwoBit {
+    fn name(&self) -> &'static str {
+        "2 bit saturating"
+    }
+    fn predict(&self, pc: u32, _target: u32) -> bool {
+        self.counters[table_index(pc, self.mask)] >= 2
+    }
+    fn update(&mut self, pc: u32, taken: bool) {
+        count(&mut self.counters[table_index(pc, self.mask)], taken);
+    }
+}
+
+//2 bit counters indexed by the pc xor the global history of branch outcomes
+pub struct Gshare {
+    counters: Vec<u8>,
+    history: u32,
+    mask: u32,
+}
+
+impl Gshare {
+    pub fn new(bits: u32) -> Self {
+        Gshare {
+            counters: vec![1; 1 << bits],
+            history: 0,
+            mask: (1 << bits) - 1,
+        }
+    }
+
+    #[inline]
+    fn index(&self, pc: u32) -> usize {
+        table_index(pc ^ (self.history << 2), self.mask)
+    }
+}
+
+impl BranchPredictor for Gshare {
+    fn name(&self) -> &'static str {
+        "gshare"
+    }
+    fn predict(&self, pc: u32, _target: u32) -> bool {
+        self.counters[self.index(pc)] >= 2
+    }
+    fn update(&mut self, pc: u32, taken: bool) {
+        let index = self.index(pc);
+        count(&mut self.counters[index], taken);
+        self.history = ((self.history << 1) | taken as u32) & self.mask;
+    }
+}
+
+//Chooses per branch between a 2 bit and a gshare predictor, depending on which was right more often
+pub struct Tournament {
+    local: TwoBit,
+    global: Gshare,
+    //2 and 3 select the gshare predictor
+    chooser: Vec<u8>,
+    mask: u32,
+}
+
+impl Tournament {
+    pub fn new(bits: u32) -> Self {
+        Tournament {
+            local: TwoBit::new(bits),
+            global: Gshare::new(bits),
+            chooser: vec![1; 1 << bits],
+            mask: (1 << bits) - 1,
+        }
+    }
+}
+
+impl BranchPredictor for Tournament {
+    fn name(&self) -> &'static str {
+        "tournament"
+    }
+    fn predict(&self, pc: u32, target: u32) -> bool {
+        if self.chooser[table_index(pc, self.mask)] >= 2 {
+            self.global.predict(pc, target)
+        } else {
+            self.local.predict(pc, target)
+        }
+    }
+    fn update(&mut self, pc: u32, taken: bool) {
+        //the target doesn't matter for the dynamic predictors
+        let local_correct = self.local.predict(pc, 0) == taken;
+        let global_correct = self.global.predict(pc, 0) == taken;
+        if local_correct != global_correct {
+            count(
+                &mut self.chooser[table_index(pc, self.mask)],
+                global_correct,
+            );
+        }
+        self.local.update(pc, taken);
+        self.global.update(pc, taken);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum BranchPredictorKind {
+    #[default]
+    NotTaken,
+    BackwardTaken,
+    OneBit,
+    TwoBit,
+    Gshare,
+    Tournament,
+}
+
+const PREDICTOR_KINDS: [BranchPredictorKind; 6] = [
+    BranchPredictorKind::NotTaken,
+    BranchPredictorKind::BackwardTaken,
+    BranchPredictorKind::OneBit,
+    BranchPredictorKind::TwoBit,
+    BranchPredictorKind::Gshare,
+    BranchPredictorKind::Tournament,
+];
+
+impl BranchPredictorKind {
+    pub fn create(&self, bits: u32) -> Box<dyn BranchPredictor> {
+        match self {
+            BranchPredictorKind::NotTaken => Box::new(StaticNotTaken),
+            BranchPredictorKind::BackwardTaken => Box::new(StaticBackwardTaken),
+            BranchPredictorKind::OneBit => Box::new(OneBit::new(bits)),
+            BranchPredictorKind::TwoBit => Box::new(TwoBit::new(bits)),
+            BranchPredictorKind::Gshare => Box::new(Gshare::new(bits)),
+            BranchPredictorKind::Tournament => Box::new(Tournament::new(bits)),
+        }
+    }
+}
+
+impl FromStr for BranchPredictorKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nottaken" => Ok(BranchPredictorKind::NotTaken),
+            "backward" => Ok(BranchPredictorKind::BackwardTaken),
+            "1bit" => Ok(BranchPredictorKind::OneBit),
+            "2bit" => Ok(BranchPredictorKind::TwoBit),
+            "gshare" => Ok(BranchPredictorKind::Gshare),
+            "tournament" => Ok(BranchPredictorKind::Tournament),
+            _ => Err(format!("Unknown branch predictor {}", s)),
+        }
+    }
+}
+
+#[derive(Default, Clone, Copy, Debug)]
+pub struct PredictionStatistics {
+    pub correct: u64,
+    pub total: u64,
+}
+
+pub struct BranchPredictionUnit {
+    predictors: Vec<Box<dyn BranchPredictor>>,
+    statistics: Vec<PredictionStatistics>,
+    //Index of the predictor steering the fetch
+    active: usize,
+}
+
+impl Default for BranchPredictionUnit {
+    fn default() -> Self {
+        BranchPredictionUnit::new(BranchPredictorKind::default(), 10)
+    }
+}
+
+impl BranchPredictionUnit {
+    //bits is the number of pc bits indexing the tables of the dynamic predictors
+    pub fn new(active: BranchPredictorKind, bits: u32) -> Self {
+        BranchPredictionUnit {
+            predictors: PREDICTOR_KINDS.iter().map(|k| k.create(bits)).collect(),
+            statistics: vec![PredictionStatistics::default(); PREDICTOR_KINDS.len()],
+            active: PREDICTOR_KINDS.iter().position(|k| *k == active).unwrap(),
+        }
+    }
+
+    //Predictions of all predictors, bit n holds the one of predictor n
+    pub fn predict(&self, pc: u32, target: u32) -> u8 {
+        self.predictors
+            .iter()
+            .enumerate()
+            .fold(0, |m, (n, p)| m | ((p.predict(pc, target) as u8) << n))
+    }
+
+    #[inline]
+    pub fn taken(&self, predictions: u8) -> bool {
+        predictions & (1 << self.active) != 0
+    }
+
+    pub fn resolve(&mut self, pc: u32, predictions: u8, taken: bool) {
+        for (n, (p, s)) in self
+            .predictors
+            .iter_mut()
+            .zip(self.statistics.iter_mut())
+            .enumerate()
+        {
+            s.total += 1;
+            if (predictions & (1 << n) != 0) == taken {
+                s.correct += 1;
+            }
+            p.update(pc, taken);
+        }
+    }
+
+    #[inline]
+    pub fn active_statistics(&self) -> PredictionStatistics {
+        self.statistics[self.active]
+    }
+}
+
+impl fmt::Display for BranchPredictionUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Branch predictor accuracy:")?;
+        for (n, (p, s)) in self
+            .predictors
+            .iter()
+            .zip(self.statistics.iter())
+            .enumerate()
+        {
+            writeln!(
+                f,
+                "{} {:<22}{} of {} ({:.1}%)",
+                if n == self.active { '>' } else { ' ' },
+                p.name(),
+                s.correct,
+                s.total,
+                100.0 * s.correct as f64 / s.total.max(1) as f64
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    //Loop branch taken 3 times and then falling through, repeated
+    fn accuracy(mut p: Box<dyn BranchPredictor>) -> u64 {
+        let mut correct = 0;
+        for _ in 0..10 {
+            for taken in [true, true, true, false].iter() {
+                if p.predict(0x100, 0xF0) == *taken {
+                    correct += 1;
+                }
+                p.update(0x100, *taken);
+            }
+        }
+        correct
+    }
+
+    #[test]
+    fn loop_branch() {
+        assert_eq!(accuracy(BranchPredictorKind::NotTaken.create(4)), 10);
+        assert_eq!(accuracy(BranchPredictorKind::BackwardTaken.create(4)), 30);
+        //mispredicts the first and last iteration of every repetition
+        assert_eq!(accuracy(BranchPredictorKind::OneBit.create(4)), 20);
+        assert_eq!(accuracy(BranchPredictorKind::TwoBit.create(4)), 29);
+        //learns the pattern through the history
+        assert!(accuracy(BranchPredictorKind::Gshare.create(4)) > 30);
+    }
+
+    #[test]
+    fn tournament_chooser_switches_to_gshare() {
+        //alternating outcomes defeat the 2 bit counter but not the history of gshare
+        let mut p = Tournament::new(4);
+        let index = table_index(0x100, p.mask);
+        let mut taken = true;
+        assert!(p.chooser[index] < 2);
+        for _ in 0..16 {
+            p.update(0x100, taken);
+            taken = !taken;
+        }
+        assert!(
+            p.chooser[index] >= 2,
+            "Chooser still selects the 2 bit counter"
+        );
+        for _ in 0..8 {
+            assert_eq!(p.predict(0x100, 0xF0), p.global.predict(0x100, 0xF0));
+            assert_eq!(p.predict(0x100, 0xF0), taken);
+            p.update(0x100, taken);
+            taken = !taken;
+        }
+    }
+}
@@ -0,0 +1,209 @@
+//Target prediction for the branches found at fetch
+use std::fmt;
+
+//Direct mapped and tagged with the full branch address.
+//Without entries the targets of direct branches are computed when they are fetched and
+//indirect jumps are never predicted.
+#[derive(Default)]
+pub struct BranchTargetBuffer {
+    entries: Vec<Option<(u32, u32)>>,
+    pub lookups: u64,
+    pub hits: u64,
+}
+
+impl BranchTargetBuffer {
+    pub fn new(entries: usize) -> Self {
+        BranchTargetBuffer {
+            entries: vec![None; entries],
+            ..Default::default()
+        }
+    }
+
+    #[inline]
+    pub fn enabled(&self) -> bool {
+        !self.entries.is_empty()
+    }
+
+    #[inline]
+    fn index(&self, pc: u32) -> usize {
+        (pc >> 2) as usize % self.entries.len()
+    }
+
+    //direct is the target of a branch that doesn't depend on a register
+    pub fn predict(&mut self, pc: u32, direct: Option<u32>) -> Option<u32> {
+        if !self.enabled() {
+            return direct;
+        }
+        self.lookups += 1;
+        match self.entries[self.index(pc)] {
+            Some((tag, target)) if tag == pc => {
+                self.hits += 1;
+                Some(target)
+            }
+            _ => None,
+        }
+    }
+
+    //Records the target of a taken branch
+    pub fn update(&mut self, pc: u32, target: u32) {
+        if self.enabled() {
+            let index = self.index(pc);
+            self.entries[index] = Some((pc, target));
+        }
+    }
+}
+
+impl fmt::Display for BranchTargetBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "BTB hits: {} of {} lookups ({:.1}%)",
+            self.hits,
+            self.lookups,
+            100.0 * self.hits as f64 / self.lookups.max(1) as f64
+        )
+    }
+}
+
+//Position and entry of the top of the return address stack before an instruction was fetched
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct RasCheckpoint {
+    top: usize,
+    depth: usize,
+    address: u32,
+}
+
+//Return addresses pushed by calls when they are fetched and popped by jr $ra.
+//The oldest address is overwritten when the stack is full. Pops only move the top, so restoring
+//a checkpoint undoes the pushes and pops of squashed wrong path instructions.
+#[derive(Default)]
+pub struct ReturnAddressStack {
+    //Circular buffer, top is the index of the latest push
+    entries: Vec<u32>,
+    top: usize,
+    depth: usize,
+    pub overflows: u64,
+    pub predictions: u64,
+    pub correct: u64,
+}
+
+impl ReturnAddressStack {
+    pub fn new(capacity: usize) -> Self {
+        ReturnAddressStack {
+            entries: vec![0; capacity],
+            ..Default::default()
+        }
+    }
+
+    pub fn push(&mut self, return_address: u32) {
+        let capacity = self.entries.len();
+        if capacity == 0 {
+            return;
+        }
+        if self.depth == capacity {
+            self.overflows += 1;
+        } else {
+            self.depth += 1;
+        }
+        self.top = (self.top + 1) % capacity;
+        self.entries[self.top] = return_address;
+    }
+
+    pub fn pop(&mut self) -> Option<u32> {
+        if self.depth == 0 {
+            return None;
+        }
+        let address = self.entries[self.top];
+        self.top = (self.top + self.entries.len() - 1) % self.entries.len();
+        self.depth -= 1;
+        Some(address)
+    }
+
+    #[inline]
+    pub fn checkpoint(&self) -> RasCheckpoint {
+        RasCheckpoint {
+            top: self.top,
+            depth: self.depth,
+            address: self.entries.get(self.top).copied().unwrap_or(0),
+        }
+    }
+
+    //A wrong path push may have overwritten the top entry, it is restored as well
+    pub fn restore(&mut self, checkpoint: RasCheckpoint) {
+        if self.entries.is_empty() {
+            return;
+        }
+        self.top = checkpoint.top;
+        self.depth = checkpoint.depth;
+        self.entries[self.top] = checkpoint.address;
+    }
+
+    //Compares a popped address with the target the return actually jumped to
+    pub fn resolve(&mut self, predicted: u32, target: u32) {
+        self.predictions += 1;
+        if predicted == target {
+            self.correct += 1;
+        }
+    }
+}
+
+impl fmt::Display for ReturnAddressStack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Return address stack: {} of {} returns predicted correctly ({:.1}%), {} overflows",
+            self.correct,
+            self.predictions,
+            100.0 * self.correct as f64 / self.predictions.max(1) as f64,
+            self.overflows
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn btb_replaces_conflicting_branch() {
+        let mut btb = BranchTargetBuffer::new(4);
+        assert_eq!(btb.predict(0x100, Some(0x200)), None);
+        btb.update(0x100, 0x200);
+        assert_eq!(btb.predict(0x100, Some(0x200)), Some(0x200));
+        //same index, different tag
+        btb.update(0x110, 0x300);
+        assert_eq!(btb.predict(0x100, Some(0x200)), None);
+        assert_eq!((btb.hits, btb.lookups), (1, 3));
+        assert_eq!(
+            BranchTargetBuffer::default().predict(0x100, Some(0x200)),
+            Some(0x200)
+        );
+    }
+
+    #[test]
+    fn ras_overwrites_oldest() {
+        let mut ras = ReturnAddressStack::new(2);
+        for a in [8, 16, 24].iter() {
+            ras.push(*a);
+        }
+        assert_eq!(ras.pop(), Some(24));
+        assert_eq!(ras.pop(), Some(16));
+        assert_eq!(ras.pop(), None);
+        assert_eq!(ras.overflows, 1);
+    }
+
+    #[test]
+    fn ras_restores_checkpoint() {
+        let mut ras = ReturnAddressStack::new(4);
+        ras.push(8);
+        ras.push(16);
+        let checkpoint = ras.checkpoint();
+        //wrong path return and call
+        assert_eq!(ras.pop(), Some(16));
+        ras.push(40);
+        ras.restore(checkpoint);
+        assert_eq!(ras.pop(), Some(16));
+        assert_eq!(ras.pop(), Some(8));
+        assert_eq!(ras.pop(), None);
+    }
+}
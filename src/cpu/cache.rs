@@ -0,0 +1,346 @@
+//Timing model of a set associative cache between the pipeline and the bus.
+//Only tags are kept, the data always lives on the bus, so a cache never changes what a program
+//computes, just how many cycles the memory accesses take.
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Replacement {
+    Lru,
+    Fifo,
+    Random,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum WritePolicy {
+    WriteThrough,
+    WriteBack,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct CacheConfig {
+    //Capacity and line size in bytes
+    pub size: u32,
+    pub line_size: u32,
+    pub associativity: u32,
+    pub replacement: Replacement,
+    pub write_policy: WritePolicy,
+    //Store misses load the line into the cache
+    pub write_allocate: bool,
+}
+
+impl CacheConfig {
+    #[inline]
+    fn sets(&self) -> u32 {
+        self.size / (self.line_size * self.associativity)
+    }
+}
+
+//Parses SIZE,LINE,WAYS[,lru|fifo|random[,wb|wt[,alloc|noalloc]]], by default the cache replaces the
+//least recently used line and is write back with write allocate
+impl FromStr for CacheConfig {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = s.split(',').collect();
+        if fields.len() < 3 || fields.len() > 6 {
+            return Err(format!("Invalid cache configuration {}", s));
+        }
+        let number = |x: &str| {
+            x.parse::<u32>()
+                .ok()
+                .filter(|n| n.is_power_of_two())
+                .ok_or_else(|| format!("{} is not a power of two", x))
+        };
+        let config = CacheConfig {
+            size: number(fields[0])?,
+            line_size: number(fields[1])?,
+            associativity: number(fields[2])?,
+            replacement: match fields.get(3) {
+                None | Some(&"lru") => Replacement::Lru,
+                Some(&"fifo") => Replacement::Fifo,
+                Some(&"random") => Replacement::Random,
+                Some(x) => return Err(format!("Unknown replacement policy {}", x)),
+            },
+            write_policy: match fields.get(4) {
+                None | Some(&"wb") => WritePolicy::WriteBack,
+                Some(&"wt") => WritePolicy::WriteThrough,
+                Some(x) => return Err(format!("Unknown write policy {}", x)),
+            },
+            write_allocate: match fields.get(5) {
+                None | Some(&"alloc") => true,
+                Some(&"noalloc") => false,
+                Some(x) => return Err(format!("Unknown write miss policy {}", x)),
+            },
+        };
+        //a set has to fit into the cache, large powers of two overflow
+        match config.line_size.checked_mul(config.associativity) {
+            Some(set_size) if config.line_size >= 4 && config.size >= set_size => Ok(config),
+            _ => Err(format!("Invalid cache geometry {}", s)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct Line {
+    valid: bool,
+    dirty: bool,
+    tag: u32,
+    //Access counter values of the last use and of the fill
+    used: u64,
+    filled: u64,
+}
+
+//Bus transactions an access caused, each one costs the miss penalty
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct MemoryTraffic {
+    //Lines read from memory
+    pub line_fills: u32,
+    //Dirty lines written back on eviction
+    pub write_backs: u32,
+    //Words written through to memory
+    pub word_writes: u32,
+    //Uncached words read from memory
+    pub word_reads: u32,
+}
+
+#[derive(Default, Clone, Copy, Debug)]
+pub struct CacheStatistics {
+    pub read_hits: u64,
+    pub read_misses: u64,
+    pub write_hits: u64,
+    pub write_misses: u64,
+    pub write_backs: u64,
+}
+
+pub struct Cache {
+    name: &'static str,
+    config: CacheConfig,
+    lines: Vec<Line>,
+    accesses: u64,
+    //xorshift state for random replacement
+    random: u32,
+    pub statistics: CacheStatistics,
+}
+
+impl Cache {
+    pub fn new(name: &'static str, config: CacheConfig) -> Self {
+        Cache {
+            name,
+            config,
+            lines: vec![Line::default(); (config.sets() * config.associativity) as usize],
+            accesses: 0,
+            random: 0x2545_F491,
+            statistics: CacheStatistics::default(),
+        }
+    }
+
+    //Ways of the set holding addr and the tag of addr
+    fn set(&mut self, addr: u32) -> (&mut [Line], u32) {
+        let line = addr / self.config.line_size;
+        let sets = self.config.sets();
+        let ways = self.config.associativity as usize;
+        let start = (line % sets) as usize * ways;
+        (&mut self.lines[start..start + ways], line / sets)
+    }
+
+    //Looks up addr and marks the line as used, returns whether it hit
+    fn lookup(&mut self, addr: u32, write: bool) -> bool {
+        self.accesses += 1;
+        let accesses = self.accesses;
+        let write_back = self.config.write_policy == WritePolicy::WriteBack;
+        let (set, tag) = self.set(addr);
+        match set.iter_mut().find(|l| l.valid && l.tag == tag) {
+            Some(line) => {
+                line.used = accesses;
+                line.dirty |= write && write_back;
+                true
+            }
+            None => false,
+        }
+    }
+
+    //Loads the line of addr into its set, evicting a line if the set is full
+    fn fill(&mut self, addr: u32, write: bool) -> MemoryTraffic {
+        self.random ^= self.random << 13;
+        self.random ^= self.random >> 17;
+        self.random ^= self.random << 5;
+        let random = self.random as usize;
+        let accesses = self.accesses;
+        let replacement = self.config.replacement;
+        let dirty = write && self.config.write_policy == WritePolicy::WriteBack;
+        let (set, tag) = self.set(addr);
+        let victim = match set.iter().position(|l| !l.valid) {
+            Some(way) => way,
+            None => match replacement {
+                Replacement::Lru => (0..set.len()).min_by_key(|w| set[*w].used).unwrap(),
+                Replacement::Fifo => (0..set.len()).min_by_key(|w| set[*w].filled).unwrap(),
+                Replacement::Random => random % set.len(),
+            },
+        };
+        let write_backs = (set[victim].valid && set[victim].dirty) as u32;
+        set[victim] = Line {
+            valid: true,
+            dirty,
+            tag,
+            used: accesses,
+            filled: accesses,
+        };
+        self.statistics.write_backs += write_backs as u64;
+        MemoryTraffic {
+            line_fills: 1,
+            write_backs,
+            word_writes: 0,
+            word_reads: 0,
+        }
+    }
+
+    pub fn read(&mut self, addr: u32) -> MemoryTraffic {
+        if self.lookup(addr, false) {
+            self.statistics.read_hits += 1;
+            MemoryTraffic::default()
+        } else {
+            self.statistics.read_misses += 1;
+            self.fill(addr, false)
+        }
+    }
+
+    pub fn write(&mut self, addr: u32) -> MemoryTraffic {
+        let mut traffic = if self.lookup(addr, true) {
+            self.statistics.write_hits += 1;
+            MemoryTraffic::default()
+        } else {
+            self.statistics.write_misses += 1;
+            if self.config.write_allocate {
+                self.fill(addr, true)
+            } else {
+                //the word goes around the cache straight to memory
+                return MemoryTraffic {
+                    word_writes: 1,
+                    ..Default::default()
+                };
+            }
+        };
+        if self.config.write_policy == WritePolicy::WriteThrough {
+            traffic.word_writes = 1;
+        }
+        traffic
+    }
+}
+
+impl fmt::Display for Cache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = &self.statistics;
+        let accesses = s.read_hits + s.read_misses + s.write_hits + s.write_misses;
+        writeln!(
+            f,
+            "{}: {} bytes, {} byte lines, {} way, {:?}, {:?}{}",
+            self.name,
+            self.config.size,
+            self.config.line_size,
+            self.config.associativity,
+            self.config.replacement,
+            self.config.write_policy,
+            if self.config.write_allocate {
+                ", write allocate"
+            } else {
+                ", no write allocate"
+            }
+        )?;
+        writeln!(f, "  Reads: {} hits, {} misses", s.read_hits, s.read_misses)?;
+        writeln!(
+            f,
+            "  Writes: {} hits, {} misses",
+            s.write_hits, s.write_misses
+        )?;
+        writeln!(
+            f,
+            "  Hit rate: {:.1}%, write backs: {}",
+            100.0 * (s.read_hits + s.write_hits) as f64 / accesses.max(1) as f64,
+            s.write_backs
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_config() {
+        assert_eq!(
+            "4096,16,2".parse::<CacheConfig>(),
+            Ok(CacheConfig {
+                size: 4096,
+                line_size: 16,
+                associativity: 2,
+                replacement: Replacement::Lru,
+                write_policy: WritePolicy::WriteBack,
+                write_allocate: true,
+            })
+        );
+        let c = "1024,32,1,fifo,wt,noalloc".parse::<CacheConfig>().unwrap();
+        assert_eq!(c.replacement, Replacement::Fifo);
+        assert_eq!(c.write_policy, WritePolicy::WriteThrough);
+        assert!(!c.write_allocate);
+        assert!("1000,16,2".parse::<CacheConfig>().is_err());
+        assert!("16,16,2".parse::<CacheConfig>().is_err());
+        assert_eq!(
+            "4096,65536,65536".parse::<CacheConfig>(),
+            Err(String::from("Invalid cache geometry 4096,65536,65536"))
+        );
+    }
+
+    #[test]
+    fn lru_and_fifo_eviction() {
+        //one set with two ways
+        for (replacement, hit) in [(Replacement::Lru, true), (Replacement::Fifo, false)].iter() {
+            let mut c = Cache::new(
+                "test",
+                CacheConfig {
+                    size: 32,
+                    line_size: 16,
+                    associativity: 2,
+                    replacement: *replacement,
+                    write_policy: WritePolicy::WriteBack,
+                    write_allocate: true,
+                },
+            );
+            c.read(0x00);
+            c.read(0x10);
+            c.read(0x04);
+            //evicts 0x10 with LRU and 0x00 with FIFO
+            c.read(0x20);
+            assert_eq!(c.read(0x00) == MemoryTraffic::default(), *hit);
+        }
+    }
+
+    #[test]
+    fn write_policies() {
+        let config = "32,16,1".parse::<CacheConfig>().unwrap();
+        let mut wb = Cache::new("wb", config);
+        assert_eq!(wb.write(0x00).line_fills, 1);
+        //the dirty line is written back when 0x20 replaces it
+        assert_eq!(wb.read(0x20).write_backs, 1);
+
+        let mut wt = Cache::new(
+            "wt",
+            CacheConfig {
+                write_policy: WritePolicy::WriteThrough,
+                write_allocate: false,
+                ..config
+            },
+        );
+        assert_eq!(
+            wt.write(0x00),
+            MemoryTraffic {
+                word_writes: 1,
+                ..Default::default()
+            }
+        );
+        assert_eq!(wt.statistics.write_misses, 1);
+        assert_eq!(wt.read(0x00).line_fills, 1);
+        assert_eq!(wt.write(0x00).word_writes, 1);
+        assert_eq!(wt.read(0x20).write_backs, 0);
+    }
+}
@@ -0,0 +1,312 @@
+//System control coprocessor: the registers of the exception model of the R3000
+use super::mmu::*;
+use std::error::Error;
+use std::fmt;
+
+pub const BAD_VADDR: u8 = 8;
+pub const STATUS: u8 = 12;
+pub const CAUSE: u8 = 13;
+pub const EPC: u8 = 14;
+pub const PRID: u8 = 15;
+
+//Implementation 2 (R3000), revision 0
+const R3000_PRID: u32 = 0x0000_0200;
+
+//Status bits
+pub const STATUS_BEV: u32 = 1 << 22;
+pub const STATUS_CU0: u32 = 1 << 28;
+pub const STATUS_CU1: u32 = 1 << 29;
+//Set while the processor runs in user mode
+const STATUS_KUC: u32 = 1 << 1;
+//CU3-0, RE, BEV, IM and the three interrupt enable/kernel user pairs
+const STATUS_WRITABLE: u32 = 0xF240_FF3F;
+
+//Cause bits
+const CAUSE_BD: u32 = 1 << 31;
+//IP7-IP2 follow the six hardware interrupt lines
+const CAUSE_HARDWARE_INTERRUPTS: u32 = 0x0000_FC00;
+//Only the two software interrupts can be written
+const CAUSE_WRITABLE: u32 = 0x0000_0300;
+
+const UTLB_VECTOR: u32 = 0x8000_0000;
+const GENERAL_VECTOR: u32 = 0x8000_0080;
+const BOOT_UTLB_VECTOR: u32 = 0xBFC0_0100;
+const BOOT_GENERAL_VECTOR: u32 = 0xBFC0_0180;
+
+//ExcCode field of Cause
+#[allow(dead_code)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ExceptionCode {
+    Interrupt = 0,
+    TlbModified = 1,
+    TlbLoad = 2,
+    TlbStore = 3,
+    AddressLoad = 4,
+    AddressStore = 5,
+    InstructionBus = 6,
+    DataBus = 7,
+    Syscall = 8,
+    Breakpoint = 9,
+    ReservedInstruction = 10,
+    CoprocessorUnusable = 11,
+    Overflow = 12,
+    //MIPS II trap instructions
+    Trap = 13,
+    //MIPS II and later, the R3010 of an R3000 interrupts instead
+    FloatingPoint = 15,
+}
+
+//Mnemonics of the R3000 manual
+impl fmt::Display for ExceptionCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ExceptionCode::Interrupt => "Int",
+            ExceptionCode::TlbModified => "Mod",
+            ExceptionCode::TlbLoad => "TLBL",
+            ExceptionCode::TlbStore => "TLBS",
+            ExceptionCode::AddressLoad => "AdEL",
+            ExceptionCode::AddressStore => "AdES",
+            ExceptionCode::InstructionBus => "IBE",
+            ExceptionCode::DataBus => "DBE",
+            ExceptionCode::Syscall => "Sys",
+            ExceptionCode::Breakpoint => "Bp",
+            ExceptionCode::ReservedInstruction => "RI",
+            ExceptionCode::CoprocessorUnusable => "CpU",
+            ExceptionCode::Overflow => "Ov",
+            ExceptionCode::Trap => "Tr",
+            ExceptionCode::FloatingPoint => "FPE",
+        })
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Exception {
+    pub code: ExceptionCode,
+    //Address loaded into BadVAddr by address errors
+    pub bad_vaddr: Option<u32>,
+    //Coprocessor number of a coprocessor unusable exception
+    pub coprocessor: u8,
+    //TLB miss in kuseg, handled at the UTLB vector
+    pub tlb_refill: bool,
+}
+
+impl Exception {
+    pub fn new(code: ExceptionCode) -> Self {
+        Exception {
+            code,
+            bad_vaddr: None,
+            coprocessor: 0,
+            tlb_refill: false,
+        }
+    }
+
+    //AdEL or AdES for a misaligned access or fetch
+    pub fn address_error(addr: u32, store: bool) -> Self {
+        let code = match store {
+            true => ExceptionCode::AddressStore,
+            false => ExceptionCode::AddressLoad,
+        };
+        Exception {
+            bad_vaddr: Some(addr),
+            ..Exception::new(code)
+        }
+    }
+}
+
+//An exception without coprocessor 0 to deliver it to, it stops the simulation
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct UnhandledException {
+    pub code: ExceptionCode,
+    pub pc: u32,
+    pub bad_vaddr: Option<u32>,
+}
+
+impl fmt::Display for UnhandledException {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Unhandled {} exception at {:#010X?}", self.code, self.pc)?;
+        if let Some(addr) = self.bad_vaddr {
+            write!(f, " accessing {:#010X?}", addr)?;
+        }
+        write!(
+            f,
+            ", enable coprocessor 0 with --cp0 to deliver it to the program"
+        )
+    }
+}
+
+impl Error for UnhandledException {}
+
+//The loaded program starts in kernel mode with interrupts disabled and the exception vectors
+//in RAM, as the boot ROM would leave it
+#[derive(Default)]
+pub struct Cp0 {
+    //Exceptions are delivered to the program instead of stopping the simulation
+    pub enabled: bool,
+    pub status: u32,
+    pub cause: u32,
+    pub epc: u32,
+    pub bad_vaddr: u32,
+    pub exceptions: u64,
+    //Index, Random, EntryLo, Context and EntryHi belong to the TLB
+    pub mmu: Mmu,
+}
+
+impl Cp0 {
+    pub fn read(&self, register: u8) -> u32 {
+        match register {
+            BAD_VADDR => self.bad_vaddr,
+            STATUS => self.status,
+            CAUSE => self.cause,
+            EPC => self.epc,
+            PRID => R3000_PRID,
+            r => self.mmu.read(r),
+        }
+    }
+
+    pub fn write(&mut self, register: u8, value: u32) {
+        match register {
+            STATUS => self.status = value & STATUS_WRITABLE,
+            CAUSE => self.cause = (self.cause & !CAUSE_WRITABLE) | (value & CAUSE_WRITABLE),
+            //BadVAddr, EPC and PRId are read only
+            BAD_VADDR | EPC | PRID => (),
+            r => self.mmu.write(r, value),
+        }
+    }
+
+    //Records the exception of the instruction at epc and returns the address of the handler.
+    //The kernel/user and interrupt enable bits are pushed onto their three entry stack, the
+    //processor continues in kernel mode with interrupts disabled.
+    pub fn enter(&mut self, exception: Exception, epc: u32, delay_slot: bool) -> u32 {
+        self.exceptions += 1;
+        self.epc = epc;
+        if let Some(addr) = exception.bad_vaddr {
+            self.bad_vaddr = addr;
+            if matches!(
+                exception.code,
+                ExceptionCode::TlbModified | ExceptionCode::TlbLoad | ExceptionCode::TlbStore
+            ) {
+                self.mmu.record_fault(addr, exception.tlb_refill);
+            }
+        }
+        let interrupts = self.cause & 0x0000_FF00;
+        self.cause = interrupts
+            | ((exception.coprocessor as u32 & 3) << 28)
+            | ((exception.code as u32) << 2);
+        if delay_slot {
+            self.cause |= CAUSE_BD;
+        }
+        self.status = (self.status & !0x3F) | ((self.status << 2) & 0x3C);
+        match (self.status & STATUS_BEV != 0, exception.tlb_refill) {
+            (true, true) => BOOT_UTLB_VECTOR,
+            (true, false) => BOOT_GENERAL_VECTOR,
+            (false, true) => UTLB_VECTOR,
+            (false, false) => GENERAL_VECTOR,
+        }
+    }
+
+    //Samples the hardware interrupt lines into IP7-IP2
+    #[inline]
+    pub fn set_interrupt_lines(&mut self, lines: u8) {
+        self.cause = (self.cause & !CAUSE_HARDWARE_INTERRUPTS) | ((lines as u32 & 0x3F) << 10);
+    }
+
+    //A pending interrupt is taken if it is unmasked in IM and interrupts are enabled by IEc
+    #[inline]
+    pub fn interrupt_pending(&self) -> bool {
+        self.status & 1 != 0 && self.status & self.cause & 0x0000_FF00 != 0
+    }
+
+    #[inline]
+    pub fn user_mode(&self) -> bool {
+        self.status & STATUS_KUC != 0
+    }
+
+    //User mode may only address kuseg, the kernel segments raise an address error
+    pub fn check_segment(&self, vaddr: u32, store: bool) -> Result<(), Exception> {
+        match self.user_mode() && vaddr >= KSEG0 {
+            true => Err(Exception::address_error(vaddr, store)),
+            false => Ok(()),
+        }
+    }
+
+    //The kernel can always use coprocessor 0, user mode only if CU0 is set
+    #[inline]
+    pub fn cop0_usable(&self) -> bool {
+        !self.user_mode() || self.status & STATUS_CU0 != 0
+    }
+
+    //rfe pops the kernel/user and interrupt enable stack
+    pub fn restore_from_exception(&mut self) {
+        self.status = (self.status & !0x0F) | ((self.status >> 2) & 0x0F);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn exception_stack() {
+        let mut cp0 = Cp0::default();
+        //user mode with interrupts enabled
+        cp0.write(STATUS, 0b11);
+        let vector = cp0.enter(Exception::new(ExceptionCode::Syscall), 0x400010, true);
+        assert_eq!(vector, 0x8000_0080);
+        assert_eq!(cp0.read(STATUS) & 0x3F, 0b00_1100);
+        assert_eq!(cp0.read(CAUSE), CAUSE_BD | (8 << 2));
+        assert_eq!(cp0.read(EPC), 0x400010);
+        cp0.restore_from_exception();
+        assert_eq!(cp0.read(STATUS) & 0x3F, 0b00_0011);
+    }
+
+    #[test]
+    fn interrupt_mask() {
+        let mut cp0 = Cp0::default();
+        cp0.set_interrupt_lines(0b10_0001);
+        assert_eq!(cp0.read(CAUSE), 0x8400);
+        //IM7 set but IEc clear
+        cp0.write(STATUS, 0x8000);
+        assert!(!cp0.interrupt_pending());
+        cp0.write(STATUS, 0x8001);
+        assert!(cp0.interrupt_pending());
+        //the handler runs with interrupts disabled
+        cp0.enter(Exception::new(ExceptionCode::Interrupt), 0x400000, false);
+        assert!(!cp0.interrupt_pending());
+        assert_eq!(cp0.read(CAUSE), 0x8400);
+        cp0.restore_from_exception();
+        cp0.set_interrupt_lines(0);
+        assert!(!cp0.interrupt_pending());
+        //software interrupts are masked the same way
+        cp0.write(CAUSE, 0x100);
+        cp0.write(STATUS, 0x101);
+        assert!(cp0.interrupt_pending());
+    }
+
+    #[test]
+    fn user_mode_segments() {
+        let mut cp0 = Cp0::default();
+        assert_eq!(cp0.check_segment(0x8000_0000, false), Ok(()));
+        cp0.write(STATUS, STATUS_KUC);
+        assert_eq!(cp0.check_segment(0x7FFF_FFFC, true), Ok(()));
+        let error = cp0.check_segment(0xA000_0000, true).unwrap_err();
+        assert_eq!(error, Exception::address_error(0xA000_0000, true));
+        assert!(!cp0.cop0_usable());
+        cp0.write(STATUS, STATUS_KUC | STATUS_CU0);
+        assert!(cp0.cop0_usable());
+        //exceptions switch to kernel mode, rfe returns to user mode
+        cp0.enter(Exception::new(ExceptionCode::Syscall), 0x400000, false);
+        assert!(!cp0.user_mode());
+        cp0.restore_from_exception();
+        assert!(cp0.user_mode());
+    }
+
+    #[test]
+    fn read_only_registers() {
+        let mut cp0 = Cp0::default();
+        cp0.write(EPC, 0x1234);
+        cp0.write(CAUSE, 0xFFFF_FFFF);
+        assert_eq!(cp0.read(EPC), 0);
+        assert_eq!(cp0.read(CAUSE), 0x300);
+        assert_eq!(cp0.read(PRID), R3000_PRID);
+    }
+}
@@ -0,0 +1,522 @@
+//Floating point coprocessor: the registers of the R3010 and its IEEE 754 arithmetic.
+//The host computes every result rounded to nearest, the exact error of the operation tells in
+//which direction the other rounding modes have to adjust it and whether it was inexact.
+use std::cmp::Ordering;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+//Control registers
+pub const FIR: u8 = 0;
+pub const FCSR: u8 = 31;
+//Implementation 3 (R3010), revision 0
+const R3010_FIR: u32 = 0x0000_0300;
+
+//fmt field of the arithmetic instructions
+pub const FMT_S: u8 = 0x10;
+pub const FMT_D: u8 = 0x11;
+pub const FMT_W: u8 = 0x14;
+
+//FCR31 fields
+const FCSR_CONDITION: u32 = 1 << 23;
+const FCSR_WRITABLE: u32 = 0x0183_FFFF;
+const FLAGS_SHIFT: u32 = 2;
+const ENABLES_SHIFT: u32 = 7;
+const CAUSE_SHIFT: u32 = 12;
+
+//Exceptions as ordered in the flags, enables and cause fields
+pub const INEXACT: u32 = 1;
+pub const UNDERFLOW: u32 = 2;
+pub const OVERFLOW: u32 = 4;
+pub const DIVISION_BY_ZERO: u32 = 8;
+pub const INVALID: u32 = 16;
+
+//The R3010 has no exception code of its own before MIPS II, it signals enabled exceptions on a
+//hardware interrupt of the R3000, Int3 by convention
+pub const INTERRUPT_LINE: u8 = 3;
+
+//Default results of invalid operations, MIPS quiet NaNs have the top mantissa bit clear
+const DEFAULT_NAN_SINGLE: u32 = 0x7FBF_FFFF;
+const DEFAULT_NAN_DOUBLE: u64 = 0x7FF7_FFFF_FFFF_FFFF;
+//Result of converting NaN, infinity or an out of range value to a word
+const INVALID_WORD: u32 = 0x7FFF_FFFF;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RoundingMode {
+    Nearest,
+    Zero,
+    Up,
+    Down,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Operation {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+//Single and double precision values as stored in the registers
+pub trait Float:
+    Copy
+    + PartialOrd
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+{
+    const ZERO: Self;
+    const MAX: Self;
+    const MIN_POSITIVE: Self;
+    fn default_nan() -> Self;
+    fn is_nan(self) -> bool;
+    //MIPS signaling NaNs have the top mantissa bit set
+    fn is_signaling(self) -> bool;
+    fn is_finite(self) -> bool;
+    fn abs(self) -> Self;
+    //self * a + b rounded once
+    fn fma(self, a: Self, b: Self) -> Self;
+    fn next_up(self) -> Self;
+    fn next_down(self) -> Self;
+    //Mantissa in [1, 2) and exponent of a finite nonzero value
+    fn normalize(self) -> (Self, i32);
+    fn exp2(exponent: i32) -> Self;
+    fn read(cp1: &Cp1, register: u8) -> Self;
+    fn write(cp1: &mut Cp1, register: u8, value: Self);
+
+    //Multiplies by a power of two in steps that stay in range
+    fn scale(self, mut exponent: i32) -> Self {
+        let mut x = self;
+        while exponent != 0 {
+            let step = exponent.clamp(-64, 64);
+            x = x * Self::exp2(step);
+            exponent -= step;
+        }
+        x
+    }
+}
+
+impl Float for f32 {
+    const ZERO: Self = 0.0;
+    const MAX: Self = f32::MAX;
+    const MIN_POSITIVE: Self = f32::MIN_POSITIVE;
+    fn default_nan() -> Self {
+        f32::from_bits(DEFAULT_NAN_SINGLE)
+    }
+    fn is_nan(self) -> bool {
+        f32::is_nan(self)
+    }
+    fn is_signaling(self) -> bool {
+        self.is_nan() && self.to_bits() & (1 << 22) != 0
+    }
+    fn is_finite(self) -> bool {
+        f32::is_finite(self)
+    }
+    fn abs(self) -> Self {
+        f32::abs(self)
+    }
+    fn fma(self, a: Self, b: Self) -> Self {
+        self.mul_add(a, b)
+    }
+    fn next_up(self) -> Self {
+        f32::next_up(self)
+    }
+    fn next_down(self) -> Self {
+        f32::next_down(self)
+    }
+    fn normalize(self) -> (Self, i32) {
+        let (x, bias) = match self.abs() < f32::MIN_POSITIVE {
+            true => (self * 2f32.powi(32), 32),
+            false => (self, 0),
+        };
+        let exponent = ((x.to_bits() >> 23) & 0xFF) as i32 - 127;
+        let mantissa = f32::from_bits((x.to_bits() & !(0xFF << 23)) | (127 << 23));
+        (mantissa, exponent - bias)
+    }
+    fn exp2(exponent: i32) -> Self {
+        2f32.powi(exponent)
+    }
+    fn read(cp1: &Cp1, register: u8) -> Self {
+        f32::from_bits(cp1.read(register))
+    }
+    fn write(cp1: &mut Cp1, register: u8, value: Self) {
+        cp1.write(register, value.to_bits());
+    }
+}
+
+//Doubles occupy an even/odd register pair, the even register holds the low word
+impl Float for f64 {
+    const ZERO: Self = 0.0;
+    const MAX: Self = f64::MAX;
+    const MIN_POSITIVE: Self = f64::MIN_POSITIVE;
+    fn default_nan() -> Self {
+        f64::from_bits(DEFAULT_NAN_DOUBLE)
+    }
+    fn is_nan(self) -> bool {
+        f64::is_nan(self)
+    }
+    fn is_signaling(self) -> bool {
+        self.is_nan() && self.to_bits() & (1 << 51) != 0
+    }
+    fn is_finite(self) -> bool {
+        f64::is_finite(self)
+    }
+    fn abs(self) -> Self {
+        f64::abs(self)
+    }
+    fn fma(self, a: Self, b: Self) -> Self {
+        self.mul_add(a, b)
+    }
+    fn next_up(self) -> Self {
+        f64::next_up(self)
+    }
+    fn next_down(self) -> Self {
+        f64::next_down(self)
+    }
+    fn normalize(self) -> (Self, i32) {
+        let (x, bias) = match self.abs() < f64::MIN_POSITIVE {
+            true => (self * 2f64.powi(64), 64),
+            false => (self, 0),
+        };
+        let exponent = ((x.to_bits() >> 52) & 0x7FF) as i32 - 1023;
+        let mantissa = f64::from_bits((x.to_bits() & !(0x7FF << 52)) | (1023 << 52));
+        (mantissa, exponent - bias)
+    }
+    fn exp2(exponent: i32) -> Self {
+        2f64.powi(exponent)
+    }
+    fn read(cp1: &Cp1, register: u8) -> Self {
+        let r = register & !1;
+        f64::from_bits(((cp1.read(r + 1) as u64) << 32) | cp1.read(r) as u64)
+    }
+    fn write(cp1: &mut Cp1, register: u8, value: Self) {
+        let r = register & !1;
+        let bits = value.to_bits();
+        cp1.write(r, bits as u32);
+        cp1.write(r + 1, (bits >> 32) as u32);
+    }
+}
+
+//Adjusts the result rounded to nearest, exact tells how the exact result compares to it
+fn round<T: Float>(nearest: T, exact: Ordering, mode: RoundingMode) -> T {
+    match (mode, exact) {
+        (RoundingMode::Nearest, _) | (_, Ordering::Equal) => nearest,
+        (RoundingMode::Up, Ordering::Greater) => nearest.next_up(),
+        (RoundingMode::Down, Ordering::Less) => nearest.next_down(),
+        (RoundingMode::Zero, Ordering::Greater) if nearest < T::ZERO => nearest.next_up(),
+        (RoundingMode::Zero, Ordering::Less) if nearest > T::ZERO => nearest.next_down(),
+        _ => nearest,
+    }
+}
+
+//Rounds the result of finite operands and returns it with the exceptions it caused, an infinite
+//result rounded to nearest overflowed
+fn finish<T: Float>(nearest: T, exact: Ordering, mode: RoundingMode) -> (T, u32) {
+    if !nearest.is_finite() {
+        //the largest finite number is the result when rounding away from infinity
+        let result = match mode {
+            RoundingMode::Zero if nearest > T::ZERO => T::MAX,
+            RoundingMode::Zero => -T::MAX,
+            RoundingMode::Up if nearest < T::ZERO => -T::MAX,
+            RoundingMode::Down if nearest > T::ZERO => T::MAX,
+            _ => nearest,
+        };
+        return (result, OVERFLOW | INEXACT);
+    }
+    let result = round(nearest, exact, mode);
+    let mut exceptions = 0;
+    if exact != Ordering::Equal {
+        exceptions |= INEXACT;
+        if result.abs() < T::MIN_POSITIVE {
+            exceptions |= UNDERFLOW;
+        }
+        if !result.is_finite() {
+            exceptions |= OVERFLOW;
+        }
+    }
+    (result, exceptions)
+}
+
+//Sign of the exact result minus the rounded one
+#[inline]
+fn error_sign<T: Float>(error: T) -> Ordering {
+    error.partial_cmp(&T::ZERO).unwrap_or(Ordering::Equal)
+}
+
+//Quiet NaN operands propagate silently, signaling ones are invalid
+fn nan_operands<T: Float>(a: T, b: T) -> Option<(T, u32)> {
+    match a.is_signaling() || b.is_signaling() {
+        true => Some((T::default_nan(), INVALID)),
+        false if a.is_nan() || b.is_nan() => Some((T::default_nan(), 0)),
+        false => None,
+    }
+}
+
+pub fn arithmetic<T: Float>(operation: Operation, a: T, b: T, mode: RoundingMode) -> (T, u32) {
+    if let Some(result) = nan_operands(a, b) {
+        return result;
+    }
+    let nearest = match operation {
+        Operation::Add => a + b,
+        Operation::Sub => a - b,
+        Operation::Mul => a * b,
+        Operation::Div => a / b,
+    };
+    if nearest.is_nan() {
+        //inf - inf, 0 * inf, 0 / 0 or inf / inf
+        return (T::default_nan(), INVALID);
+    }
+    if !a.is_finite() || !b.is_finite() {
+        return (nearest, 0);
+    }
+    if operation == Operation::Div && b == T::ZERO {
+        return (nearest, DIVISION_BY_ZERO);
+    }
+    if !nearest.is_finite() {
+        return finish(nearest, Ordering::Equal, mode);
+    }
+    //the error of a tiny product or quotient can be below the smallest denormal, it is computed
+    //on the normalized operands instead
+    let tiny = nearest.abs() < T::MIN_POSITIVE && a != T::ZERO && b != T::ZERO;
+    let (ma, ea) = if tiny { a.normalize() } else { (a, 0) };
+    let (mb, eb) = if tiny { b.normalize() } else { (b, 0) };
+    let error = match operation {
+        //error free transformation of the sum
+        Operation::Add | Operation::Sub => {
+            let b = if operation == Operation::Sub { -b } else { b };
+            let b_virtual = nearest - a;
+            (a - (nearest - b_virtual)) + (b - b_virtual)
+        }
+        Operation::Mul => ma.fma(mb, -nearest.scale(-(ea + eb))),
+        //the remainder has the sign of the error times the sign of the divisor
+        Operation::Div => {
+            let remainder = (-nearest.scale(eb - ea)).fma(mb, ma);
+            if b < T::ZERO {
+                -remainder
+            } else {
+                remainder
+            }
+        }
+    };
+    finish(nearest, error_sign(error), mode)
+}
+
+//cvt.s.d
+pub fn double_to_single(x: f64, mode: RoundingMode) -> (f32, u32) {
+    if x.is_nan() {
+        let invalid = if x.is_signaling() { INVALID } else { 0 };
+        return (f32::default_nan(), invalid);
+    }
+    let nearest = x as f32;
+    if x.is_infinite() {
+        return (nearest, 0);
+    }
+    finish(nearest, error_sign(x - nearest as f64), mode)
+}
+
+//cvt.s.w, a word has more significant bits than a single
+pub fn word_to_single(w: i32, mode: RoundingMode) -> (f32, u32) {
+    let nearest = w as f32;
+    finish(nearest, error_sign(w as f64 - nearest as f64), mode)
+}
+
+//cvt.w.s and cvt.w.d
+pub fn to_word(x: f64, mode: RoundingMode) -> (u32, u32) {
+    let rounded = match mode {
+        RoundingMode::Nearest => x.round_ties_even(),
+        RoundingMode::Zero => x.trunc(),
+        RoundingMode::Up => x.ceil(),
+        RoundingMode::Down => x.floor(),
+    };
+    if !(i32::MIN as f64..=i32::MAX as f64).contains(&rounded) {
+        return (INVALID_WORD, INVALID);
+    }
+    let exceptions = if rounded != x { INEXACT } else { 0 };
+    (rounded as i32 as u32, exceptions)
+}
+
+//c.cond, the low 4 bits of funct select unordered, equal, less and signaling on NaN
+pub fn compare<T: Float>(a: T, b: T, condition: u8) -> (bool, u32) {
+    let unordered = a.is_nan() || b.is_nan();
+    let result = (condition & 1 != 0 && unordered)
+        || (condition & 2 != 0 && a == b)
+        || (condition & 4 != 0 && a < b);
+    let signaling = a.is_signaling() || b.is_signaling();
+    let exceptions = if signaling || (unordered && condition & 8 != 0) {
+        INVALID
+    } else {
+        0
+    };
+    (result, exceptions)
+}
+
+#[derive(Default)]
+pub struct Cp1 {
+    //An R3010 is attached to the processor
+    pub present: bool,
+    registers: [u32; 32],
+    fcsr: u32,
+    pub operations: u64,
+}
+
+impl Cp1 {
+    #[inline]
+    pub fn read(&self, register: u8) -> u32 {
+        self.registers[register as usize]
+    }
+
+    #[inline]
+    pub fn write(&mut self, register: u8, value: u32) {
+        self.registers[register as usize] = value;
+    }
+
+    pub fn read_control(&self, register: u8) -> u32 {
+        match register {
+            FIR => R3010_FIR,
+            FCSR => self.fcsr,
+            _ => 0,
+        }
+    }
+
+    pub fn write_control(&mut self, register: u8, value: u32) {
+        if register == FCSR {
+            self.fcsr = value & FCSR_WRITABLE;
+        }
+    }
+
+    pub fn rounding_mode(&self) -> RoundingMode {
+        match self.fcsr & 3 {
+            0 => RoundingMode::Nearest,
+            1 => RoundingMode::Zero,
+            2 => RoundingMode::Up,
+            _ => RoundingMode::Down,
+        }
+    }
+
+    #[inline]
+    pub fn condition(&self) -> bool {
+        self.fcsr & FCSR_CONDITION != 0
+    }
+
+    pub fn set_condition(&mut self, condition: bool) {
+        self.fcsr = (self.fcsr & !FCSR_CONDITION) | ((condition as u32) << 23);
+    }
+
+    //Records the exceptions of an operation in the cause field. Enabled ones trap and leave the
+    //destination unchanged, the others accumulate in the flags. Returns whether it traps.
+    pub fn signal(&mut self, exceptions: u32) -> bool {
+        self.operations += 1;
+        let enables = (self.fcsr >> ENABLES_SHIFT) & 0x1F;
+        self.fcsr = (self.fcsr & !(0x3F << CAUSE_SHIFT)) | (exceptions << CAUSE_SHIFT);
+        if exceptions & enables != 0 {
+            return true;
+        }
+        self.fcsr |= exceptions << FLAGS_SHIFT;
+        false
+    }
+
+    //An enabled exception is recorded in the cause field
+    #[inline]
+    pub fn interrupt(&self) -> bool {
+        let enables = (self.fcsr >> ENABLES_SHIFT) & 0x1F;
+        (self.fcsr >> CAUSE_SHIFT) & enables != 0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn rounding_modes() {
+        let third = |mode| arithmetic(Operation::Div, 1.0f32, 3.0, mode);
+        let (nearest, exceptions) = third(RoundingMode::Nearest);
+        assert_eq!(exceptions, INEXACT);
+        //1/3 rounds up to nearest as a single
+        assert_eq!(third(RoundingMode::Zero).0, nearest.next_down());
+        assert_eq!(third(RoundingMode::Down).0, nearest.next_down());
+        assert_eq!(third(RoundingMode::Up).0, nearest);
+        assert_eq!(
+            arithmetic(Operation::Div, -1.0f32, 3.0, RoundingMode::Zero).0,
+            (-1.0f32 / 3.0).next_up()
+        );
+        //and down as a double
+        assert_eq!(
+            arithmetic(Operation::Div, 1.0f64, 3.0, RoundingMode::Up).0,
+            (1.0f64 / 3.0).next_up()
+        );
+        assert_eq!(
+            arithmetic(Operation::Add, 1.0f64, 2.0, RoundingMode::Up),
+            (3.0, 0)
+        );
+        assert_eq!(to_word(2.5, RoundingMode::Nearest), (2, INEXACT));
+        assert_eq!(to_word(-2.5, RoundingMode::Down), (-3i32 as u32, INEXACT));
+        assert_eq!(to_word(3e9, RoundingMode::Zero), (INVALID_WORD, INVALID));
+    }
+
+    #[test]
+    fn exceptions() {
+        let mode = RoundingMode::Nearest;
+        assert_eq!(
+            arithmetic(Operation::Mul, f32::MAX, 2.0, mode),
+            (f32::INFINITY, OVERFLOW | INEXACT)
+        );
+        assert_eq!(
+            arithmetic(Operation::Mul, f32::MAX, 2.0, RoundingMode::Zero).0,
+            f32::MAX
+        );
+        assert_eq!(
+            arithmetic(Operation::Div, 1.0f64, 0.0, mode),
+            (f64::INFINITY, DIVISION_BY_ZERO)
+        );
+        let (nan, exceptions) = arithmetic(Operation::Sub, f64::INFINITY, f64::INFINITY, mode);
+        assert_eq!((nan.to_bits(), exceptions), (DEFAULT_NAN_DOUBLE, INVALID));
+        assert_eq!(
+            arithmetic(Operation::Mul, f32::MIN_POSITIVE, 0.1, mode).1,
+            UNDERFLOW | INEXACT
+        );
+        //the exact result is far below the smallest denormal
+        let smallest = f64::from_bits(1);
+        assert_eq!(
+            arithmetic(Operation::Mul, smallest, smallest, RoundingMode::Up),
+            (smallest, UNDERFLOW | INEXACT)
+        );
+        assert_eq!(
+            arithmetic(Operation::Div, -smallest, 3.0, RoundingMode::Down),
+            (-smallest, UNDERFLOW | INEXACT)
+        );
+        assert_eq!(compare(f32::NAN, 1.0, 0b1100), (false, INVALID));
+        assert_eq!(compare(1.0f64, 2.0, 0b0110), (true, 0));
+    }
+
+    #[test]
+    fn signaling_nan_operands() {
+        let mode = RoundingMode::Nearest;
+        let quiet = f32::default_nan();
+        let signaling = f32::from_bits(0x7FC0_0000);
+        assert_eq!(arithmetic(Operation::Add, quiet, 1.0, mode).1, 0);
+        let (nan, exceptions) = arithmetic(Operation::Mul, 1.0, signaling, mode);
+        assert_eq!((nan.to_bits(), exceptions), (DEFAULT_NAN_SINGLE, INVALID));
+        let signaling = f64::from_bits(0x7FF8_0000_0000_0000);
+        assert_eq!(double_to_single(signaling, mode).1, INVALID);
+        assert_eq!(double_to_single(f64::default_nan(), mode).1, 0);
+        //c.eq does not signal on quiet NaNs
+        assert_eq!(compare(signaling, 1.0, 0b0010), (false, INVALID));
+        assert_eq!(compare(f64::default_nan(), 1.0, 0b0010), (false, 0));
+    }
+
+    #[test]
+    fn trapping_leaves_flags_alone() {
+        let mut cp1 = Cp1::default();
+        //enable division by zero
+        cp1.write_control(FCSR, DIVISION_BY_ZERO << ENABLES_SHIFT);
+        assert!(!cp1.signal(INEXACT));
+        assert!(cp1.signal(DIVISION_BY_ZERO));
+        let fcsr = cp1.read_control(FCSR);
+        assert_eq!((fcsr >> CAUSE_SHIFT) & 0x3F, DIVISION_BY_ZERO);
+        assert_eq!((fcsr >> FLAGS_SHIFT) & 0x1F, INEXACT);
+        f64::write(&mut cp1, 2, 1.5);
+        assert_eq!(cp1.read(3), 0x3FF8_0000);
+        assert_eq!(f64::read(&cp1, 2), 1.5);
+    }
+}
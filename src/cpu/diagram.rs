@@ -0,0 +1,213 @@
+//Textbook pipeline occupancy chart: one row per instruction, one column per cycle
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+//Rows are only recorded up to this limit so a hot loop in the selection can't exhaust memory
+const MAX_ROWS: usize = 1000;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DiagramSelection {
+    //Every instruction fetched from an address in the inclusive range
+    Pc(u32, u32),
+    //Instructions by the inclusive range of their fetch order, starting at 0
+    Window(u64, u64),
+}
+
+impl DiagramSelection {
+    fn contains(&self, seq: u64, pc: u32) -> bool {
+        match *self {
+            DiagramSelection::Pc(start, end) => start <= pc && pc <= end,
+            DiagramSelection::Window(first, last) => first <= seq && seq <= last,
+        }
+    }
+}
+
+//Parses START-END with hexadecimal addresses for a pc range
+//and FIRST-LAST with decimal numbers for an instruction window
+impl FromStr for DiagramSelection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s
+            .split_once('-')
+            .ok_or_else(|| format!("Invalid diagram range {}", s))?;
+        if start.starts_with("0x") {
+            let parse = |x: &str| u32::from_str_radix(x.trim_start_matches("0x"), 16);
+            match (parse(start), parse(end)) {
+                (Ok(start), Ok(end)) => Ok(DiagramSelection::Pc(start, end)),
+                _ => Err(format!("Invalid pc range {}", s)),
+            }
+        } else {
+            match (start.parse(), end.parse()) {
+                (Ok(first), Ok(last)) => Ok(DiagramSelection::Window(first, last)),
+                _ => Err(format!("Invalid instruction window {}", s)),
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Stage {
+    Fetch,
+    Decode,
+    Execute,
+    Memory,
+    WriteBack,
+}
+
+impl Stage {
+    fn letter(&self) -> char {
+        match self {
+            Stage::Fetch => 'F',
+            Stage::Decode => 'D',
+            Stage::Execute => 'X',
+            Stage::Memory => 'M',
+            Stage::WriteBack => 'W',
+        }
+    }
+}
+
+struct DiagramRow {
+    pc: u32,
+    //Disassembly, empty until the instruction was decoded
+    text: String,
+    first_cycle: u64,
+    cells: Vec<char>,
+    last_stage: Option<Stage>,
+}
+
+pub struct PipelineDiagram {
+    selection: DiagramSelection,
+    rows: Vec<DiagramRow>,
+    //Row of every fetch sequence number that is recorded
+    index: HashMap<u64, usize>,
+}
+
+impl PipelineDiagram {
+    pub fn new(selection: DiagramSelection) -> Self {
+        PipelineDiagram {
+            selection,
+            rows: vec![],
+            index: HashMap::new(),
+        }
+    }
+
+    pub fn fetched(&mut self, seq: u64, pc: u32, cycle: u64) {
+        if self.rows.len() < MAX_ROWS && self.selection.contains(seq, pc) {
+            self.index.insert(seq, self.rows.len());
+            self.rows.push(DiagramRow {
+                pc,
+                text: String::new(),
+                first_cycle: cycle,
+                cells: vec![],
+                last_stage: None,
+            });
+        }
+    }
+
+    pub fn decoded(&mut self, seq: u64, text: impl FnOnce() -> String) {
+        if let Some(row) = self.index.get(&seq).copied().map(|r| &mut self.rows[r]) {
+            if row.text.is_empty() {
+                row.text = text();
+            }
+        }
+    }
+
+    //Marks the stage the instruction occupied in the cycle, a stage held for more than one cycle
+    //is a stall
+    pub fn occupied(&mut self, seq: u64, stage: Stage, cycle: u64) {
+        if let Some(row) = self.index.get(&seq).copied().map(|r| &mut self.rows[r]) {
+            let column = (cycle - row.first_cycle) as usize;
+            row.cells.resize(column, ' ');
+            row.cells.push(match row.last_stage {
+                Some(s) if s == stage => '*',
+                _ => stage.letter(),
+            });
+            row.last_stage = Some(stage);
+        }
+    }
+
+    //The instruction stays in the stage it occupied in the previous cycle
+    pub fn held(&mut self, seq: u64, cycle: u64) {
+        if let Some(stage) = self.index.get(&seq).and_then(|r| self.rows[*r].last_stage) {
+            self.occupied(seq, stage, cycle);
+        }
+    }
+}
+
+impl fmt::Display for PipelineDiagram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let first_cycle = match self.rows.iter().map(|r| r.first_cycle).min() {
+            Some(c) => c,
+            None => return writeln!(f, "No instruction in the diagram selection"),
+        };
+        let last_cycle = self
+            .rows
+            .iter()
+            .map(|r| r.first_cycle + r.cells.len() as u64)
+            .max()
+            .unwrap();
+        write!(f, "{:<12}{:<28}", "pc", "instruction")?;
+        for cycle in first_cycle..last_cycle {
+            write!(f, "{:>3}", cycle % 1000)?;
+        }
+        writeln!(f)?;
+        for row in &self.rows {
+            let text = if row.text.is_empty() {
+                "(flushed)"
+            } else {
+                &row.text
+            };
+            write!(f, "{:<#12X?}{:<28}", row.pc, text)?;
+            for _ in first_cycle..row.first_cycle {
+                write!(f, "   ")?;
+            }
+            for cell in &row.cells {
+                write!(f, "{:>3}", cell)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_selection() {
+        assert_eq!(
+            "0x400000-0x40001c".parse::<DiagramSelection>(),
+            Ok(DiagramSelection::Pc(0x400000, 0x40001C))
+        );
+        assert_eq!(
+            "100-150".parse::<DiagramSelection>(),
+            Ok(DiagramSelection::Window(100, 150))
+        );
+        assert!("100".parse::<DiagramSelection>().is_err());
+    }
+
+    #[test]
+    fn repeated_stage_is_stall() {
+        let mut d = PipelineDiagram::new(DiagramSelection::Window(0, 0));
+        d.fetched(0, 0, 1);
+        d.fetched(1, 4, 2);
+        for (cycle, stage) in [
+            Stage::Fetch,
+            Stage::Decode,
+            Stage::Decode,
+            Stage::Execute,
+            Stage::Memory,
+            Stage::WriteBack,
+        ]
+        .iter()
+        .enumerate()
+        {
+            d.occupied(0, *stage, cycle as u64 + 1);
+        }
+        assert_eq!(d.rows.len(), 1);
+        assert_eq!(d.rows[0].cells.iter().collect::<String>(), "FD*XMW");
+    }
+}
@@ -0,0 +1,106 @@
+//MIPS leaves HI and LO unpredictable after a division by zero or an overflowing signed division,
+//the divider of the R3000 deterministically produces the values below and never traps by itself.
+//Compilers guard divisions with a check that traps instead, which can be enabled here.
+use std::collections::HashSet;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum DivideByZero {
+    //Results of the R3000 divider
+    #[default]
+    Silent,
+    //Like Silent but reports the divisions by zero and the overflowing divisions
+    Warn,
+    //Divisions by zero and overflowing divisions raise the exception of the check emitted by
+    //compilers, Breakpoint for break before MIPS II and Trap for teq from MIPS II on
+    Trap,
+}
+
+impl FromStr for DivideByZero {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "silent" => Ok(DivideByZero::Silent),
+            "warn" => Ok(DivideByZero::Warn),
+            "trap" => Ok(DivideByZero::Trap),
+            _ => Err(format!("Unknown divide by zero mode {}", s)),
+        }
+    }
+}
+
+//LO and HI of div. A zero divisor gives -1 or 1 depending on the sign of the dividend with the
+//dividend as the remainder, -2^31 / -1 gives -2^31 with remainder 0.
+pub fn signed_division(x: i32, y: i32) -> (u32, u32) {
+    match (x, y) {
+        (x, 0) if x >= 0 => (u32::MAX, x as u32),
+        (x, 0) => (1, x as u32),
+        (i32::MIN, -1) => (i32::MIN as u32, 0),
+        (x, y) => ((x / y) as u32, (x % y) as u32),
+    }
+}
+
+//LO and HI of divu, a zero divisor gives all ones with the dividend as the remainder
+pub fn unsigned_division(x: u32, y: u32) -> (u32, u32) {
+    match y {
+        0 => (u32::MAX, x),
+        y => (x / y, x % y),
+    }
+}
+
+#[derive(Default)]
+pub struct DivisionCheck {
+    pub mode: DivideByZero,
+    //Divisions that have already been reported
+    warned: HashSet<u32>,
+    pub warnings: u64,
+}
+
+impl DivisionCheck {
+    //Reports the division at pc if its result is unpredictable, returns whether it traps
+    pub fn check(&mut self, pc: u32, memonic: &str, zero: bool, overflow: bool) -> bool {
+        if !zero && !overflow {
+            return false;
+        }
+        if self.mode == DivideByZero::Trap {
+            return true;
+        }
+        if self.mode == DivideByZero::Warn {
+            self.warnings += 1;
+            if self.warned.insert(pc) {
+                let reason = if zero { "divides by zero" } else { "overflows" };
+                println!("Warning: {:#04X?}: {} {}", pc, memonic, reason);
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn r3000_results() {
+        assert_eq!(signed_division(7, 0), (u32::MAX, 7));
+        assert_eq!(signed_division(-7, 0), (1, -7i32 as u32));
+        assert_eq!(signed_division(i32::MIN, -1), (0x8000_0000, 0));
+        assert_eq!(signed_division(-7, 2), (-3i32 as u32, -1i32 as u32));
+        assert_eq!(unsigned_division(7, 0), (u32::MAX, 7));
+        assert_eq!(unsigned_division(7, 2), (3, 1));
+    }
+
+    #[test]
+    fn trap_mode_checks_overflow() {
+        let mut check = DivisionCheck {
+            mode: DivideByZero::Trap,
+            ..Default::default()
+        };
+        assert!(check.check(0, "div", true, false));
+        assert!(check.check(0, "div", false, true));
+        assert!(!check.check(0, "div", false, false));
+        check.mode = DivideByZero::Warn;
+        assert!(!check.check(0, "div", false, true));
+        assert_eq!(check.warnings, 1);
+    }
+}
@@ -0,0 +1,64 @@
+//Bypass network from the EX/MEM and MEM/WB latches back to the ALU inputs
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum Forwarding {
+    //Operands are only read from the register file in ID
+    None,
+    //Only the EX/MEM latch is bypassed, so load results can't be forwarded
+    ExOnly,
+    //EX/MEM and MEM/WB are bypassed like on the R3000
+    #[default]
+    Full,
+}
+
+impl FromStr for Forwarding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Forwarding::None),
+            "ex" => Ok(Forwarding::ExOnly),
+            "full" => Ok(Forwarding::Full),
+            _ => Err(format!("Unknown forwarding mode {}", s)),
+        }
+    }
+}
+
+//Where an operand read by an instruction entering EX came from
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OperandSource {
+    RegisterFile,
+    ExMem,
+    MemWb,
+}
+
+#[derive(Default, Clone, Copy, Debug)]
+pub struct ForwardingStatistics {
+    pub register_file: u64,
+    pub ex_mem: u64,
+    pub mem_wb: u64,
+}
+
+impl ForwardingStatistics {
+    pub fn count(&mut self, source: OperandSource) {
+        match source {
+            OperandSource::RegisterFile => self.register_file += 1,
+            OperandSource::ExMem => self.ex_mem += 1,
+            OperandSource::MemWb => self.mem_wb += 1,
+        }
+    }
+}
+
+impl fmt::Display for ForwardingStatistics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Operands read from register file: {}",
+            self.register_file
+        )?;
+        writeln!(f, "Operands forwarded from EX/MEM: {}", self.ex_mem)?;
+        writeln!(f, "Operands forwarded from MEM/WB: {}", self.mem_wb)
+    }
+}
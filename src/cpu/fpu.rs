@@ -0,0 +1,135 @@
+//The R3010 executes its operations next to the integer pipeline, an instruction reading a floating
+//point register or the condition bit waits in ID until the operation writing it has finished
+use super::cp1::*;
+use super::instruction_info::*;
+
+//Cycles of the R3010 operations
+const ADD_LATENCY: u64 = 2;
+const MUL_SINGLE_LATENCY: u64 = 4;
+const MUL_DOUBLE_LATENCY: u64 = 5;
+const DIV_SINGLE_LATENCY: u64 = 12;
+const DIV_DOUBLE_LATENCY: u64 = 19;
+const SIGN_LATENCY: u64 = 1;
+const CVT_FROM_WORD_LATENCY: u64 = 3;
+const CVT_LATENCY: u64 = 2;
+const CVT_D_S_LATENCY: u64 = 1;
+const COMPARE_LATENCY: u64 = 1;
+//lwc1, mtc1 and ctc1 deliver their word at the end of MEM
+const MOVE_LATENCY: u64 = 2;
+
+//Index of the condition bit behind the 32 registers
+const CONDITION: usize = 32;
+
+struct Operands {
+    sources: Vec<usize>,
+    destinations: Vec<usize>,
+    latency: u64,
+}
+
+//A double occupies an even/odd register pair
+fn registers(register: u8, double: bool) -> Vec<usize> {
+    match double {
+        true => vec![(register & !1) as usize, (register | 1) as usize],
+        false => vec![register as usize],
+    }
+}
+
+//Floating point registers read and written by coprocessor 1 instructions
+fn operands(instruction: &InstructionInfos) -> Option<Operands> {
+    let (sources, destinations, latency) = match instruction {
+        InstructionInfos::IType(i) => {
+            let d = i.decoded_instruction;
+            match d.op {
+                //bc1f, bc1t
+                0b010001 => (vec![CONDITION], vec![], 0),
+                //lwc1
+                0b110001 => (vec![], registers(d.rt(), false), MOVE_LATENCY),
+                //swc1
+                0b111001 => (registers(d.rt(), false), vec![], 0),
+                _ => return None,
+            }
+        }
+        InstructionInfos::RType(i) if i.decoded_instruction.op == 0b010001 => {
+            let d = i.decoded_instruction;
+            let (fmt, ft, fs, fd) = (d.rs(), d.rt(), d.rd(), d.shamt());
+            let double = fmt == FMT_D;
+            match fmt {
+                //mfc1
+                0b00000 => (registers(fs, false), vec![], 0),
+                //cfc1 reads the flags of the pending operations
+                0b00010 => (vec![CONDITION], vec![], 0),
+                //mtc1
+                0b00100 => (vec![], registers(fs, false), MOVE_LATENCY),
+                //ctc1
+                0b00110 => (vec![], vec![CONDITION], MOVE_LATENCY),
+                _ => {
+                    let mut sources = registers(fs, double);
+                    let (destination, latency) = match d.funct {
+                        0b000000 | 0b000001 => (registers(fd, double), ADD_LATENCY),
+                        0b000010 if double => (registers(fd, true), MUL_DOUBLE_LATENCY),
+                        0b000010 => (registers(fd, false), MUL_SINGLE_LATENCY),
+                        0b000011 if double => (registers(fd, true), DIV_DOUBLE_LATENCY),
+                        0b000011 => (registers(fd, false), DIV_SINGLE_LATENCY),
+                        0b000101..=0b000111 => (registers(fd, double), SIGN_LATENCY),
+                        //cvt.s, cvt.d, cvt.w
+                        0b100000 | 0b100001 if fmt == FMT_W => {
+                            (registers(fd, d.funct == 0b100001), CVT_FROM_WORD_LATENCY)
+                        }
+                        0b100000 => (registers(fd, false), CVT_LATENCY),
+                        0b100001 => (registers(fd, true), CVT_D_S_LATENCY),
+                        0b100100 => (registers(fd, false), CVT_LATENCY),
+                        //c.cond
+                        _ => (vec![CONDITION], COMPARE_LATENCY),
+                    };
+                    if d.funct <= 0b000011 || d.funct >= 0b110000 {
+                        sources.extend(registers(ft, double));
+                    }
+                    (sources, destination, latency)
+                }
+            }
+        }
+        _ => return None,
+    };
+    Some(Operands {
+        sources,
+        destinations,
+        latency,
+    })
+}
+
+pub struct FloatingPointUnit {
+    //First cycle in which an instruction in EX can read each register and the condition bit
+    ready: [u64; 33],
+    pub instructions: u64,
+    pub stall_cycles: u64,
+}
+
+impl Default for FloatingPointUnit {
+    fn default() -> Self {
+        FloatingPointUnit {
+            ready: [0; 33],
+            instructions: 0,
+            stall_cycles: 0,
+        }
+    }
+}
+
+impl FloatingPointUnit {
+    //True if the instruction in ID in this cycle would reach EX before its operands are written
+    pub fn interlock(&self, instruction: &InstructionInfos, cycle: u64) -> bool {
+        match operands(instruction) {
+            Some(o) => o.sources.iter().any(|r| cycle + 1 < self.ready[*r]),
+            None => false,
+        }
+    }
+
+    //Called when the instruction enters EX
+    pub fn start(&mut self, instruction: &InstructionInfos, cycle: u64) {
+        if let Some(o) = operands(instruction) {
+            self.instructions += 1;
+            for r in o.destinations {
+                self.ready[r] = cycle + o.latency;
+            }
+        }
+    }
+}
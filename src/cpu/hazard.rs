@@ -0,0 +1,156 @@
+//Detection of read after write hazards between the instruction in ID and the older
+//instructions that have not written back their result yet
+use super::forwarding::*;
+use super::instruction_info::*;
+use super::MIPS_REGISTER_NAMES;
+use std::collections::BTreeMap;
+use std::fmt;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct Hazard {
+    pub producer_pc: u32,
+    pub consumer_pc: u32,
+    pub register: u8,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct HazardRecord {
+    pub producer: &'static str,
+    pub consumer: &'static str,
+    pub occurrences: u64,
+    pub stall_cycles: u64,
+}
+
+//Instruction that has left ID but not yet written back its result
+#[derive(Clone, Copy)]
+pub struct InFlight {
+    pub pc: u32,
+    pub instruction: InstructionInfos,
+}
+
+#[derive(Default)]
+pub struct HazardUnit {
+    //Hazard the instruction in ID is currently waiting for
+    stalled_on: Option<Hazard>,
+    pub records: BTreeMap<Hazard, HazardRecord>,
+    pub stall_cycles: u64,
+
+    pub forwarding: Forwarding,
+    pub forwarded: ForwardingStatistics,
+
+    //Strict R3000 load delay, the instruction after a load doesn't wait for its result
+    pub load_delay_slot: bool,
+}
+
+impl HazardUnit {
+    //in_flight holds the instructions in EX/MEM and MEM/WB, the youngest first.
+    //Without forwarding a consumer has to wait in ID until its producer is in WB, the register
+    //file is written in the first and read in the second half of the cycle.
+    pub fn detect(
+        &self,
+        consumer_pc: u32,
+        consumer: &InstructionInfos,
+        in_flight: &[Option<InFlight>],
+    ) -> Option<Hazard> {
+        consumer
+            .source_registers()
+            .iter()
+            .filter(|r| **r != 0)
+            .find_map(|r| {
+                let (distance, producer) = self.producer(*r, in_flight)?;
+                match self.operand_source(distance, &producer.instruction) {
+                    Some(_) => None,
+                    None => Some(Hazard {
+                        producer_pc: producer.pc,
+                        consumer_pc,
+                        register: *r,
+                    }),
+                }
+            })
+    }
+
+    //Counts where the operands of an instruction leaving ID for EX will come from
+    pub fn issue(&mut self, consumer: &InstructionInfos, in_flight: &[Option<InFlight>]) {
+        for r in consumer.source_registers().iter().filter(|r| **r != 0) {
+            let source = match self.producer(*r, in_flight) {
+                Some((distance, producer)) => self
+                    .operand_source(distance, &producer.instruction)
+                    .unwrap_or(OperandSource::RegisterFile),
+                None => OperandSource::RegisterFile,
+            };
+            self.forwarded.count(source);
+        }
+    }
+
+    //Youngest in flight instruction writing the register and its distance to ID
+    fn producer(&self, register: u8, in_flight: &[Option<InFlight>]) -> Option<(usize, InFlight)> {
+        in_flight
+            .iter()
+            .enumerate()
+            .find_map(|(distance, x)| match x {
+                //the instruction in the load delay slot reads the old value
+                Some(p) if distance == 0 && self.load_delay_slot && p.instruction.is_load() => None,
+                Some(p) if p.instruction.destination_register() == register => Some((distance, *p)),
+                _ => None,
+            })
+    }
+
+    //Path delivering the result of a producer distance + 1 stages ahead of ID in the next cycle,
+    //None if the consumer has to stall
+    fn operand_source(
+        &self,
+        distance: usize,
+        producer: &InstructionInfos,
+    ) -> Option<OperandSource> {
+        match (distance, self.forwarding) {
+            (0, Forwarding::ExOnly) | (0, Forwarding::Full) if !producer.is_load() => {
+                Some(OperandSource::ExMem)
+            }
+            (1, Forwarding::Full) => Some(OperandSource::MemWb),
+            _ => None,
+        }
+    }
+
+    //Accounts one stall cycle to the hazard
+    pub fn stall(&mut self, hazard: Hazard, producer: &'static str, consumer: &'static str) {
+        let record = self.records.entry(hazard).or_insert(HazardRecord {
+            producer,
+            consumer,
+            occurrences: 0,
+            stall_cycles: 0,
+        });
+        if self.stalled_on != Some(hazard) {
+            record.occurrences += 1;
+            self.stalled_on = Some(hazard);
+        }
+        record.stall_cycles += 1;
+        self.stall_cycles += 1;
+    }
+
+    //The instruction in ID could move on to EX
+    #[inline]
+    pub fn proceed(&mut self) {
+        self.stalled_on = None;
+    }
+}
+
+impl fmt::Display for HazardUnit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut records: Vec<_> = self.records.iter().collect();
+        records.sort_by_key(|x| std::cmp::Reverse(x.1.stall_cycles));
+        for (hazard, record) in records {
+            writeln!(
+                f,
+                "{:#010X?} {:<6} -> {:#010X?} {:<6} ${:<5}: {} occurrences, {} stall cycles",
+                hazard.producer_pc,
+                record.producer,
+                hazard.consumer_pc,
+                record.consumer,
+                MIPS_REGISTER_NAMES[hazard.register as usize],
+                record.occurrences,
+                record.stall_cycles
+            )?;
+        }
+        Ok(())
+    }
+}
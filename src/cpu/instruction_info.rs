@@ -1,9 +1,17 @@
+use super::cp1::{FMT_D, FMT_S, FMT_W};
 use super::instructions::*;
+use super::isa::IsaLevel;
+use super::MIPS_REGISTER_NAMES;
 use crate::cpu;
+use std::error::Error;
+use std::fmt;
+#[derive(Clone, Copy)]
 pub struct InstructionInfo<T> {
     pub memonic: &'static str,
     pub decoded_instruction: T,
     pub f: fn(&mut cpu::MipsCpu<'_>, T),
+    //Encoding no decoder knows, executed as reserved_instruction
+    pub reserved: bool,
 }
 
 impl<T> InstructionInfo<T> {
@@ -12,10 +20,51 @@ impl<T> InstructionInfo<T> {
             memonic,
             decoded_instruction,
             f,
+            reserved: false,
         }
     }
 }
 
+//An instruction word without a decoding, reported with the decoder fields that selected nothing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodeError {
+    pub pc: u32,
+    pub word: u32,
+    pub path: String,
+}
+
+impl DecodeError {
+    pub fn new(pc: u32, word: u32) -> Self {
+        let op = word >> 26;
+        let path = match op {
+            0b000000 => format!("SPECIAL funct {:#04X?}", word & 0x3F),
+            0b000001 => format!("REGIMM rt {:#04X?}", (word >> 16) & 0x1F),
+            0b010000 | 0b010001 => format!(
+                "COP{} rs {:#04X?} funct {:#04X?}",
+                op & 3,
+                (word >> 21) & 0x1F,
+                word & 0x3F
+            ),
+            _ => format!("opcode {:#04X?}", op),
+        };
+        DecodeError { pc, word, path }
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Reserved instruction {:#010X?} at {:#010X?}: unknown {}, enable coprocessor 0 with --cp0 to raise a Reserved Instruction exception",
+            self.word, self.pc, self.path
+        )
+    }
+}
+
+impl Error for DecodeError {}
+
+#[allow(clippy::enum_variant_names)]
+#[derive(Clone, Copy)]
 pub enum InstructionInfos {
     IType(InstructionInfo<ITypeInstruction>),
     JType(InstructionInfo<JTypeInstruction>),
@@ -33,12 +82,485 @@ impl InstructionInfos {
     pub fn new_r(i: InstructionInfo<RTypeInstruction>) -> Self {
         InstructionInfos::RType(i)
     }
+
+    #[inline]
+    pub fn memonic(&self) -> &'static str {
+        match self {
+            InstructionInfos::IType(i) => i.memonic,
+            InstructionInfos::JType(i) => i.memonic,
+            InstructionInfos::RType(i) => i.memonic,
+        }
+    }
+
+    //General purpose registers read by the instruction, register 0 means no operand
+    pub fn source_registers(&self) -> [u8; 2] {
+        match self {
+            //ins merges into rt, the byte shuffles read rt, rdhwr reads no register
+            InstructionInfos::RType(i) if i.decoded_instruction.op == 0b011111 => {
+                let d = i.decoded_instruction;
+                match d.funct {
+                    0b000000 => [d.rs(), 0],
+                    0b000100 => [d.rs(), d.rt()],
+                    0b100000 => [d.rt(), 0],
+                    _ => [0, 0],
+                }
+            }
+            //clz and clo only read rs
+            InstructionInfos::RType(i) if i.decoded_instruction.op == 0b011100 => {
+                let d = i.decoded_instruction;
+                match d.funct {
+                    0b100000 | 0b100001 => [d.rs(), 0],
+                    _ => [d.rs(), d.rt()],
+                }
+            }
+            //mtc0, mtc1 and ctc1 write rt to the coprocessor
+            InstructionInfos::RType(i) if i.decoded_instruction.op != 0 => {
+                let d = i.decoded_instruction;
+                match d.rs() {
+                    0b00100 | 0b00110 => [d.rt(), 0],
+                    _ => [0, 0],
+                }
+            }
+            InstructionInfos::RType(i) => {
+                let d = i.decoded_instruction;
+                match d.funct {
+                    //shifts by immediate only read rt
+                    0b000000 | 0b000010 | 0b000011 => [d.rt(), 0],
+                    //jr, jalr, mthi, mtlo
+                    0b001000 | 0b001001 | 0b010001 | 0b010011 => [d.rs(), 0],
+                    //mfhi, mflo
+                    0b010000 | 0b010010 => [0, 0],
+                    _ => [d.rs(), d.rt()],
+                }
+            }
+            InstructionInfos::IType(i) => {
+                let d = i.decoded_instruction;
+                match d.op {
+                    //regimm branches, blez, bgtz
+                    0b000001 | 0b000110 | 0b000111 => [d.rs(), 0],
+                    //beq, bne, beql, bnel
+                    0b000100 | 0b000101 | 0b010100 | 0b010101 => [d.rs(), d.rt()],
+                    //lui, bc1f, bc1t
+                    0b001111 | 0b010001 => [0, 0],
+                    //lwl and lwr merge into rt, stores and sc write rt to memory
+                    0b100010 | 0b100110 | 0b101000..=0b101110 | 0b111000 => [d.rs(), d.rt()],
+                    _ => [d.rs(), 0],
+                }
+            }
+            InstructionInfos::JType(_) => [0, 0],
+        }
+    }
+
+    //General purpose register written by the instruction, 0 if it writes none
+    pub fn destination_register(&self) -> u8 {
+        match self {
+            InstructionInfos::RType(i) if i.reserved => 0,
+            //the byte shuffles write rd, ext, ins and rdhwr write rt
+            InstructionInfos::RType(i) if i.decoded_instruction.op == 0b011111 => {
+                let d = i.decoded_instruction;
+                match d.funct {
+                    0b100000 => d.rd(),
+                    _ => d.rt(),
+                }
+            }
+            //mul, clz and clo, the multiply accumulate instructions write HI and LO
+            InstructionInfos::RType(i) if i.decoded_instruction.op == 0b011100 => {
+                let d = i.decoded_instruction;
+                match d.funct {
+                    0b000010 | 0b100000 | 0b100001 => d.rd(),
+                    _ => 0,
+                }
+            }
+            //mfc0, mfc1 and cfc1 read the coprocessor register into rt, di and ei the old Status
+            InstructionInfos::RType(i) if i.decoded_instruction.op != 0 => {
+                let d = i.decoded_instruction;
+                match (d.op, d.rs()) {
+                    (0b010000, 0b00000 | 0b01011) | (0b010001, 0b00000 | 0b00010) => d.rt(),
+                    _ => 0,
+                }
+            }
+            InstructionInfos::RType(i) => {
+                let d = i.decoded_instruction;
+                match d.funct {
+                    //the shifts, jalr, movz, movn, mfhi, mflo and the ALU operations
+                    0b000000..=0b000111
+                    | 0b001001..=0b001011
+                    | 0b010000
+                    | 0b010010
+                    | 0b100000..=0b101011 => d.rd(),
+                    //syscall, break, sync and the traps may hold a code where rd is
+                    _ => 0,
+                }
+            }
+            InstructionInfos::IType(i) => {
+                let d = i.decoded_instruction;
+                match d.op {
+                    //bgezal, bltzal link into ra
+                    0b000001 if d.rt & 0b10000 != 0 => 31,
+                    0b000001 | 0b000100..=0b000111 | 0b010100..=0b010111 => 0,
+                    //stores, bc1f, bc1t and the floating point loads and stores
+                    0b101000..=0b101110 | 0b010001 | 0b110001 | 0b111001 => 0,
+                    _ => d.rt(),
+                }
+            }
+            InstructionInfos::JType(i) => match i.decoded_instruction.op {
+                //jal
+                0b000011 => 31,
+                _ => 0,
+            },
+        }
+    }
+
+    //Branches and jumps, all of them are followed by a delay slot. The regimm traps are no branches.
+    pub fn is_branch(&self) -> bool {
+        match self {
+            InstructionInfos::RType(i) if i.decoded_instruction.op == 0 => {
+                matches!(i.decoded_instruction.funct, 0b001000 | 0b001001)
+            }
+            InstructionInfos::RType(_) => false,
+            InstructionInfos::IType(i) => {
+                let d = i.decoded_instruction;
+                match d.op {
+                    0b000001 => d.rt & 0b01000 == 0,
+                    op => matches!(op, 0b000100..=0b000111 | 0b010001 | 0b010100..=0b010111),
+                }
+            }
+            InstructionInfos::JType(_) => true,
+        }
+    }
+
+    //mult, multu and the multiply accumulate instructions
+    pub fn is_multiplication(&self) -> bool {
+        match self {
+            InstructionInfos::RType(i) => matches!(
+                (i.decoded_instruction.op, i.decoded_instruction.funct),
+                (0, 0b011000 | 0b011001) | (0b011100, 0b000000 | 0b000001 | 0b000100 | 0b000101)
+            ),
+            _ => false,
+        }
+    }
+
+    //div and divu
+    pub fn is_division(&self) -> bool {
+        match self {
+            InstructionInfos::RType(i) if i.decoded_instruction.op == 0 => {
+                matches!(i.decoded_instruction.funct, 0b011010 | 0b011011)
+            }
+            _ => false,
+        }
+    }
+
+    //Instructions reading or writing HI and LO, including the ones starting the multiply/divide unit
+    pub fn uses_hi_lo(&self) -> bool {
+        match self {
+            InstructionInfos::RType(i) if i.decoded_instruction.op == 0 => {
+                matches!(i.decoded_instruction.funct, 0b010000..=0b010011 | 0b011000..=0b011011)
+            }
+            _ => self.is_multiplication(),
+        }
+    }
+
+    //sll with destination zero, which covers nop and ssnop
+    pub fn is_nop(&self) -> bool {
+        match self {
+            InstructionInfos::RType(i) => {
+                let d = i.decoded_instruction;
+                d.op == 0 && d.funct == 0 && d.rd() == 0
+            }
+            _ => false,
+        }
+    }
+
+    //lwl and lwr, which merge the loaded bytes into their destination
+    pub fn is_unaligned_load(&self) -> bool {
+        match self {
+            InstructionInfos::IType(i) => matches!(i.decoded_instruction.op, 0b100010 | 0b100110),
+            _ => false,
+        }
+    }
+
+    //Loads, ll, mfc0, mfc1 and cfc1 deliver their result at the end of MEM instead of EX
+    pub fn is_load(&self) -> bool {
+        match self {
+            InstructionInfos::IType(i) => {
+                matches!(i.decoded_instruction.op, 0b100000..=0b100110 | 0b110000)
+            }
+            InstructionInfos::RType(i) => {
+                let d = i.decoded_instruction;
+                matches!(
+                    (d.op, d.rs()),
+                    (0b010000, 0b00000) | (0b010001, 0b00000 | 0b00010)
+                )
+            }
+            _ => false,
+        }
+    }
+
+    //tlbwi and tlbwr, which can map the page of the instructions fetched behind them
+    pub fn writes_tlb(&self) -> bool {
+        match self {
+            InstructionInfos::RType(i) if !i.reserved => {
+                let d = i.decoded_instruction;
+                d.op == 0b010000 && d.rs() == 0b10000 && matches!(d.funct, 0b000010 | 0b000110)
+            }
+            _ => false,
+        }
+    }
 }
 
-pub fn decode_opcode(op_i: OpDecodedInstruction) -> InstructionInfos {
+//Memonic and operands as printed by the disassembler
+impl fmt::Display for InstructionInfos {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InstructionInfos::RType(i) if i.reserved => {
+                write!(f, "{} {:#010X?}", i.memonic, i.decoded_instruction.word())
+            }
+            //clz and clo, madd and msub only name their sources
+            InstructionInfos::RType(i) if i.decoded_instruction.op == 0b011100 => {
+                let d = i.decoded_instruction;
+                let names = |r: u8| MIPS_REGISTER_NAMES[r as usize];
+                match d.funct {
+                    0b000010 => write!(
+                        f,
+                        "{} {},{},{}",
+                        i.memonic,
+                        names(d.rd()),
+                        names(d.rs()),
+                        names(d.rt())
+                    ),
+                    0b100000 | 0b100001 => {
+                        write!(f, "{} {},{}", i.memonic, names(d.rd()), names(d.rs()))
+                    }
+                    _ => write!(f, "{} {},{}", i.memonic, names(d.rs()), names(d.rt())),
+                }
+            }
+            //traps compare rs with rt
+            InstructionInfos::RType(i)
+                if i.decoded_instruction.op == 0
+                    && (0b110000..=0b110110).contains(&i.decoded_instruction.funct) =>
+            {
+                write!(
+                    f,
+                    "{} {},{}",
+                    i.memonic,
+                    MIPS_REGISTER_NAMES[i.decoded_instruction.rs() as usize],
+                    MIPS_REGISTER_NAMES[i.decoded_instruction.rt() as usize]
+                )
+            }
+            InstructionInfos::RType(i) if i.decoded_instruction.op == 0b011111 => {
+                let d = i.decoded_instruction;
+                let names = |r: u8| MIPS_REGISTER_NAMES[r as usize];
+                match d.funct {
+                    //position and size of the field
+                    0b000000 => write!(
+                        f,
+                        "{} {},{},{},{}",
+                        i.memonic,
+                        names(d.rt()),
+                        names(d.rs()),
+                        d.shamt(),
+                        d.rd() + 1
+                    ),
+                    0b000100 => write!(
+                        f,
+                        "{} {},{},{},{}",
+                        i.memonic,
+                        names(d.rt()),
+                        names(d.rs()),
+                        d.shamt(),
+                        (d.rd() + 1).saturating_sub(d.shamt())
+                    ),
+                    0b100000 => write!(f, "{} {},{}", i.memonic, names(d.rd()), names(d.rt())),
+                    _ => write!(f, "{} {},${}", i.memonic, names(d.rt()), d.rd()),
+                }
+            }
+            //di and ei
+            InstructionInfos::RType(i)
+                if i.decoded_instruction.op == 0b010000
+                    && i.decoded_instruction.rs() == 0b01011 =>
+            {
+                write!(
+                    f,
+                    "{} {}",
+                    i.memonic,
+                    MIPS_REGISTER_NAMES[i.decoded_instruction.rt() as usize]
+                )
+            }
+            //mfc0 and mtc0
+            InstructionInfos::RType(i)
+                if i.decoded_instruction.op == 0b010000 && i.decoded_instruction.rs() < 0b10000 =>
+            {
+                write!(
+                    f,
+                    "{} {},${}",
+                    i.memonic,
+                    MIPS_REGISTER_NAMES[i.decoded_instruction.rt() as usize],
+                    i.decoded_instruction.rd()
+                )
+            }
+            //mfc1, cfc1, mtc1 and ctc1
+            InstructionInfos::RType(i)
+                if i.decoded_instruction.op == 0b010001 && i.decoded_instruction.rs() < 0b10000 =>
+            {
+                let d = i.decoded_instruction;
+                match d.rs() {
+                    0b00010 | 0b00110 => write!(
+                        f,
+                        "{} {},${}",
+                        i.memonic,
+                        MIPS_REGISTER_NAMES[d.rt() as usize],
+                        d.rd()
+                    ),
+                    _ => write!(
+                        f,
+                        "{} {},$f{}",
+                        i.memonic,
+                        MIPS_REGISTER_NAMES[d.rt() as usize],
+                        d.rd()
+                    ),
+                }
+            }
+            //fd, fs and ft of the floating point operations, compares have no destination
+            InstructionInfos::RType(i) if i.decoded_instruction.op == 0b010001 => {
+                let d = i.decoded_instruction;
+                match d.funct {
+                    0b000000..=0b000011 => {
+                        write!(f, "{} $f{},$f{},$f{}", i.memonic, d.shamt(), d.rd(), d.rt())
+                    }
+                    0b110000..=0b111111 => write!(f, "{} $f{},$f{}", i.memonic, d.rd(), d.rt()),
+                    _ => write!(f, "{} $f{},$f{}", i.memonic, d.shamt(), d.rd()),
+                }
+            }
+            //shifts and rotates name the shifted register rt before the amount
+            InstructionInfos::RType(i)
+                if i.decoded_instruction.op == 0 && i.decoded_instruction.funct < 0b001000 =>
+            {
+                let d = i.decoded_instruction;
+                let names = |r: u8| MIPS_REGISTER_NAMES[r as usize];
+                match d.funct {
+                    0b000000..=0b000011 => {
+                        write!(
+                            f,
+                            "{} {},{},{}",
+                            i.memonic,
+                            names(d.rd()),
+                            names(d.rt()),
+                            d.shamt()
+                        )
+                    }
+                    _ => write!(
+                        f,
+                        "{} {},{},{}",
+                        i.memonic,
+                        names(d.rd()),
+                        names(d.rt()),
+                        names(d.rs())
+                    ),
+                }
+            }
+            //rfe, coprocessor instructions, syscall, break and sync have no register operands
+            InstructionInfos::RType(i)
+                if i.decoded_instruction.op != 0
+                    || matches!(i.decoded_instruction.funct, 0b001100 | 0b001101 | 0b001111) =>
+            {
+                f.write_str(i.memonic)
+            }
+            InstructionInfos::RType(i) => write!(
+                f,
+                "{} {},{},{}",
+                i.memonic,
+                MIPS_REGISTER_NAMES[i.decoded_instruction.rd() as usize],
+                MIPS_REGISTER_NAMES[i.decoded_instruction.rs() as usize],
+                MIPS_REGISTER_NAMES[i.decoded_instruction.rt() as usize]
+            ),
+            InstructionInfos::IType(i) if i.decoded_instruction.op == 0b010001 => {
+                write!(
+                    f,
+                    "{} {}",
+                    i.memonic,
+                    i.decoded_instruction.immediate() as i16
+                )
+            }
+            InstructionInfos::IType(i)
+                if matches!(i.decoded_instruction.op, 0b110001 | 0b111001) =>
+            {
+                write!(
+                    f,
+                    "{} $f{},{},{}",
+                    i.memonic,
+                    i.decoded_instruction.rt(),
+                    MIPS_REGISTER_NAMES[i.decoded_instruction.rs() as usize],
+                    i.decoded_instruction.immediate() as i16
+                )
+            }
+            InstructionInfos::IType(i) => write!(
+                f,
+                "{} {},{},{}",
+                i.memonic,
+                MIPS_REGISTER_NAMES[i.decoded_instruction.rt() as usize],
+                MIPS_REGISTER_NAMES[i.decoded_instruction.rs() as usize],
+                i.decoded_instruction.immediate() as i16
+            ),
+            InstructionInfos::JType(i) => write!(
+                f,
+                "{} {:#04X?}",
+                i.memonic,
+                (i.decoded_instruction.target() << 2) as i32
+            ),
+        }
+    }
+}
+
+//Executes as a Reserved Instruction exception, so a wrong path fetch of data is harmless
+fn reserved(op_i: OpDecodedInstruction) -> InstructionInfos {
+    InstructionInfos::new_r(InstructionInfo {
+        reserved: true,
+        ..InstructionInfo::new(
+            "reserved",
+            RTypeInstruction::decode(op_i),
+            reserved_instruction,
+        )
+    })
+}
+
+//Encodings added by a later ISA level than isa decode as reserved
+pub fn decode_opcode(op_i: OpDecodedInstruction, isa: IsaLevel) -> InstructionInfos {
+    let mips2 = isa >= IsaLevel::Mips2;
     match op_i.op {
-        0b000000 => decode_rtype(op_i),
-        0b000001 => decode_regimm(op_i),
+        0b000000 => decode_rtype(op_i, isa),
+        0b000001 => decode_regimm(op_i, isa),
+        0b011100 if isa >= IsaLevel::Mips32R1 => decode_special2(op_i),
+        0b011111 if isa >= IsaLevel::Mips32R2 => decode_special3(op_i),
+        0b010100 if mips2 => InstructionInfos::new_i(InstructionInfo::new(
+            "beql",
+            ITypeInstruction::decode(op_i),
+            beql,
+        )),
+        0b010101 if mips2 => InstructionInfos::new_i(InstructionInfo::new(
+            "bnel",
+            ITypeInstruction::decode(op_i),
+            bnel,
+        )),
+        0b010110 if mips2 => InstructionInfos::new_i(InstructionInfo::new(
+            "blezl",
+            ITypeInstruction::decode(op_i),
+            blezl,
+        )),
+        0b010111 if mips2 => InstructionInfos::new_i(InstructionInfo::new(
+            "bgtzl",
+            ITypeInstruction::decode(op_i),
+            bgtzl,
+        )),
+        0b110000 if mips2 => InstructionInfos::new_i(InstructionInfo::new(
+            "ll",
+            ITypeInstruction::decode(op_i),
+            ll,
+        )),
+        0b111000 if mips2 => InstructionInfos::new_i(InstructionInfo::new(
+            "sc",
+            ITypeInstruction::decode(op_i),
+            sc,
+        )),
         0b001000 => InstructionInfos::new_i(InstructionInfo::new(
             "addi",
             ITypeInstruction::decode(op_i),
@@ -74,8 +596,18 @@ pub fn decode_opcode(op_i: OpDecodedInstruction) -> InstructionInfos {
             ITypeInstruction::decode(op_i),
             bne,
         )),
-        0b010000 | 0b010001 | 0b010010 | 0b010011 => {
-            todo!("Coprocessor Instruction or move from/to coprocessor")
+        0b010000 => decode_cop0(op_i, isa),
+        0b010001 => decode_cop1(op_i, isa),
+        0b010010 | 0b010011 => {
+            let memonic = match op_i.op & 3 {
+                2 => "cop2",
+                _ => "cop3",
+            };
+            InstructionInfos::new_r(InstructionInfo::new(
+                memonic,
+                RTypeInstruction::decode(op_i),
+                cop_unusable,
+            ))
         }
         0b000010 => {
             InstructionInfos::new_j(InstructionInfo::new("j", JTypeInstruction::decode(op_i), j))
@@ -115,7 +647,22 @@ pub fn decode_opcode(op_i: OpDecodedInstruction) -> InstructionInfos {
             ITypeInstruction::decode(op_i),
             lw,
         )),
-        0b110000 | 0b110001 | 0b110010 | 0b110011 => todo!("Load word to coprocessor"),
+        0b110001 => InstructionInfos::new_i(InstructionInfo::new(
+            "lwc1",
+            ITypeInstruction::decode(op_i),
+            lwc1,
+        )),
+        0b110010 | 0b110011 => {
+            let memonic = match op_i.op & 3 {
+                2 => "lwc2",
+                _ => "lwc3",
+            };
+            InstructionInfos::new_r(InstructionInfo::new(
+                memonic,
+                RTypeInstruction::decode(op_i),
+                cop_unusable,
+            ))
+        }
         0b100010 => InstructionInfos::new_i(InstructionInfo::new(
             "lwl",
             ITypeInstruction::decode(op_i),
@@ -156,7 +703,22 @@ pub fn decode_opcode(op_i: OpDecodedInstruction) -> InstructionInfos {
             ITypeInstruction::decode(op_i),
             sw,
         )),
-        0b111000 | 0b111001 | 0b111010 | 0b111011 => todo!("Store word from Coprocessor"),
+        0b111001 => InstructionInfos::new_i(InstructionInfo::new(
+            "swc1",
+            ITypeInstruction::decode(op_i),
+            swc1,
+        )),
+        0b111010 | 0b111011 => {
+            let memonic = match op_i.op & 3 {
+                2 => "swc2",
+                _ => "swc3",
+            };
+            InstructionInfos::new_r(InstructionInfo::new(
+                memonic,
+                RTypeInstruction::decode(op_i),
+                cop_unusable,
+            ))
+        }
         0b101010 => InstructionInfos::new_i(InstructionInfo::new(
             "swl",
             ITypeInstruction::decode(op_i),
@@ -172,17 +734,38 @@ pub fn decode_opcode(op_i: OpDecodedInstruction) -> InstructionInfos {
             ITypeInstruction::decode(op_i),
             xori,
         )),
-        _ => panic!("unknown instruction"),
+        _ => reserved(op_i),
     }
 }
 
-pub fn decode_rtype(op_i: OpDecodedInstruction) -> InstructionInfos {
+pub fn decode_rtype(op_i: OpDecodedInstruction, isa: IsaLevel) -> InstructionInfos {
     let decoded = RTypeInstruction::decode(op_i);
+    let mips2 = isa >= IsaLevel::Mips2;
     match decoded.funct {
+        0b001111 if mips2 => InstructionInfos::new_r(InstructionInfo::new("sync", decoded, sync)),
+        0b110000 if mips2 => InstructionInfos::new_r(InstructionInfo::new("tge", decoded, tge)),
+        0b110001 if mips2 => InstructionInfos::new_r(InstructionInfo::new("tgeu", decoded, tgeu)),
+        0b110010 if mips2 => InstructionInfos::new_r(InstructionInfo::new("tlt", decoded, tlt)),
+        0b110011 if mips2 => InstructionInfos::new_r(InstructionInfo::new("tltu", decoded, tltu)),
+        0b110100 if mips2 => InstructionInfos::new_r(InstructionInfo::new("teq", decoded, teq)),
+        0b110110 if mips2 => InstructionInfos::new_r(InstructionInfo::new("tne", decoded, tne)),
+        //rotates reuse the unused rs and shamt fields of srl and srlv
+        0b000010 if decoded.rs() == 1 && isa >= IsaLevel::Mips32R2 => {
+            InstructionInfos::new_r(InstructionInfo::new("rotr", decoded, rotr))
+        }
+        0b000110 if decoded.shamt() == 1 && isa >= IsaLevel::Mips32R2 => {
+            InstructionInfos::new_r(InstructionInfo::new("rotrv", decoded, rotrv))
+        }
+        0b001010 if isa >= IsaLevel::Mips32R1 => {
+            InstructionInfos::new_r(InstructionInfo::new("movz", decoded, movz))
+        }
+        0b001011 if isa >= IsaLevel::Mips32R1 => {
+            InstructionInfos::new_r(InstructionInfo::new("movn", decoded, movn))
+        }
         0b100000 => InstructionInfos::new_r(InstructionInfo::new("add", decoded, add)),
         0b100001 => InstructionInfos::new_r(InstructionInfo::new("addu", decoded, addu)),
         0b100100 => InstructionInfos::new_r(InstructionInfo::new("and", decoded, and)),
-        0b001101 => panic!("Break instruction"),
+        0b001101 => InstructionInfos::new_r(InstructionInfo::new("break", decoded, breakpoint)),
         0b011010 => InstructionInfos::new_r(InstructionInfo::new("div", decoded, div)),
         0b011011 => InstructionInfos::new_r(InstructionInfo::new("divu", decoded, divu)),
         0b001001 => InstructionInfos::new_r(InstructionInfo::new("jalr", decoded, jalr)),
@@ -205,20 +788,197 @@ pub fn decode_rtype(op_i: OpDecodedInstruction) -> InstructionInfos {
         0b000110 => InstructionInfos::new_r(InstructionInfo::new("srlv", decoded, srlv)),
         0b100010 => InstructionInfos::new_r(InstructionInfo::new("sub", decoded, sub)),
         0b100011 => InstructionInfos::new_r(InstructionInfo::new("subu", decoded, subu)),
-        0b001100 => panic!("Syscall instruction"),
+        0b001100 => InstructionInfos::new_r(InstructionInfo::new("syscall", decoded, syscall)),
         0b100110 => InstructionInfos::new_r(InstructionInfo::new("xor", decoded, xor)),
-        _ => panic!("unknown instruction"),
+        _ => reserved(op_i),
     }
 }
 
 //decodes register immediate branch instructions in IType that only need one register
-pub fn decode_regimm(op_i: OpDecodedInstruction) -> InstructionInfos {
+pub fn decode_regimm(op_i: OpDecodedInstruction, isa: IsaLevel) -> InstructionInfos {
     let decoded = ITypeInstruction::decode(op_i);
+    let mips2 = isa >= IsaLevel::Mips2;
     match decoded.rt {
+        0b00010 if mips2 => InstructionInfos::new_i(InstructionInfo::new("bltzl", decoded, bltzl)),
+        0b00011 if mips2 => InstructionInfos::new_i(InstructionInfo::new("bgezl", decoded, bgezl)),
+        0b10010 if mips2 => {
+            InstructionInfos::new_i(InstructionInfo::new("bltzall", decoded, bltzall))
+        }
+        0b10011 if mips2 => {
+            InstructionInfos::new_i(InstructionInfo::new("bgezall", decoded, bgezall))
+        }
+        0b01000 if mips2 => InstructionInfos::new_i(InstructionInfo::new("tgei", decoded, tgei)),
+        0b01001 if mips2 => InstructionInfos::new_i(InstructionInfo::new("tgeiu", decoded, tgeiu)),
+        0b01010 if mips2 => InstructionInfos::new_i(InstructionInfo::new("tlti", decoded, tlti)),
+        0b01011 if mips2 => InstructionInfos::new_i(InstructionInfo::new("tltiu", decoded, tltiu)),
+        0b01100 if mips2 => InstructionInfos::new_i(InstructionInfo::new("teqi", decoded, teqi)),
+        0b01110 if mips2 => InstructionInfos::new_i(InstructionInfo::new("tnei", decoded, tnei)),
         0b00001 => InstructionInfos::new_i(InstructionInfo::new("bgez", decoded, bgez)),
         0b10001 => InstructionInfos::new_i(InstructionInfo::new("bgezal", decoded, bgezal)),
         0b00000 => InstructionInfos::new_i(InstructionInfo::new("bltz", decoded, bltz)),
         0b10000 => InstructionInfos::new_i(InstructionInfo::new("bltzal", decoded, bltzal)),
-        _ => panic!("unknown instruction"),
+        _ => reserved(op_i),
+    }
+}
+
+//decodes the MIPS32 multiply accumulate, mul and count leading bits instructions
+pub fn decode_special2(op_i: OpDecodedInstruction) -> InstructionInfos {
+    let decoded = RTypeInstruction::decode(op_i);
+    match decoded.funct {
+        0b000000 => InstructionInfos::new_r(InstructionInfo::new("madd", decoded, madd)),
+        0b000001 => InstructionInfos::new_r(InstructionInfo::new("maddu", decoded, maddu)),
+        0b000010 => InstructionInfos::new_r(InstructionInfo::new("mul", decoded, mul)),
+        0b000100 => InstructionInfos::new_r(InstructionInfo::new("msub", decoded, msub)),
+        0b000101 => InstructionInfos::new_r(InstructionInfo::new("msubu", decoded, msubu)),
+        0b100000 => InstructionInfos::new_r(InstructionInfo::new("clz", decoded, clz)),
+        0b100001 => InstructionInfos::new_r(InstructionInfo::new("clo", decoded, clo)),
+        _ => reserved(op_i),
+    }
+}
+
+//decodes the MIPS32 Release 2 bit field, byte shuffle and hardware register instructions
+pub fn decode_special3(op_i: OpDecodedInstruction) -> InstructionInfos {
+    let decoded = RTypeInstruction::decode(op_i);
+    match (decoded.funct, decoded.shamt()) {
+        (0b000000, _) => InstructionInfos::new_r(InstructionInfo::new("ext", decoded, ext)),
+        (0b000100, _) => InstructionInfos::new_r(InstructionInfo::new("ins", decoded, ins)),
+        //BSHFL, shamt selects the operation
+        (0b100000, 0b00010) => InstructionInfos::new_r(InstructionInfo::new("wsbh", decoded, wsbh)),
+        (0b100000, 0b10000) => InstructionInfos::new_r(InstructionInfo::new("seb", decoded, seb)),
+        (0b100000, 0b11000) => InstructionInfos::new_r(InstructionInfo::new("seh", decoded, seh)),
+        (0b111011, _) => InstructionInfos::new_r(InstructionInfo::new("rdhwr", decoded, rdhwr)),
+        _ => reserved(op_i),
+    }
+}
+
+//decodes the system control coprocessor instructions, the rs field selects the operation
+pub fn decode_cop0(op_i: OpDecodedInstruction, isa: IsaLevel) -> InstructionInfos {
+    let decoded = RTypeInstruction::decode(op_i);
+    match (decoded.rs(), decoded.funct) {
+        //MFMC0, bit 5 selects ei
+        (0b01011, 0b000000) if isa >= IsaLevel::Mips32R2 => {
+            InstructionInfos::new_r(InstructionInfo::new("di", decoded, di))
+        }
+        (0b01011, 0b100000) if isa >= IsaLevel::Mips32R2 => {
+            InstructionInfos::new_r(InstructionInfo::new("ei", decoded, ei))
+        }
+        (0b00000, _) => InstructionInfos::new_r(InstructionInfo::new("mfc0", decoded, mfc0)),
+        (0b00100, _) => InstructionInfos::new_r(InstructionInfo::new("mtc0", decoded, mtc0)),
+        (0b10000, 0b000001) => InstructionInfos::new_r(InstructionInfo::new("tlbr", decoded, tlbr)),
+        (0b10000, 0b000010) => {
+            InstructionInfos::new_r(InstructionInfo::new("tlbwi", decoded, tlbwi))
+        }
+        (0b10000, 0b000110) => {
+            InstructionInfos::new_r(InstructionInfo::new("tlbwr", decoded, tlbwr))
+        }
+        (0b10000, 0b001000) => InstructionInfos::new_r(InstructionInfo::new("tlbp", decoded, tlbp)),
+        (0b10000, 0b010000) => InstructionInfos::new_r(InstructionInfo::new("rfe", decoded, rfe)),
+        _ => reserved(op_i),
+    }
+}
+
+//c.cond memonics indexed by the low four bits of funct
+const COMPARE_S: [&str; 16] = [
+    "c.f.s", "c.un.s", "c.eq.s", "c.ueq.s", "c.olt.s", "c.ult.s", "c.ole.s", "c.ule.s", "c.sf.s",
+    "c.ngle.s", "c.seq.s", "c.ngl.s", "c.lt.s", "c.nge.s", "c.le.s", "c.ngt.s",
+];
+const COMPARE_D: [&str; 16] = [
+    "c.f.d", "c.un.d", "c.eq.d", "c.ueq.d", "c.olt.d", "c.ult.d", "c.ole.d", "c.ule.d", "c.sf.d",
+    "c.ngle.d", "c.seq.d", "c.ngl.d", "c.lt.d", "c.nge.d", "c.le.d", "c.ngt.d",
+];
+
+//decodes the floating point coprocessor instructions, the rs field selects moves, branches or the format
+pub fn decode_cop1(op_i: OpDecodedInstruction, isa: IsaLevel) -> InstructionInfos {
+    let decoded = RTypeInstruction::decode(op_i);
+    match (decoded.rs(), decoded.funct) {
+        (0b00000, _) => InstructionInfos::new_r(InstructionInfo::new("mfc1", decoded, mfc1)),
+        (0b00010, _) => InstructionInfos::new_r(InstructionInfo::new("cfc1", decoded, cfc1)),
+        (0b00100, _) => InstructionInfos::new_r(InstructionInfo::new("mtc1", decoded, mtc1)),
+        (0b00110, _) => InstructionInfos::new_r(InstructionInfo::new("ctc1", decoded, ctc1)),
+        (0b01000, _) => {
+            let decoded = ITypeInstruction::decode(op_i);
+            match decoded.rt {
+                0b00000 => InstructionInfos::new_i(InstructionInfo::new("bc1f", decoded, bc1f)),
+                0b00001 => InstructionInfos::new_i(InstructionInfo::new("bc1t", decoded, bc1t)),
+                0b00010 if isa >= IsaLevel::Mips2 => {
+                    InstructionInfos::new_i(InstructionInfo::new("bc1fl", decoded, bc1fl))
+                }
+                0b00011 if isa >= IsaLevel::Mips2 => {
+                    InstructionInfos::new_i(InstructionInfo::new("bc1tl", decoded, bc1tl))
+                }
+                _ => reserved(op_i),
+            }
+        }
+        (FMT_S, 0b000000) => {
+            InstructionInfos::new_r(InstructionInfo::new("add.s", decoded, add_fmt))
+        }
+        (FMT_D, 0b000000) => {
+            InstructionInfos::new_r(InstructionInfo::new("add.d", decoded, add_fmt))
+        }
+        (FMT_S, 0b000001) => {
+            InstructionInfos::new_r(InstructionInfo::new("sub.s", decoded, sub_fmt))
+        }
+        (FMT_D, 0b000001) => {
+            InstructionInfos::new_r(InstructionInfo::new("sub.d", decoded, sub_fmt))
+        }
+        (FMT_S, 0b000010) => {
+            InstructionInfos::new_r(InstructionInfo::new("mul.s", decoded, mul_fmt))
+        }
+        (FMT_D, 0b000010) => {
+            InstructionInfos::new_r(InstructionInfo::new("mul.d", decoded, mul_fmt))
+        }
+        (FMT_S, 0b000011) => {
+            InstructionInfos::new_r(InstructionInfo::new("div.s", decoded, div_fmt))
+        }
+        (FMT_D, 0b000011) => {
+            InstructionInfos::new_r(InstructionInfo::new("div.d", decoded, div_fmt))
+        }
+        (FMT_S, 0b000101) => {
+            InstructionInfos::new_r(InstructionInfo::new("abs.s", decoded, abs_fmt))
+        }
+        (FMT_D, 0b000101) => {
+            InstructionInfos::new_r(InstructionInfo::new("abs.d", decoded, abs_fmt))
+        }
+        (FMT_S, 0b000110) => {
+            InstructionInfos::new_r(InstructionInfo::new("mov.s", decoded, mov_fmt))
+        }
+        (FMT_D, 0b000110) => {
+            InstructionInfos::new_r(InstructionInfo::new("mov.d", decoded, mov_fmt))
+        }
+        (FMT_S, 0b000111) => {
+            InstructionInfos::new_r(InstructionInfo::new("neg.s", decoded, neg_fmt))
+        }
+        (FMT_D, 0b000111) => {
+            InstructionInfos::new_r(InstructionInfo::new("neg.d", decoded, neg_fmt))
+        }
+        (FMT_D, 0b100000) => {
+            InstructionInfos::new_r(InstructionInfo::new("cvt.s.d", decoded, cvt_s))
+        }
+        (FMT_W, 0b100000) => {
+            InstructionInfos::new_r(InstructionInfo::new("cvt.s.w", decoded, cvt_s))
+        }
+        (FMT_S, 0b100001) => {
+            InstructionInfos::new_r(InstructionInfo::new("cvt.d.s", decoded, cvt_d))
+        }
+        (FMT_W, 0b100001) => {
+            InstructionInfos::new_r(InstructionInfo::new("cvt.d.w", decoded, cvt_d))
+        }
+        (FMT_S, 0b100100) => {
+            InstructionInfos::new_r(InstructionInfo::new("cvt.w.s", decoded, cvt_w))
+        }
+        (FMT_D, 0b100100) => {
+            InstructionInfos::new_r(InstructionInfo::new("cvt.w.d", decoded, cvt_w))
+        }
+        (FMT_S, 0b110000..=0b111111) => InstructionInfos::new_r(InstructionInfo::new(
+            COMPARE_S[(decoded.funct & 0xF) as usize],
+            decoded,
+            c_cond,
+        )),
+        (FMT_D, 0b110000..=0b111111) => InstructionInfos::new_r(InstructionInfo::new(
+            COMPARE_D[(decoded.funct & 0xF) as usize],
+            decoded,
+            c_cond,
+        )),
+        _ => reserved(op_i),
     }
 }
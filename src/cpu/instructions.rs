@@ -1,5 +1,6 @@
-use crate::bus_objects::BusObject;
 use crate::cpu;
+use crate::cpu::cp1::*;
+use crate::cpu::memory_access::*;
 #[derive(Clone, Copy, Debug)]
 pub struct OpDecodedInstruction {
     pub op: u8,
@@ -102,6 +103,20 @@ impl RTypeInstruction {
     pub fn rd(&self) -> u8 {
         self.rd
     }
+    #[inline]
+    pub fn shamt(&self) -> u8 {
+        self.shamt
+    }
+
+    //The instruction word the fields were decoded from
+    pub fn word(&self) -> u32 {
+        ((self.op as u32) << 26)
+            | ((self.rs as u32) << 21)
+            | ((self.rt as u32) << 16)
+            | ((self.rd as u32) << 11)
+            | ((self.shamt as u32) << 6)
+            | self.funct as u32
+    }
 }
 
 impl FromOpDecodedInstruction for RTypeInstruction {
@@ -123,36 +138,47 @@ impl FromOpDecodedInstruction for RTypeInstruction {
 }
 #[inline]
 fn generic_branch(cpu: &mut cpu::MipsCpu<'_>, i: ITypeInstruction, cond: bool) {
-    let offset = (i.immediate as i16 as i32) << 2;
+    let offset = ((i.immediate as i16 as i32) << 2) as u32;
     if cond {
         cpu.branch = true;
-        cpu.branch_target = ((cpu.pc + 4) as i32 + offset) as u32;
+        cpu.branch_target = cpu.pc.wrapping_add(4).wrapping_add(offset);
+    }
+}
+
+//Writes a signed result to rd, or raises an Integer overflow exception leaving rd unchanged
+#[inline]
+fn set_checked(cpu: &mut cpu::MipsCpu<'_>, rd: u8, result: Option<i32>) {
+    match result {
+        Some(v) => cpu.set_register(rd, v as u32),
+        None => cpu.raise(cpu::cp0::ExceptionCode::Overflow),
     }
 }
 
 //add 2 integers
 //throw Integer overflow exception on overflow
 pub fn add(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
-    //no exception
-    addu(cpu, i);
+    let x = cpu.get_register(i.rs) as i32;
+    let y = cpu.get_register(i.rt) as i32;
+    set_checked(cpu, i.rd, x.checked_add(y));
 }
 //add content of one register to immediate sign extended value
 //throw Integer overflow exception on overflow
 pub fn addi(cpu: &mut cpu::MipsCpu<'_>, i: ITypeInstruction) {
-    //no exception
-    addiu(cpu, i);
+    let x = cpu.get_register(i.rs) as i32;
+    let imm = i.immediate as i16 as i32;
+    set_checked(cpu, i.rt, x.checked_add(imm));
 }
 //add content of one register to immediate sign extended value
 pub fn addiu(cpu: &mut cpu::MipsCpu<'_>, i: ITypeInstruction) {
-    let x = cpu.get_register(i.rs) as i32;
-    let imm = i.immediate as i16 as i32;
-    cpu.set_register(i.rt, (x + imm) as u32);
+    let x = cpu.get_register(i.rs);
+    let imm = i.immediate as i16 as i32 as u32;
+    cpu.set_register(i.rt, x.wrapping_add(imm));
 }
-//add 2 signed integers
+//add 2 integers without overflow check
 pub fn addu(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
     let x = cpu.get_register(i.rs);
     let y = cpu.get_register(i.rt);
-    cpu.set_register(i.rd, ((x as i32) + (y as i32)) as u32);
+    cpu.set_register(i.rd, x.wrapping_add(y));
 }
 
 pub fn and(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
@@ -201,18 +227,124 @@ pub fn bne(cpu: &mut cpu::MipsCpu<'_>, i: ITypeInstruction) {
     generic_branch(cpu, i, cpu.get_register(i.rs) != cpu.get_register(i.rt))
 }
 
+//Only reached when the coprocessor is not implemented, R3000 systems mostly lack coprocessor 2 and 3
+pub fn cop_unusable(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
+    cpu.exception = Some(cpu::cp0::Exception {
+        coprocessor: i.op & 3,
+        ..cpu::cp0::Exception::new(cpu::cp0::ExceptionCode::CoprocessorUnusable)
+    });
+}
+
+//Halfwords and words have to be naturally aligned, otherwise the access raises an address error
+fn aligned(cpu: &mut cpu::MipsCpu<'_>, addr: u32, size: u32, store: bool) -> bool {
+    if addr.is_multiple_of(size) {
+        return true;
+    }
+    cpu.exception = Some(cpu::cp0::Exception::address_error(addr, store));
+    false
+}
+
+//Physical address of a load or store of size bytes, None if the access raised an exception
+fn data_address(
+    cpu: &mut cpu::MipsCpu<'_>,
+    i: &ITypeInstruction,
+    size: u32,
+    access: cpu::mmu::Access,
+) -> Option<(u32, bool)> {
+    let addr = cpu
+        .get_register(i.rs)
+        .wrapping_add(i.immediate as i16 as u32);
+    if !aligned(cpu, addr, size, access == cpu::mmu::Access::Store) {
+        return None;
+    }
+    let cached = cpu.cp0.mmu.cached(addr);
+    cpu.translate(addr, access).map(|addr| (addr, cached))
+}
+
+//Hands a load into rt to the memory access, nothing is loaded if the address raised an exception
+fn load(cpu: &mut cpu::MipsCpu<'_>, i: &ITypeInstruction, width: Width, signed: bool) {
+    let size = match width {
+        Width::Halfword => 2,
+        Width::Word => 4,
+        _ => 1,
+    };
+    let (addr, cached) = match data_address(cpu, i, size, cpu::mmu::Access::Load) {
+        Some(target) => target,
+        None => return,
+    };
+    let merge = match width {
+        Width::Left | Width::Right => cpu.get_merge_register(i.rt),
+        _ => 0,
+    };
+    let kind = AccessKind::Load {
+        width,
+        signed,
+        rt: i.rt,
+        merge,
+    };
+    cpu.access(MemoryAccess { addr, cached, kind });
+}
+
+//Hands a store of rt to the memory access
+fn store(cpu: &mut cpu::MipsCpu<'_>, i: &ITypeInstruction, width: Width) {
+    let size = match width {
+        Width::Halfword => 2,
+        Width::Word => 4,
+        _ => 1,
+    };
+    let (addr, cached) = match data_address(cpu, i, size, cpu::mmu::Access::Store) {
+        Some(target) => target,
+        None => return,
+    };
+    let value = cpu.get_register(i.rt);
+    let kind = AccessKind::Store { width, value };
+    cpu.access(MemoryAccess { addr, cached, kind });
+}
+
+//Encodings no decoder knows, only fatal for the simulation if coprocessor 0 cannot deliver them
+pub fn reserved_instruction(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
+    //Linux emulates rdhwr of the thread pointer on processors before Release 2
+    let rdhwr_29 = i.op == 0b011111 && i.funct == 0b111011 && i.rs == 0 && i.rd == 29;
+    if rdhwr_29 && cpu.syscalls.emulation == cpu::syscall::SyscallEmulation::Linux {
+        return rdhwr(cpu, i);
+    }
+    if !cpu.cp0.enabled {
+        let error = cpu::instruction_info::DecodeError::new(cpu.pc, i.word());
+        cpu.fatal = Some(Box::new(error));
+        return;
+    }
+    cpu.raise(cpu::cp0::ExceptionCode::ReservedInstruction);
+}
+
+pub fn breakpoint(cpu: &mut cpu::MipsCpu<'_>, _i: RTypeInstruction) {
+    cpu.raise(cpu::cp0::ExceptionCode::Breakpoint);
+}
+
 pub fn div(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
     let x = cpu.get_register(i.rs) as i32;
     let y = cpu.get_register(i.rt) as i32;
-    cpu.lo = (x / y) as u32;
-    cpu.hi = (x % y) as u32;
+    let overflow = x == i32::MIN && y == -1;
+    if cpu.division.check(cpu.pc, "div", y == 0, overflow) {
+        return division_trap(cpu);
+    }
+    (cpu.lo, cpu.hi) = cpu::division::signed_division(x, y);
 }
 
 pub fn divu(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
     let x = cpu.get_register(i.rs);
     let y = cpu.get_register(i.rt);
-    cpu.lo = x / y;
-    cpu.hi = x % y;
+    if cpu.division.check(cpu.pc, "divu", y == 0, false) {
+        return division_trap(cpu);
+    }
+    (cpu.lo, cpu.hi) = cpu::division::unsigned_division(x, y);
+}
+
+//The check compilers emit is break before MIPS II and teq from MIPS II on
+fn division_trap(cpu: &mut cpu::MipsCpu<'_>) {
+    match cpu.isa >= cpu::isa::IsaLevel::Mips2 {
+        true => cpu.raise(cpu::cp0::ExceptionCode::Trap),
+        false => cpu.raise(cpu::cp0::ExceptionCode::Breakpoint),
+    }
 }
 
 pub fn j(cpu: &mut cpu::MipsCpu<'_>, i: JTypeInstruction) {
@@ -237,27 +369,19 @@ pub fn jr(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
 }
 
 pub fn lb(cpu: &mut cpu::MipsCpu<'_>, i: ITypeInstruction) {
-    let addr = (i.immediate as i16 as i32) + cpu.get_register(i.rs) as i32;
-    let loaded = cpu.bus.read_byte(addr as u32) as i8 as i32;
-    cpu.set_register(i.rt, loaded as u32);
+    load(cpu, &i, Width::Byte, true);
 }
 
 pub fn lbu(cpu: &mut cpu::MipsCpu<'_>, i: ITypeInstruction) {
-    let addr = (i.immediate as i16 as i32) + cpu.get_register(i.rs) as i32;
-    let loaded = cpu.bus.read_byte(addr as u32) as u32;
-    cpu.set_register(i.rt, loaded);
+    load(cpu, &i, Width::Byte, false);
 }
 
 pub fn lh(cpu: &mut cpu::MipsCpu<'_>, i: ITypeInstruction) {
-    let addr = (i.immediate as i16 as i32) + cpu.get_register(i.rs) as i32;
-    let loaded = u16::from_be(cpu.bus.read_hw(addr as u32)) as i16 as i32;
-    cpu.set_register(i.rt, loaded as u32);
+    load(cpu, &i, Width::Halfword, true);
 }
 
 pub fn lhu(cpu: &mut cpu::MipsCpu<'_>, i: ITypeInstruction) {
-    let addr = (i.immediate as i16 as i32) + cpu.get_register(i.rs) as i32;
-    let loaded = u16::from_be(cpu.bus.read_hw(addr as u32)) as u32;
-    cpu.set_register(i.rt, loaded);
+    load(cpu, &i, Width::Halfword, false);
 }
 
 pub fn lui(cpu: &mut cpu::MipsCpu<'_>, i: ITypeInstruction) {
@@ -266,35 +390,32 @@ pub fn lui(cpu: &mut cpu::MipsCpu<'_>, i: ITypeInstruction) {
 }
 
 pub fn lw(cpu: &mut cpu::MipsCpu<'_>, i: ITypeInstruction) {
-    let addr = (i.immediate as i16 as i32) + cpu.get_register(i.rs) as i32;
-    let loaded = cpu.bus.read_w(addr as u32);
-    cpu.set_register_nc(i.rt, loaded);
+    load(cpu, &i, Width::Word, false);
 }
 
+//load the bytes from addr to the least significant byte of the aligned word into the most
+//significant bytes of rt
 pub fn lwl(cpu: &mut cpu::MipsCpu<'_>, i: ITypeInstruction) {
-    let mut addr = ((i.immediate as i16 as i32) + cpu.get_register(i.rs) as i32) as u32;
-    let mut register = cpu.get_register_nc(i.rt);
-    let mut counter = 0;
-    while addr % 4 != 0 {
-        register &= 0xff << ((3 - counter) * 8);
-        register |= (cpu.bus.read_byte(addr) as u32) << ((3 - counter) * 8);
-        counter += 1;
-        addr += 1;
-    }
-    cpu.set_register_nc(i.rt, register);
+    load(cpu, &i, Width::Left, false);
 }
 
+//load the bytes from the most significant byte of the aligned word to addr into the least
+//significant bytes of rt
 pub fn lwr(cpu: &mut cpu::MipsCpu<'_>, i: ITypeInstruction) {
-    let mut addr = ((i.immediate as i16 as i32) + cpu.get_register(i.rs) as i32) as u32;
-    let mut register = cpu.get_register_nc(i.rt);
-    let mut counter = 0;
-    while addr % 4 != 0 {
-        register &= 0xff << (counter * 8);
-        register |= (cpu.bus.read_byte(addr) as u32) << (counter * 8);
-        counter += 1;
-        addr -= 1;
+    load(cpu, &i, Width::Right, false);
+}
+
+//move from coprocessor 0, delayed like a load
+pub fn mfc0(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
+    if cop0_usable(cpu) {
+        cpu.set_loaded_register(i.rt, cpu.cp0.read(i.rd));
+    }
+}
+
+pub fn mtc0(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
+    if cop0_usable(cpu) {
+        cpu.cp0.write(i.rd, cpu.get_register(i.rt));
     }
-    cpu.set_register_nc(i.rt, register);
 }
 
 pub fn mfhi(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
@@ -347,16 +468,44 @@ pub fn ori(cpu: &mut cpu::MipsCpu<'_>, i: ITypeInstruction) {
     cpu.set_register(i.rt, x | imm);
 }
 
+//read, write and probe the TLB through Index, Random, EntryHi and EntryLo
+pub fn tlbr(cpu: &mut cpu::MipsCpu<'_>, _i: RTypeInstruction) {
+    if cop0_usable(cpu) {
+        cpu.cp0.mmu.tlbr();
+    }
+}
+
+pub fn tlbwi(cpu: &mut cpu::MipsCpu<'_>, _i: RTypeInstruction) {
+    if cop0_usable(cpu) {
+        cpu.cp0.mmu.tlbwi();
+    }
+}
+
+pub fn tlbwr(cpu: &mut cpu::MipsCpu<'_>, _i: RTypeInstruction) {
+    if cop0_usable(cpu) {
+        cpu.cp0.mmu.tlbwr();
+    }
+}
+
+pub fn tlbp(cpu: &mut cpu::MipsCpu<'_>, _i: RTypeInstruction) {
+    if cop0_usable(cpu) {
+        cpu.cp0.mmu.tlbp();
+    }
+}
+
+//restore from exception
+pub fn rfe(cpu: &mut cpu::MipsCpu<'_>, _i: RTypeInstruction) {
+    if cop0_usable(cpu) {
+        cpu.cp0.restore_from_exception();
+    }
+}
+
 pub fn sb(cpu: &mut cpu::MipsCpu<'_>, i: ITypeInstruction) {
-    let addr = (i.immediate as i16 as i32) + cpu.get_register(i.rs) as i32;
-    cpu.bus
-        .write_byte(addr as u32, cpu.get_register(i.rt) as u8);
+    store(cpu, &i, Width::Byte);
 }
 
 pub fn sh(cpu: &mut cpu::MipsCpu<'_>, i: ITypeInstruction) {
-    let addr = (i.immediate as i16 as i32) + cpu.get_register(i.rs) as i32;
-    cpu.bus
-        .write_hw(addr as u32, u16::to_be(cpu.get_register(i.rt) as u16));
+    store(cpu, &i, Width::Halfword);
 }
 
 pub fn sll(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
@@ -432,42 +581,38 @@ pub fn srlv(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
     cpu.set_register(i.rd, x >> y);
 }
 
+//subtract rt from rs
+//throw Integer overflow exception on overflow
 pub fn sub(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
-    let x = cpu.get_register(i.rt);
-    let y = cpu.get_register(i.rs);
-    cpu.set_register(i.rd, y - x);
+    let x = cpu.get_register(i.rs) as i32;
+    let y = cpu.get_register(i.rt) as i32;
+    set_checked(cpu, i.rd, x.checked_sub(y));
 }
 
 pub fn subu(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
-    sub(cpu, i);
+    let x = cpu.get_register(i.rs);
+    let y = cpu.get_register(i.rt);
+    cpu.set_register(i.rd, x.wrapping_sub(y));
 }
 
 pub fn sw(cpu: &mut cpu::MipsCpu<'_>, i: ITypeInstruction) {
-    let addr = (i.immediate as i16 as i32) + cpu.get_register(i.rs) as i32;
-    cpu.bus.write_w(addr as u32, cpu.get_register_nc(i.rt));
+    store(cpu, &i, Width::Word);
 }
 
+//store the most significant bytes of rt from addr to the least significant byte of the aligned word
 pub fn swl(cpu: &mut cpu::MipsCpu<'_>, i: ITypeInstruction) {
-    let mut addr = ((i.immediate as i16 as i32) + cpu.get_register(i.rs) as i32) as u32;
-    let register = cpu.get_register_nc(i.rt);
-    let mut counter = 0;
-    while addr % 4 != 0 {
-        let value = (register >> ((3 - counter) * 8)) as u8;
-        cpu.bus.write_byte(addr, value);
-        counter += 1;
-        addr += 1;
-    }
+    store(cpu, &i, Width::Left);
 }
 
+//store the least significant bytes of rt from the most significant byte of the aligned word to addr
 pub fn swr(cpu: &mut cpu::MipsCpu<'_>, i: ITypeInstruction) {
-    let mut addr = ((i.immediate as i16 as i32) + cpu.get_register(i.rs) as i32) as u32;
-    let register = cpu.get_register_nc(i.rt);
-    let mut counter = 0;
-    while addr % 4 != 0 {
-        let value = (register >> (counter * 8)) as u8;
-        cpu.bus.write_byte(addr, value);
-        counter += 1;
-        addr -= 1;
+    store(cpu, &i, Width::Right);
+}
+
+pub fn syscall(cpu: &mut cpu::MipsCpu<'_>, _i: RTypeInstruction) {
+    match cpu.syscalls.emulation {
+        cpu::syscall::SyscallEmulation::None => cpu.raise(cpu::cp0::ExceptionCode::Syscall),
+        _ => cpu.emulate_syscall(),
     }
 }
 
@@ -482,3 +627,542 @@ pub fn xori(cpu: &mut cpu::MipsCpu<'_>, i: ITypeInstruction) {
     let imm = i.immediate as u32;
     cpu.set_register(i.rt, x ^ imm);
 }
+
+//MIPS II
+
+//Branch likely: the delay slot is nullified if the branch is not taken
+#[inline]
+fn generic_branch_likely(cpu: &mut cpu::MipsCpu<'_>, i: ITypeInstruction, cond: bool) {
+    generic_branch(cpu, i, cond);
+    cpu.nullify = !cond;
+}
+
+pub fn beql(cpu: &mut cpu::MipsCpu<'_>, i: ITypeInstruction) {
+    generic_branch_likely(cpu, i, cpu.get_register(i.rs) == cpu.get_register(i.rt))
+}
+
+pub fn bnel(cpu: &mut cpu::MipsCpu<'_>, i: ITypeInstruction) {
+    generic_branch_likely(cpu, i, cpu.get_register(i.rs) != cpu.get_register(i.rt))
+}
+
+pub fn blezl(cpu: &mut cpu::MipsCpu<'_>, i: ITypeInstruction) {
+    generic_branch_likely(cpu, i, cpu.get_register(i.rs) as i32 <= 0)
+}
+
+pub fn bgtzl(cpu: &mut cpu::MipsCpu<'_>, i: ITypeInstruction) {
+    generic_branch_likely(cpu, i, cpu.get_register(i.rs) as i32 > 0)
+}
+
+pub fn bltzl(cpu: &mut cpu::MipsCpu<'_>, i: ITypeInstruction) {
+    generic_branch_likely(cpu, i, (cpu.get_register(i.rs) as i32) < 0)
+}
+
+pub fn bgezl(cpu: &mut cpu::MipsCpu<'_>, i: ITypeInstruction) {
+    generic_branch_likely(cpu, i, cpu.get_register(i.rs) as i32 >= 0)
+}
+
+pub fn bltzall(cpu: &mut cpu::MipsCpu<'_>, i: ITypeInstruction) {
+    bltzl(cpu, i);
+    cpu.set_register(31, cpu.pc + 8);
+}
+
+pub fn bgezall(cpu: &mut cpu::MipsCpu<'_>, i: ITypeInstruction) {
+    bgezl(cpu, i);
+    cpu.set_register(31, cpu.pc + 8);
+}
+
+//Raises a Trap exception if the condition holds
+#[inline]
+fn generic_trap(cpu: &mut cpu::MipsCpu<'_>, cond: bool) {
+    if cond {
+        cpu.raise(cpu::cp0::ExceptionCode::Trap);
+    }
+}
+
+pub fn teq(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
+    generic_trap(cpu, cpu.get_register(i.rs) == cpu.get_register(i.rt))
+}
+
+pub fn tne(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
+    generic_trap(cpu, cpu.get_register(i.rs) != cpu.get_register(i.rt))
+}
+
+pub fn tge(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
+    generic_trap(
+        cpu,
+        cpu.get_register(i.rs) as i32 >= cpu.get_register(i.rt) as i32,
+    )
+}
+
+pub fn tgeu(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
+    generic_trap(cpu, cpu.get_register(i.rs) >= cpu.get_register(i.rt))
+}
+
+pub fn tlt(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
+    generic_trap(
+        cpu,
+        (cpu.get_register(i.rs) as i32) < cpu.get_register(i.rt) as i32,
+    )
+}
+
+pub fn tltu(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
+    generic_trap(cpu, cpu.get_register(i.rs) < cpu.get_register(i.rt))
+}
+
+//the immediate traps compare with the sign extended immediate, the unsigned ones as well
+pub fn teqi(cpu: &mut cpu::MipsCpu<'_>, i: ITypeInstruction) {
+    generic_trap(
+        cpu,
+        cpu.get_register(i.rs) as i32 == i.immediate as i16 as i32,
+    )
+}
+
+pub fn tnei(cpu: &mut cpu::MipsCpu<'_>, i: ITypeInstruction) {
+    generic_trap(
+        cpu,
+        cpu.get_register(i.rs) as i32 != i.immediate as i16 as i32,
+    )
+}
+
+pub fn tgei(cpu: &mut cpu::MipsCpu<'_>, i: ITypeInstruction) {
+    generic_trap(
+        cpu,
+        cpu.get_register(i.rs) as i32 >= i.immediate as i16 as i32,
+    )
+}
+
+pub fn tgeiu(cpu: &mut cpu::MipsCpu<'_>, i: ITypeInstruction) {
+    generic_trap(cpu, cpu.get_register(i.rs) >= i.immediate as i16 as u32)
+}
+
+pub fn tlti(cpu: &mut cpu::MipsCpu<'_>, i: ITypeInstruction) {
+    generic_trap(
+        cpu,
+        (cpu.get_register(i.rs) as i32) < i.immediate as i16 as i32,
+    )
+}
+
+pub fn tltiu(cpu: &mut cpu::MipsCpu<'_>, i: ITypeInstruction) {
+    generic_trap(cpu, cpu.get_register(i.rs) < i.immediate as i16 as u32)
+}
+
+//load linked, starts a read-modify-write sequence that sc completes
+pub fn ll(cpu: &mut cpu::MipsCpu<'_>, i: ITypeInstruction) {
+    let (addr, cached) = match data_address(cpu, &i, 4, cpu::mmu::Access::Load) {
+        Some(target) => target,
+        None => return,
+    };
+    cpu.ll_bit = true;
+    let kind = AccessKind::Load {
+        width: Width::Word,
+        signed: false,
+        rt: i.rt,
+        merge: 0,
+    };
+    cpu.access(MemoryAccess { addr, cached, kind });
+}
+
+//store conditional, only stores if no exception occurred since ll and sets rt to 1 if it did
+pub fn sc(cpu: &mut cpu::MipsCpu<'_>, i: ITypeInstruction) {
+    if !std::mem::replace(&mut cpu.ll_bit, false) {
+        cpu.set_register(i.rt, 0);
+        return;
+    }
+    let (addr, cached) = match data_address(cpu, &i, 4, cpu::mmu::Access::Store) {
+        Some(target) => target,
+        None => return,
+    };
+    let kind = AccessKind::Store {
+        width: Width::Word,
+        value: cpu.get_register(i.rt),
+    };
+    cpu.access(MemoryAccess { addr, cached, kind });
+    cpu.set_register(i.rt, 1);
+}
+
+//memory accesses complete in program order in the simulator
+pub fn sync(_cpu: &mut cpu::MipsCpu<'_>, _i: RTypeInstruction) {}
+
+//MIPS32 Release 1
+
+//HI and LO as one 64 bit accumulator
+#[inline]
+fn accumulator(cpu: &cpu::MipsCpu<'_>) -> u64 {
+    ((cpu.hi as u64) << 32) | cpu.lo as u64
+}
+
+#[inline]
+fn set_accumulator(cpu: &mut cpu::MipsCpu<'_>, value: u64) {
+    cpu.lo = value as u32;
+    cpu.hi = (value >> 32) as u32;
+}
+
+pub fn madd(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
+    let x = cpu.get_register(i.rs) as i32 as i64;
+    let y = cpu.get_register(i.rt) as i32 as i64;
+    let r = accumulator(cpu).wrapping_add((x * y) as u64);
+    set_accumulator(cpu, r);
+}
+
+pub fn maddu(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
+    let x = cpu.get_register(i.rs) as u64;
+    let y = cpu.get_register(i.rt) as u64;
+    let r = accumulator(cpu).wrapping_add(x * y);
+    set_accumulator(cpu, r);
+}
+
+pub fn msub(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
+    let x = cpu.get_register(i.rs) as i32 as i64;
+    let y = cpu.get_register(i.rt) as i32 as i64;
+    let r = accumulator(cpu).wrapping_sub((x * y) as u64);
+    set_accumulator(cpu, r);
+}
+
+pub fn msubu(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
+    let x = cpu.get_register(i.rs) as u64;
+    let y = cpu.get_register(i.rt) as u64;
+    let r = accumulator(cpu).wrapping_sub(x * y);
+    set_accumulator(cpu, r);
+}
+
+//multiply into a general purpose register, HI and LO are left unchanged
+pub fn mul(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
+    let x = cpu.get_register(i.rs) as i32;
+    let y = cpu.get_register(i.rt) as i32;
+    cpu.set_register(i.rd, x.wrapping_mul(y) as u32);
+}
+
+pub fn clz(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
+    let x = cpu.get_register(i.rs);
+    cpu.set_register(i.rd, x.leading_zeros());
+}
+
+pub fn clo(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
+    let x = cpu.get_register(i.rs);
+    cpu.set_register(i.rd, x.leading_ones());
+}
+
+pub fn movn(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
+    if cpu.get_register(i.rt) != 0 {
+        cpu.set_register(i.rd, cpu.get_register(i.rs));
+    }
+}
+
+pub fn movz(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
+    if cpu.get_register(i.rt) == 0 {
+        cpu.set_register(i.rd, cpu.get_register(i.rs));
+    }
+}
+
+//MIPS32 Release 2
+
+//Mask of the low size bits, size is 1 to 32
+#[inline]
+fn bit_mask(size: u32) -> u32 {
+    u32::MAX >> (32 - size)
+}
+
+//extract the field of rd + 1 bits at bit shamt of rs into rt
+pub fn ext(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
+    let x = cpu.get_register(i.rs) >> i.shamt;
+    cpu.set_register(i.rt, x & bit_mask(i.rd as u32 + 1));
+}
+
+//insert the low bits of rs into rt at the field from bit shamt to bit rd
+pub fn ins(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
+    let size = (i.rd as u32 + 1).saturating_sub(i.shamt as u32).max(1);
+    let mask = bit_mask(size) << i.shamt;
+    let x = cpu.get_register(i.rs) << i.shamt;
+    let y = cpu.get_register(i.rt);
+    cpu.set_register(i.rt, (y & !mask) | (x & mask));
+}
+
+//swap the bytes within each halfword
+pub fn wsbh(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
+    let x = cpu.get_register(i.rt);
+    cpu.set_register(i.rd, ((x & 0x00FF_00FF) << 8) | ((x >> 8) & 0x00FF_00FF));
+}
+
+pub fn seb(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
+    let x = cpu.get_register(i.rt);
+    cpu.set_register(i.rd, x as i8 as i32 as u32);
+}
+
+pub fn seh(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
+    let x = cpu.get_register(i.rt);
+    cpu.set_register(i.rd, x as i16 as i32 as u32);
+}
+
+pub fn rotr(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
+    let x = cpu.get_register(i.rt);
+    cpu.set_register(i.rd, x.rotate_right(i.shamt as u32));
+}
+
+pub fn rotrv(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
+    let x = cpu.get_register(i.rt);
+    let y = cpu.get_register(i.rs) & 0x1F;
+    cpu.set_register(i.rd, x.rotate_right(y));
+}
+
+//disable and enable interrupts, rt receives the previous Status
+pub fn di(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
+    if cop0_usable(cpu) {
+        cpu.set_register(i.rt, cpu.cp0.status);
+        cpu.cp0.status &= !1;
+    }
+}
+
+pub fn ei(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
+    if cop0_usable(cpu) {
+        cpu.set_register(i.rt, cpu.cp0.status);
+        cpu.cp0.status |= 1;
+    }
+}
+
+//read hardware register rd: CPUNum, SYNCI_Step, CC, CCRes and the thread pointer in ULR
+pub fn rdhwr(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
+    let value = match i.rd {
+        0 => 0,
+        //the caches only model timing and fetches always see the bus, so synci is never needed
+        1 => 0,
+        2 => cpu.pipeline.cycles as u32,
+        3 => 1,
+        29 => cpu.syscalls.thread_pointer,
+        _ => return cpu.raise(cpu::cp0::ExceptionCode::ReservedInstruction),
+    };
+    cpu.set_register(i.rt, value);
+}
+
+//Coprocessor 0 instructions in user mode need CU0, otherwise they raise a coprocessor unusable
+//exception
+fn cop0_usable(cpu: &mut cpu::MipsCpu<'_>) -> bool {
+    if cpu.cp0.cop0_usable() {
+        return true;
+    }
+    cpu.raise(cpu::cp0::ExceptionCode::CoprocessorUnusable);
+    false
+}
+
+//The R3010 has to be attached and enabled in Status, otherwise the instruction raises a
+//coprocessor unusable exception
+fn cp1_usable(cpu: &mut cpu::MipsCpu<'_>) -> bool {
+    if cpu.cp1.present && cpu.cp0.status & cpu::cp0::STATUS_CU1 != 0 {
+        return true;
+    }
+    cpu.exception = Some(cpu::cp0::Exception {
+        coprocessor: 1,
+        ..cpu::cp0::Exception::new(cpu::cp0::ExceptionCode::CoprocessorUnusable)
+    });
+    false
+}
+
+//Records the IEEE exceptions of an operation, returns whether its result can be written. Before
+//MIPS II the trap is the interrupt of the R3010.
+fn fpu_signal(cpu: &mut cpu::MipsCpu<'_>, exceptions: u32) -> bool {
+    if cpu.cp1.signal(exceptions) {
+        if cpu.isa >= cpu::isa::IsaLevel::Mips2 {
+            cpu.raise(cpu::cp0::ExceptionCode::FloatingPoint);
+        }
+        return false;
+    }
+    true
+}
+
+//fd = fs op ft, fmt in rs, ft in rt, fs in rd and fd in shamt
+fn fpu_arithmetic<T: Float>(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction, op: Operation) {
+    let fs = T::read(&cpu.cp1, i.rd);
+    let ft = T::read(&cpu.cp1, i.rt);
+    let (result, exceptions) = arithmetic(op, fs, ft, cpu.cp1.rounding_mode());
+    if fpu_signal(cpu, exceptions) {
+        T::write(&mut cpu.cp1, i.shamt, result);
+    }
+}
+
+fn fpu_binary(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction, op: Operation) {
+    if cp1_usable(cpu) {
+        match i.rs {
+            FMT_D => fpu_arithmetic::<f64>(cpu, i, op),
+            _ => fpu_arithmetic::<f32>(cpu, i, op),
+        }
+    }
+}
+
+pub fn add_fmt(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
+    fpu_binary(cpu, i, Operation::Add);
+}
+
+pub fn sub_fmt(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
+    fpu_binary(cpu, i, Operation::Sub);
+}
+
+pub fn mul_fmt(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
+    fpu_binary(cpu, i, Operation::Mul);
+}
+
+pub fn div_fmt(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
+    fpu_binary(cpu, i, Operation::Div);
+}
+
+//abs, neg and mov only change the sign bit, which is in the high word of a double
+fn fpu_sign(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction, f: fn(u32) -> u32) {
+    if cp1_usable(cpu) {
+        let (fs, fd) = match i.rs {
+            FMT_D => {
+                let low = cpu.cp1.read(i.rd & !1);
+                cpu.cp1.write(i.shamt & !1, low);
+                (i.rd | 1, i.shamt | 1)
+            }
+            _ => (i.rd, i.shamt),
+        };
+        let high = cpu.cp1.read(fs);
+        cpu.cp1.write(fd, f(high));
+    }
+}
+
+pub fn abs_fmt(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
+    fpu_sign(cpu, i, |x| x & !(1 << 31));
+}
+
+pub fn neg_fmt(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
+    fpu_sign(cpu, i, |x| x ^ (1 << 31));
+}
+
+pub fn mov_fmt(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
+    fpu_sign(cpu, i, |x| x);
+}
+
+pub fn cvt_s(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
+    if cp1_usable(cpu) {
+        let mode = cpu.cp1.rounding_mode();
+        let (result, exceptions) = match i.rs {
+            FMT_D => double_to_single(f64::read(&cpu.cp1, i.rd), mode),
+            _ => word_to_single(cpu.cp1.read(i.rd) as i32, mode),
+        };
+        if fpu_signal(cpu, exceptions) {
+            f32::write(&mut cpu.cp1, i.shamt, result);
+        }
+    }
+}
+
+//singles and words are exact as doubles
+pub fn cvt_d(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
+    if cp1_usable(cpu) {
+        let result = match i.rs {
+            FMT_S => f32::read(&cpu.cp1, i.rd) as f64,
+            _ => cpu.cp1.read(i.rd) as i32 as f64,
+        };
+        if fpu_signal(cpu, 0) {
+            f64::write(&mut cpu.cp1, i.shamt, result);
+        }
+    }
+}
+
+pub fn cvt_w(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
+    if cp1_usable(cpu) {
+        let fs = match i.rs {
+            FMT_D => f64::read(&cpu.cp1, i.rd),
+            _ => f32::read(&cpu.cp1, i.rd) as f64,
+        };
+        let (result, exceptions) = to_word(fs, cpu.cp1.rounding_mode());
+        if fpu_signal(cpu, exceptions) {
+            cpu.cp1.write(i.shamt, result);
+        }
+    }
+}
+
+//c.cond.fmt compares fs with ft and sets the condition bit
+pub fn c_cond(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
+    if cp1_usable(cpu) {
+        let (condition, exceptions) = match i.rs {
+            FMT_D => compare(
+                f64::read(&cpu.cp1, i.rd),
+                f64::read(&cpu.cp1, i.rt),
+                i.funct,
+            ),
+            _ => compare(
+                f32::read(&cpu.cp1, i.rd),
+                f32::read(&cpu.cp1, i.rt),
+                i.funct,
+            ),
+        };
+        if fpu_signal(cpu, exceptions) {
+            cpu.cp1.set_condition(condition);
+        }
+    }
+}
+
+pub fn bc1f(cpu: &mut cpu::MipsCpu<'_>, i: ITypeInstruction) {
+    if cp1_usable(cpu) {
+        generic_branch(cpu, i, !cpu.cp1.condition());
+    }
+}
+
+pub fn bc1t(cpu: &mut cpu::MipsCpu<'_>, i: ITypeInstruction) {
+    if cp1_usable(cpu) {
+        generic_branch(cpu, i, cpu.cp1.condition());
+    }
+}
+
+pub fn bc1fl(cpu: &mut cpu::MipsCpu<'_>, i: ITypeInstruction) {
+    if cp1_usable(cpu) {
+        generic_branch_likely(cpu, i, !cpu.cp1.condition());
+    }
+}
+
+pub fn bc1tl(cpu: &mut cpu::MipsCpu<'_>, i: ITypeInstruction) {
+    if cp1_usable(cpu) {
+        generic_branch_likely(cpu, i, cpu.cp1.condition());
+    }
+}
+
+//move from coprocessor 1, delayed like a load
+pub fn mfc1(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
+    if cp1_usable(cpu) {
+        cpu.set_loaded_register(i.rt, cpu.cp1.read(i.rd));
+    }
+}
+
+pub fn mtc1(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
+    if cp1_usable(cpu) {
+        cpu.cp1.write(i.rd, cpu.get_register(i.rt));
+    }
+}
+
+pub fn cfc1(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
+    if cp1_usable(cpu) {
+        cpu.set_loaded_register(i.rt, cpu.cp1.read_control(i.rd));
+    }
+}
+
+pub fn ctc1(cpu: &mut cpu::MipsCpu<'_>, i: RTypeInstruction) {
+    if cp1_usable(cpu) {
+        cpu.cp1.write_control(i.rd, cpu.get_register(i.rt));
+    }
+}
+
+pub fn lwc1(cpu: &mut cpu::MipsCpu<'_>, i: ITypeInstruction) {
+    if !cp1_usable(cpu) {
+        return;
+    }
+    let (addr, cached) = match data_address(cpu, &i, 4, cpu::mmu::Access::Load) {
+        Some(target) => target,
+        None => return,
+    };
+    let kind = AccessKind::LoadCop1 { ft: i.rt };
+    cpu.access(MemoryAccess { addr, cached, kind });
+}
+
+pub fn swc1(cpu: &mut cpu::MipsCpu<'_>, i: ITypeInstruction) {
+    if !cp1_usable(cpu) {
+        return;
+    }
+    let (addr, cached) = match data_address(cpu, &i, 4, cpu::mmu::Access::Store) {
+        Some(target) => target,
+        None => return,
+    };
+    let kind = AccessKind::Store {
+        width: Width::Word,
+        value: cpu.cp1.read(i.rt),
+    };
+    cpu.access(MemoryAccess { addr, cached, kind });
+}
@@ -0,0 +1,30 @@
+//Instruction set accepted by the decoder. Every level contains the previous one, the encodings a
+//later level adds raise a Reserved Instruction exception like on a processor of the selected one.
+use std::str::FromStr;
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug, Default)]
+pub enum IsaLevel {
+    //R3000
+    #[default]
+    Mips1,
+    //ll/sc, branch likely, traps and sync
+    Mips2,
+    //mul, madd, msub, clz, clo, movn and movz
+    Mips32R1,
+    //ext, ins, wsbh, seb, seh, rotr, rotrv, di, ei and rdhwr
+    Mips32R2,
+}
+
+impl FromStr for IsaLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mips1" => Ok(IsaLevel::Mips1),
+            "mips2" => Ok(IsaLevel::Mips2),
+            "mips32r1" => Ok(IsaLevel::Mips32R1),
+            "mips32r2" => Ok(IsaLevel::Mips32R2),
+            _ => Err(format!("Unknown ISA level {}", s)),
+        }
+    }
+}
@@ -0,0 +1,71 @@
+//The R3000 makes the result of a load visible one instruction late, the instruction in the load
+//delay slot still sees the old register value
+use super::instruction_info::*;
+use super::MIPS_REGISTER_NAMES;
+use std::collections::HashSet;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum LoadDelay {
+    //Loads write their register immediately, later MIPS implementations interlock instead
+    #[default]
+    Interlocked,
+    //Models the load delay slot of the R3000 exactly
+    Strict,
+    //Like Interlocked but reports instructions reading a load target in the delay slot
+    Warn,
+}
+
+impl FromStr for LoadDelay {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "interlocked" => Ok(LoadDelay::Interlocked),
+            "strict" => Ok(LoadDelay::Strict),
+            "warn" => Ok(LoadDelay::Warn),
+            _ => Err(format!("Unknown load delay mode {}", s)),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct LoadDelaySlot {
+    pub mode: LoadDelay,
+    //Load of the previous instruction, written after the current instruction in strict mode
+    pub pending: Option<(u8, u32)>,
+    //Load of the current instruction
+    pub issued: Option<(u8, u32)>,
+    //Address and target register of the previous instruction if it was a load
+    pub previous_load: Option<(u32, u8)>,
+    //Delay slot instructions that have already been reported
+    warned: HashSet<u32>,
+    pub warnings: u64,
+}
+
+impl LoadDelaySlot {
+    //Reports the instruction at pc if it reads the target of the load before it
+    pub fn check(&mut self, pc: u32, instruction: &InstructionInfos) {
+        let (load_pc, register) = match self.previous_load {
+            Some(l) => l,
+            None => return,
+        };
+        //lwl and lwr merge with the loaded value on purpose
+        if instruction.is_unaligned_load() && instruction.destination_register() == register {
+            return;
+        }
+        if !instruction.source_registers().contains(&register) {
+            return;
+        }
+        self.warnings += 1;
+        if self.warned.insert(pc) {
+            println!(
+                "Warning: {:#04X?}: {} reads ${} in the delay slot of the load at {:#04X?}",
+                pc,
+                instruction.memonic(),
+                MIPS_REGISTER_NAMES[register as usize],
+                load_pc
+            );
+        }
+    }
+}
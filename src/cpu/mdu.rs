@@ -0,0 +1,50 @@
+//The R3000 multiply/divide unit works independently of the pipeline, only instructions accessing
+//HI and LO wait for it
+use super::instruction_info::*;
+
+//Cycles the R3000 needs for mult/multu and div/divu
+pub const R3000_MULT_LATENCY: u64 = 12;
+pub const R3000_DIV_LATENCY: u64 = 35;
+
+pub struct MultiplyDivideUnit {
+    pub mult_latency: u64,
+    pub div_latency: u64,
+    //First cycle in which an instruction in EX can access HI and LO again
+    ready: u64,
+
+    pub multiplications: u64,
+    pub divisions: u64,
+    pub stall_cycles: u64,
+}
+
+impl Default for MultiplyDivideUnit {
+    fn default() -> Self {
+        MultiplyDivideUnit {
+            mult_latency: R3000_MULT_LATENCY,
+            div_latency: R3000_DIV_LATENCY,
+            ready: 0,
+            multiplications: 0,
+            divisions: 0,
+            stall_cycles: 0,
+        }
+    }
+}
+
+impl MultiplyDivideUnit {
+    //True if the instruction in ID in this cycle would reach EX before the unit is done
+    #[inline]
+    pub fn interlock(&self, instruction: &InstructionInfos, cycle: u64) -> bool {
+        instruction.uses_hi_lo() && cycle + 1 < self.ready
+    }
+
+    //Called when the instruction enters EX
+    pub fn start(&mut self, instruction: &InstructionInfos, cycle: u64) {
+        if instruction.is_multiplication() {
+            self.multiplications += 1;
+            self.ready = cycle + self.mult_latency;
+        } else if instruction.is_division() {
+            self.divisions += 1;
+            self.ready = cycle + self.div_latency;
+        }
+    }
+}
@@ -0,0 +1,137 @@
+//Loads and stores are split into the address calculation in EX and the bus access in MEM.
+//The single step model performs the access right away, the pipeline carries it in EX/MEM.
+use super::MipsCpu;
+use crate::bus_objects::{BusObject, Endianness};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Width {
+    Byte,
+    Halfword,
+    Word,
+    //lwl and swl, from the addressed byte to the end of the aligned word
+    Left,
+    //lwr and swr, from the start of the aligned word to the addressed byte
+    Right,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AccessKind {
+    //lwl and lwr merge the loaded bytes into merge, the value rt had when they were executed
+    Load {
+        width: Width,
+        signed: bool,
+        rt: u8,
+        merge: u32,
+    },
+    LoadCop1 {
+        ft: u8,
+    },
+    Store {
+        width: Width,
+        value: u32,
+    },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MemoryAccess {
+    //Physical address
+    pub addr: u32,
+    //false for kseg1 and noncacheable pages, the access goes straight to memory
+    pub cached: bool,
+    pub kind: AccessKind,
+}
+
+impl MemoryAccess {
+    #[inline]
+    pub fn is_store(&self) -> bool {
+        matches!(self.kind, AccessKind::Store { .. })
+    }
+}
+
+impl<'a> MipsCpu<'a> {
+    //Performs the access of the executing instruction, the pipeline defers it to MEM
+    pub(super) fn access(&mut self, access: MemoryAccess) {
+        if let Some(e) = self.pipeline.executing.as_mut() {
+            e.access = Some(access);
+            return;
+        }
+        if let Some((rt, value)) = self.complete_access(access) {
+            self.set_loaded_register(rt, value);
+        }
+    }
+
+    //Bus transaction of a load or store, returns the register a load writes and the loaded value
+    pub(super) fn complete_access(&mut self, access: MemoryAccess) -> Option<(u8, u32)> {
+        let addr = access.addr;
+        match access.kind {
+            AccessKind::Load {
+                width,
+                signed,
+                rt,
+                merge,
+            } => {
+                let loaded = match width {
+                    Width::Byte if signed => self.bus.read_byte(addr) as i8 as u32,
+                    Width::Byte => self.bus.read_byte(addr) as u32,
+                    Width::Halfword if signed => self.read_halfword(addr) as i16 as u32,
+                    Width::Halfword => self.read_halfword(addr) as u32,
+                    Width::Word => self.read_word(addr),
+                    Width::Left => {
+                        let shift = self.byte_in_word(addr) * 8;
+                        let kept = merge & ((1u32 << shift) - 1);
+                        (self.read_word(addr & !3) << shift) | kept
+                    }
+                    Width::Right => {
+                        let shift = (3 - self.byte_in_word(addr)) * 8;
+                        let kept = merge & !(0xFFFFFFFF >> shift);
+                        (self.read_word(addr & !3) >> shift) | kept
+                    }
+                };
+                Some((rt, loaded))
+            }
+            AccessKind::LoadCop1 { ft } => {
+                let loaded = self.read_word(addr);
+                self.cp1.write(ft, loaded);
+                None
+            }
+            AccessKind::Store { width, value } => {
+                match width {
+                    Width::Byte => self.bus.write_byte(addr, value as u8),
+                    Width::Halfword => self.write_halfword(addr, value as u16),
+                    Width::Word => self.write_word(addr, value),
+                    Width::Left => {
+                        let shift = self.byte_in_word(addr) * 8;
+                        self.write_bytes(addr & !3, value >> shift, 0xFFFFFFFF >> shift);
+                    }
+                    Width::Right => {
+                        let shift = (3 - self.byte_in_word(addr)) * 8;
+                        self.write_bytes(addr & !3, value << shift, 0xFFFFFFFF << shift);
+                    }
+                }
+                None
+            }
+        }
+    }
+
+    //Stores the bytes of word selected by mask to the aligned word at addr. The other bytes are
+    //neither read nor written, they may belong to a device.
+    fn write_bytes(&mut self, addr: u32, word: u32, mask: u32) {
+        for byte in 0..4 {
+            let shift = (3 - byte) * 8;
+            if (mask >> shift) & 0xFF != 0 {
+                //byte_in_word maps a position back to its address as well
+                let offset = self.byte_in_word(byte);
+                self.bus.write_byte(addr | offset, (word >> shift) as u8);
+            }
+        }
+    }
+
+    //Position of the addressed byte in its aligned word, counted from the most significant byte
+    #[inline]
+    fn byte_in_word(&self, addr: u32) -> u32 {
+        match self.endianness {
+            Endianness::Big => addr & 3,
+            Endianness::Little => 3 - (addr & 3),
+        }
+    }
+}
@@ -0,0 +1,293 @@
+//R3000 memory management. kuseg and kseg2 are mapped by a 64 entry fully associative TLB,
+//kseg0 and kseg1 are unmapped windows onto the first 512MB of physical memory.
+//Without an MMU every virtual address is used as the physical address, as before.
+use super::cp0::{Exception, ExceptionCode};
+
+pub const INDEX: u8 = 0;
+pub const RANDOM: u8 = 1;
+pub const ENTRY_LO: u8 = 2;
+pub const CONTEXT: u8 = 4;
+pub const ENTRY_HI: u8 = 10;
+
+const ENTRIES: u32 = 64;
+//Random never selects the first 8 entries, they hold mappings tlbwr must not replace
+const WIRED: u32 = 8;
+
+pub const KSEG0: u32 = 0x8000_0000;
+const KSEG1: u32 = 0xA000_0000;
+const KSEG2: u32 = 0xC000_0000;
+const SEGMENT_OFFSET: u32 = 0x1FFF_FFFF;
+
+//EntryHi holds the virtual page number and the address space id
+const VPN: u32 = 0xFFFF_F000;
+const ASID: u32 = 0x0000_0FC0;
+//EntryLo holds the page frame number and the noncacheable, dirty, valid and global bits
+const PFN: u32 = 0xFFFF_F000;
+const NONCACHEABLE: u32 = 1 << 11;
+const DIRTY: u32 = 1 << 10;
+const VALID: u32 = 1 << 9;
+const GLOBAL: u32 = 1 << 8;
+const ENTRY_LO_WRITABLE: u32 = 0xFFFF_FF00;
+//Index: probe failure bit and the entry in bits 13-8
+const PROBE_FAILURE: u32 = 1 << 31;
+const INDEX_MASK: u32 = 0x0000_3F00;
+//Context: page table base written by the kernel, BadVPN set by TLB exceptions to VA[30:12]
+const PTE_BASE: u32 = 0xFFE0_0000;
+const BAD_VPN: u32 = 0x7FFF_F000;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Access {
+    Fetch,
+    Load,
+    Store,
+}
+
+//Physical address of kseg0 and kseg1 addresses, used to place the program in memory
+pub fn unmapped_physical_address(addr: u32) -> u32 {
+    match addr {
+        KSEG0..KSEG2 => addr & SEGMENT_OFFSET,
+        _ => addr,
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+struct TlbEntry {
+    hi: u32,
+    lo: u32,
+}
+
+pub struct Mmu {
+    //Addresses are translated, otherwise they go to the bus unchanged
+    pub enabled: bool,
+    entries: [TlbEntry; ENTRIES as usize],
+    index: u32,
+    random: u32,
+    entry_hi: u32,
+    entry_lo: u32,
+    context: u32,
+    pub refills: u64,
+}
+
+impl Default for Mmu {
+    fn default() -> Self {
+        Mmu {
+            enabled: false,
+            entries: [TlbEntry::default(); ENTRIES as usize],
+            index: 0,
+            random: ENTRIES - 1,
+            entry_hi: 0,
+            entry_lo: 0,
+            context: 0,
+            refills: 0,
+        }
+    }
+}
+
+impl Mmu {
+    pub fn read(&self, register: u8) -> u32 {
+        match register {
+            INDEX => self.index,
+            RANDOM => self.random << 8,
+            ENTRY_LO => self.entry_lo,
+            CONTEXT => self.context,
+            ENTRY_HI => self.entry_hi,
+            _ => 0,
+        }
+    }
+
+    pub fn write(&mut self, register: u8, value: u32) {
+        match register {
+            INDEX => self.index = (self.index & PROBE_FAILURE) | (value & INDEX_MASK),
+            ENTRY_LO => self.entry_lo = value & ENTRY_LO_WRITABLE,
+            CONTEXT => self.context = (self.context & !PTE_BASE) | (value & PTE_BASE),
+            ENTRY_HI => self.entry_hi = value & (VPN | ASID),
+            //Random is read only
+            _ => (),
+        }
+    }
+
+    //Random counts down from 63 to 8 once per clock
+    #[inline]
+    pub fn tick(&mut self) {
+        self.random = match self.random {
+            WIRED => ENTRIES - 1,
+            r => r - 1,
+        };
+    }
+
+    //Entry mapping the page of vaddr in the current address space
+    fn lookup(&self, vaddr: u32) -> Option<usize> {
+        let asid = self.entry_hi & ASID;
+        self.entries
+            .iter()
+            .position(|e| e.hi & VPN == vaddr & VPN && (e.lo & GLOBAL != 0 || e.hi & ASID == asid))
+    }
+
+    //Physical address of an access or the TLB exception it raises
+    pub fn translate(&self, vaddr: u32, access: Access) -> Result<u32, Exception> {
+        if !self.enabled {
+            return Ok(vaddr);
+        }
+        if (KSEG0..KSEG2).contains(&vaddr) {
+            return Ok(vaddr & SEGMENT_OFFSET);
+        }
+        let code = match access {
+            Access::Store => ExceptionCode::TlbStore,
+            _ => ExceptionCode::TlbLoad,
+        };
+        let exception = Exception {
+            bad_vaddr: Some(vaddr),
+            ..Exception::new(code)
+        };
+        let entry = match self.lookup(vaddr) {
+            Some(i) => self.entries[i],
+            //misses in kuseg have their own fast refill vector
+            None => {
+                return Err(Exception {
+                    tlb_refill: vaddr < KSEG0,
+                    ..exception
+                })
+            }
+        };
+        if entry.lo & VALID == 0 {
+            return Err(exception);
+        }
+        if access == Access::Store && entry.lo & DIRTY == 0 {
+            return Err(Exception {
+                bad_vaddr: Some(vaddr),
+                ..Exception::new(ExceptionCode::TlbModified)
+            });
+        }
+        Ok((entry.lo & PFN) | (vaddr & !VPN))
+    }
+
+    //kseg1 and pages marked noncacheable bypass the caches
+    pub fn cached(&self, vaddr: u32) -> bool {
+        if !self.enabled {
+            return true;
+        }
+        match vaddr {
+            KSEG0..KSEG1 => true,
+            KSEG1..KSEG2 => false,
+            _ => self
+                .lookup(vaddr)
+                .is_none_or(|i| self.entries[i].lo & NONCACHEABLE == 0),
+        }
+    }
+
+    //A TLB exception leaves the failing page in Context and EntryHi for the handler
+    pub fn record_fault(&mut self, vaddr: u32, refill: bool) {
+        if refill {
+            self.refills += 1;
+        }
+        self.context = (self.context & PTE_BASE) | ((vaddr & BAD_VPN) >> 10);
+        self.entry_hi = (vaddr & VPN) | (self.entry_hi & ASID);
+    }
+
+    pub fn tlbr(&mut self) {
+        let entry = self.entries[(self.index >> 8) as usize & 0x3F];
+        self.entry_hi = entry.hi;
+        self.entry_lo = entry.lo;
+    }
+
+    pub fn tlbwi(&mut self) {
+        self.write_entry((self.index >> 8) as usize & 0x3F);
+    }
+
+    pub fn tlbwr(&mut self) {
+        self.write_entry(self.random as usize);
+    }
+
+    fn write_entry(&mut self, index: usize) {
+        self.entries[index] = TlbEntry {
+            hi: self.entry_hi,
+            lo: self.entry_lo,
+        };
+    }
+
+    pub fn tlbp(&mut self) {
+        self.index = match self.lookup(self.entry_hi) {
+            Some(i) => (i as u32) << 8,
+            None => PROBE_FAILURE | (self.index & INDEX_MASK),
+        };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn mmu_with_entry(index: u32, hi: u32, lo: u32) -> Mmu {
+        let mut mmu = Mmu {
+            enabled: true,
+            ..Default::default()
+        };
+        mmu.write(INDEX, index << 8);
+        mmu.write(ENTRY_HI, hi);
+        mmu.write(ENTRY_LO, lo);
+        mmu.tlbwi();
+        mmu
+    }
+
+    #[test]
+    fn segments_and_mapping() {
+        //asid 1, page 0x00400000 at frame 0x00012000, writable
+        let mut mmu = mmu_with_entry(3, 0x0040_0040, 0x0001_2000 | DIRTY | VALID);
+        assert_eq!(mmu.translate(0x8000_1234, Access::Load), Ok(0x1234));
+        assert_eq!(mmu.translate(0xBFC0_0000, Access::Fetch), Ok(0x1FC0_0000));
+        assert_eq!(mmu.translate(0x0040_0ABC, Access::Store), Ok(0x0001_2ABC));
+        assert!(mmu.cached(0x8000_1234) && mmu.cached(0x0040_0ABC));
+        assert!(!mmu.cached(0xBFC0_0000));
+        //another address space misses into the refill vector
+        mmu.write(ENTRY_HI, 0x80);
+        let miss = mmu.translate(0x0040_0ABC, Access::Load).unwrap_err();
+        assert_eq!(miss.code, ExceptionCode::TlbLoad);
+        assert!(miss.tlb_refill);
+        //kseg2 misses use the general vector
+        assert!(
+            !mmu.translate(0xC000_0000, Access::Load)
+                .unwrap_err()
+                .tlb_refill
+        );
+        mmu.record_fault(0x0040_0ABC, true);
+        assert_eq!(mmu.read(ENTRY_HI), 0x0040_0080);
+        assert_eq!(mmu.read(CONTEXT), 0x0000_1000);
+        //bit 31 of a kseg2 address stays out of PTEBase
+        mmu.write(CONTEXT, 0x8000_0000);
+        mmu.record_fault(0xC000_1ABC, false);
+        assert_eq!(mmu.read(CONTEXT), 0x8010_0004);
+        mmu.write(ENTRY_HI, 0x0040_0040);
+        mmu.tlbp();
+        assert_eq!(mmu.read(INDEX), 3 << 8);
+        mmu.write(ENTRY_HI, 0x0050_0040);
+        mmu.tlbp();
+        assert_eq!(mmu.read(INDEX) & PROBE_FAILURE, PROBE_FAILURE);
+    }
+
+    #[test]
+    fn invalid_and_clean_pages() {
+        let mmu = mmu_with_entry(0, 0x0000_1000, 0x0000_2000 | GLOBAL);
+        let invalid = mmu.translate(0x1000, Access::Store).unwrap_err();
+        assert_eq!(
+            (invalid.code, invalid.tlb_refill),
+            (ExceptionCode::TlbStore, false)
+        );
+        let mmu = mmu_with_entry(0, 0x0000_1000, 0x0000_2000 | GLOBAL | VALID);
+        assert_eq!(mmu.translate(0x1004, Access::Load), Ok(0x2004));
+        let clean = mmu.translate(0x1004, Access::Store).unwrap_err();
+        assert_eq!(clean.code, ExceptionCode::TlbModified);
+        assert_eq!(clean.bad_vaddr, Some(0x1004));
+    }
+
+    #[test]
+    fn random_skips_wired_entries() {
+        let mut mmu = Mmu::default();
+        assert_eq!(mmu.read(RANDOM), 63 << 8);
+        for _ in 0..55 {
+            mmu.tick();
+        }
+        assert_eq!(mmu.read(RANDOM), 8 << 8);
+        mmu.tick();
+        assert_eq!(mmu.read(RANDOM), 63 << 8);
+    }
+}
@@ -0,0 +1,1836 @@
+//Cycle driven model of the five stage IF/ID/EX/MEM/WB pipeline.
+//Every instruction computes its result with its reference implementation in instructions.rs
+//when it enters EX, loads and stores access the bus in MEM and the register file is written in
+//WB. Results that have not been written back reach EX through the bypass network. The latches
+//carry everything the later stages and the timing model need to know about an instruction.
+use super::branch_prediction::*;
+use super::branch_target::*;
+use super::cache::*;
+use super::cp0::{Exception, ExceptionCode};
+use super::diagram::*;
+use super::forwarding::*;
+use super::fpu::*;
+use super::hazard::*;
+use super::instruction_info::*;
+use super::instructions::*;
+use super::mdu::*;
+use super::memory_access::*;
+use super::write_buffer::*;
+use super::MipsCpu;
+use std::fmt;
+
+//What the fetch assumed about a branch, checked when the branch is resolved in EX
+#[derive(Clone, Copy, Default)]
+pub struct Prediction {
+    //Predictions of a conditional branch, see BranchPredictionUnit::predict
+    pub directions: Option<u8>,
+    //Target popped from the return address stack
+    pub return_target: Option<u32>,
+    //Return address stack before the fetch, restored if the instruction is squashed
+    pub ras: RasCheckpoint,
+}
+
+//Raw instruction word fetched in IF, waiting to be decoded in ID
+#[derive(Clone, Copy)]
+pub struct IfIdLatch {
+    //Fetch order of the instruction
+    pub seq: u64,
+    pub pc: u32,
+    //None if the fetch address is not mapped on the bus, not word aligned or not in the TLB
+    pub instruction: Option<u32>,
+    pub prediction: Prediction,
+}
+
+//Decoded instruction waiting to be executed in EX
+#[derive(Clone, Copy)]
+pub struct IdExLatch {
+    pub seq: u64,
+    pub pc: u32,
+    pub instruction: Option<InstructionInfos>,
+    pub prediction: Prediction,
+}
+
+//Executed instruction waiting for its memory access
+#[derive(Clone, Copy)]
+pub struct ExMemLatch {
+    pub seq: u64,
+    pub pc: u32,
+    pub instruction: InstructionInfos,
+    //Destination register and the value computed for it
+    pub result: Option<(u8, u32)>,
+    //Load or store with its physical address
+    pub access: Option<MemoryAccess>,
+}
+
+//Instruction waiting to write back its result
+#[derive(Clone, Copy)]
+pub struct MemWbLatch {
+    pub seq: u64,
+    pub pc: u32,
+    pub instruction: InstructionInfos,
+    //Computed in EX or loaded in MEM
+    pub result: Option<(u8, u32)>,
+}
+
+//The instruction in EX while its reference implementation runs
+#[derive(Default)]
+pub struct Execution {
+    //Result of the previous instruction bypassed from MEM, a load result arrives too late for it
+    forwarded: Option<(u8, u32)>,
+    //Result of the previous instruction including loads, lwl and lwr merge with it
+    merge: Option<(u8, u32)>,
+    pub result: Option<(u8, u32)>,
+    pub access: Option<MemoryAccess>,
+}
+
+#[derive(Default)]
+pub struct Pipeline {
+    //Address of the next instruction IF will fetch
+    pub fetch_pc: u32,
+
+    //A latch holding None is a bubble
+    pub if_id: Option<IfIdLatch>,
+    pub id_ex: Option<IdExLatch>,
+    pub ex_mem: Option<ExMemLatch>,
+    pub mem_wb: Option<MemWbLatch>,
+    //Set while the instruction in EX is executed
+    pub executing: Option<Execution>,
+
+    pub hazards: HazardUnit,
+    pub mdu: MultiplyDivideUnit,
+    pub fpu: FloatingPointUnit,
+    pub predictors: BranchPredictionUnit,
+    pub btb: BranchTargetBuffer,
+    pub ras: ReturnAddressStack,
+    //Predicted target of the branch whose delay slot is fetched next
+    predicted_target: Option<u32>,
+
+    //No cache means every access completes without delay
+    pub icache: Option<Cache>,
+    pub dcache: Option<Cache>,
+    pub write_buffer: WriteBuffer,
+    //Cycles of every bus transaction caused by a cache
+    pub miss_penalty: u64,
+    //Cycles the whole pipeline stays frozen for an outstanding cache miss
+    memory_stall: u64,
+    pub memory_stall_cycles: u64,
+
+    pub diagram: Option<PipelineDiagram>,
+
+    //Number of instructions fetched so far
+    fetched: u64,
+    pub cycles: u64,
+    pub retired: u64,
+    //Wrong path instructions squashed after a branch was mispredicted
+    pub flush_cycles: u64,
+
+    //The next instruction entering EX is in a branch delay slot
+    in_delay_slot: bool,
+    pub delay_slots: u64,
+    //Delay slots holding something else than a nop
+    pub filled_delay_slots: u64,
+}
+
+impl Pipeline {
+    pub fn new(pc: u32) -> Self {
+        Pipeline {
+            fetch_pc: pc,
+            ..Default::default()
+        }
+    }
+
+    #[inline]
+    fn trace(&mut self, seq: u64, stage: Stage) {
+        if let Some(d) = self.diagram.as_mut() {
+            d.occupied(seq, stage, self.cycles);
+        }
+    }
+
+    pub fn stall_cycles(&self) -> u64 {
+        self.hazards.stall_cycles
+            + self.mdu.stall_cycles
+            + self.fpu.stall_cycles
+            + self.memory_stall_cycles
+    }
+
+    //Freezes the pipeline for the bus transactions of a cache access
+    fn access_memory(&mut self, traffic: MemoryTraffic) {
+        let transactions =
+            traffic.line_fills + traffic.write_backs + traffic.word_writes + traffic.word_reads;
+        self.memory_stall += transactions as u64 * self.miss_penalty;
+    }
+
+    //Load or store of the instruction in MEM
+    fn access_data(&mut self, addr: u32, store: bool, cached: bool) {
+        let cycle = self.cycles + self.memory_stall;
+        if !store && self.write_buffer.forward(addr, cycle) {
+            return;
+        }
+        let mut traffic = match self.dcache.as_mut() {
+            //uncached accesses bypass the data cache
+            Some(_) if !cached => MemoryTraffic {
+                word_writes: store as u32,
+                word_reads: !store as u32,
+                ..Default::default()
+            },
+            Some(cache) if store => cache.write(addr),
+            Some(cache) => cache.read(addr),
+            //without a data cache every store is written to memory
+            None if store && self.write_buffer.enabled() => MemoryTraffic {
+                word_writes: 1,
+                ..Default::default()
+            },
+            None => return,
+        };
+        if self.write_buffer.enabled() && traffic.word_writes > 0 {
+            self.memory_stall += self.write_buffer.push(addr, cycle);
+            traffic.word_writes = 0;
+        }
+        self.access_memory(traffic);
+    }
+
+    //An exception in EX discards the instruction that raised it and everything fetched after it,
+    //the older instructions in MEM and WB complete. ras is the checkpoint of the discarded
+    //instruction in EX.
+    fn flush(&mut self, vector: u32, ras: RasCheckpoint) {
+        if self.if_id.take().is_some() {
+            self.flush_cycles += 1;
+        }
+        self.ras.restore(ras);
+        self.hazards.proceed();
+        self.in_delay_slot = false;
+        self.predicted_target = None;
+        self.fetch_pc = vector;
+    }
+
+    //Discards the instruction in IF/ID and fetches it again next
+    fn refetch(&mut self) {
+        if let Some(l) = self.if_id.take() {
+            self.flush_cycles += 1;
+            self.ras.restore(l.prediction.ras);
+            self.hazards.proceed();
+            self.predicted_target = None;
+            self.fetch_pc = l.pc;
+        }
+    }
+
+    //Every instruction in the pipeline stays in its stage for a frozen cycle
+    fn hold(&mut self) {
+        let seqs = [
+            self.if_id.map(|l| l.seq),
+            self.id_ex.map(|l| l.seq),
+            self.ex_mem.map(|l| l.seq),
+            self.mem_wb.map(|l| l.seq),
+        ];
+        if let Some(d) = self.diagram.as_mut() {
+            for seq in seqs.iter().flatten() {
+                d.held(*seq, self.cycles);
+            }
+        }
+    }
+
+    //Value of a register bypassed to the instruction in EX
+    #[inline]
+    pub fn bypass(&self, register: u8) -> Option<u32> {
+        match self.executing.as_ref()?.forwarded {
+            Some((r, value)) if r == register => Some(value),
+            _ => None,
+        }
+    }
+
+    //Value lwl and lwr in EX merge with, a load right before them is bypassed as well
+    #[inline]
+    pub fn merge_bypass(&self, register: u8) -> Option<u32> {
+        match self.executing.as_ref()?.merge {
+            Some((r, value)) if r == register => Some(value),
+            _ => None,
+        }
+    }
+
+    //Instructions in EX/MEM and MEM/WB, the youngest first
+    fn in_flight(&self) -> [Option<InFlight>; 2] {
+        [
+            self.ex_mem.map(|l| InFlight {
+                pc: l.pc,
+                instruction: l.instruction,
+            }),
+            self.mem_wb.map(|l| InFlight {
+                pc: l.pc,
+                instruction: l.instruction,
+            }),
+        ]
+    }
+}
+
+impl fmt::Display for Pipeline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Cycles: {}", self.cycles)?;
+        writeln!(f, "Retired instructions: {}", self.retired)?;
+        writeln!(
+            f,
+            "CPI: {:.3}",
+            self.cycles as f64 / self.retired.max(1) as f64
+        )?;
+        writeln!(
+            f,
+            "Stall cycles: {} (data hazards: {}, HI/LO interlocks: {}, FPU interlocks: {}, cache misses: {})",
+            self.stall_cycles(),
+            self.hazards.stall_cycles,
+            self.mdu.stall_cycles,
+            self.fpu.stall_cycles,
+            self.memory_stall_cycles
+        )?;
+        writeln!(f, "Flush cycles: {}", self.flush_cycles)?;
+        let predictions = self.predictors.active_statistics();
+        writeln!(
+            f,
+            "Conditional branches mispredicted: {} of {}",
+            predictions.total - predictions.correct,
+            predictions.total
+        )?;
+        writeln!(
+            f,
+            "Branch delay slots filled: {} of {} ({:.1}%)",
+            self.filled_delay_slots,
+            self.delay_slots,
+            100.0 * self.filled_delay_slots as f64 / self.delay_slots.max(1) as f64
+        )
+    }
+}
+
+impl<'a> MipsCpu<'a> {
+    pub fn set_diagram(&mut self, selection: DiagramSelection) {
+        self.pipeline.diagram = Some(PipelineDiagram::new(selection));
+    }
+
+    pub fn set_predictor(&mut self, predictor: BranchPredictorKind, bits: u32) {
+        self.pipeline.predictors = BranchPredictionUnit::new(predictor, bits);
+    }
+
+    pub fn set_branch_target_buffer(&mut self, entries: usize) {
+        self.pipeline.btb = BranchTargetBuffer::new(entries);
+    }
+
+    pub fn set_return_address_stack(&mut self, entries: usize) {
+        self.pipeline.ras = ReturnAddressStack::new(entries);
+    }
+
+    pub fn set_caches(
+        &mut self,
+        icache: Option<CacheConfig>,
+        dcache: Option<CacheConfig>,
+        miss_penalty: u64,
+    ) {
+        self.pipeline.icache = icache.map(|c| Cache::new("I-cache", c));
+        self.pipeline.dcache = dcache.map(|c| Cache::new("D-cache", c));
+        self.pipeline.miss_penalty = miss_penalty;
+    }
+
+    pub fn set_write_buffer(&mut self, entries: usize, drain_rate: u64) {
+        self.pipeline.write_buffer = WriteBuffer::new(entries, drain_rate);
+    }
+
+    pub fn set_mdu_latencies(&mut self, mult: u64, div: u64) {
+        self.pipeline.mdu.mult_latency = mult;
+        self.pipeline.mdu.div_latency = div;
+    }
+
+    #[inline]
+    pub fn set_forwarding(&mut self, forwarding: Forwarding) {
+        self.pipeline.hazards.forwarding = forwarding;
+    }
+
+    //Advances every stage by one clock.
+    //The stages are evaluated from WB to IF so each one consumes the latch its predecessor
+    //filled in the previous cycle.
+    pub fn cycle(&mut self) {
+        self.pipeline.cycles += 1;
+        self.pipeline.write_buffer.sample(self.pipeline.cycles);
+        self.clock();
+        if self.pipeline.memory_stall > 0 {
+            self.pipeline.memory_stall -= 1;
+            self.pipeline.memory_stall_cycles += 1;
+            self.pipeline.hold();
+            return;
+        }
+        self.write_back_stage();
+        self.memory_stage();
+        let redirect = self.execute_stage();
+        self.decode_stage();
+        self.fetch_stage();
+        if let Some((branch_pc, target, nullify)) = redirect {
+            self.redirect_fetch(branch_pc, target, nullify);
+        }
+    }
+
+    //Retires the instructions that have already passed EX, nothing new enters the pipeline
+    pub fn drain(&mut self) {
+        loop {
+            let p = &mut self.pipeline;
+            p.cycles += p.memory_stall;
+            p.memory_stall_cycles += p.memory_stall;
+            p.memory_stall = 0;
+            if p.ex_mem.is_none() && p.mem_wb.is_none() {
+                return;
+            }
+            p.cycles += 1;
+            self.write_back_stage();
+            self.memory_stage();
+        }
+    }
+
+    //Emulated syscalls read and write the register file directly, the instruction in MEM/WB
+    //writes back its result first
+    pub(super) fn serialize(&mut self) {
+        if self.pipeline.executing.take().is_none() {
+            return;
+        }
+        if let Some((r, value)) = self.pipeline.mem_wb.as_mut().and_then(|l| l.result.take()) {
+            self.set_register(r, value);
+        }
+    }
+
+    //The register file is written in the first half of the cycle, before EX reads it
+    fn write_back_stage(&mut self) {
+        if let Some(l) = self.pipeline.mem_wb.take() {
+            if let Some((r, value)) = l.result {
+                self.set_register(r, value);
+            }
+            self.pipeline.retired += 1;
+            self.pipeline.trace(l.seq, Stage::WriteBack);
+        }
+    }
+
+    fn memory_stage(&mut self) {
+        let l = match self.pipeline.ex_mem.take() {
+            Some(l) => l,
+            None => return,
+        };
+        self.pipeline.trace(l.seq, Stage::Memory);
+        let mut result = l.result;
+        if let Some(access) = l.access {
+            self.pipeline
+                .access_data(access.addr, access.is_store(), access.cached);
+            result = self.complete_access(access).or(result);
+        }
+        self.pipeline.mem_wb = Some(MemWbLatch {
+            seq: l.seq,
+            pc: l.pc,
+            instruction: l.instruction,
+            result,
+        });
+    }
+
+    //Returns the address of a branch, the address executed after its delay slot and whether the
+    //delay slot is nullified
+    fn execute_stage(&mut self) -> Option<(u32, u32, bool)> {
+        let latch = self.pipeline.id_ex.take()?;
+        self.pipeline.trace(latch.seq, Stage::Execute);
+        self.pc = latch.pc;
+        //an interrupt is taken by the instruction entering EX, it restarts after the handler.
+        //A failed fetch traps here as well, so older instructions complete first.
+        let exception = if self.cp0.interrupt_pending() {
+            Some(Exception::new(ExceptionCode::Interrupt))
+        } else if latch.instruction.is_none() {
+            //nothing on the bus answered a fetch that translated
+            let bus_error = Exception::new(ExceptionCode::InstructionBus);
+            Some(self.fetch_address(latch.pc).err().unwrap_or(bus_error))
+        } else {
+            None
+        };
+        if let Some(exception) = exception {
+            let vector = self.enter_exception(exception, self.pipeline.in_delay_slot);
+            self.pipeline.flush(vector, latch.prediction.ras);
+            return None;
+        }
+        //a failed fetch has trapped above
+        let instruction = latch.instruction?;
+        //MEM has already run this cycle, so the previous instruction is in MEM/WB and the one
+        //before it has been written back
+        let previous = self
+            .pipeline
+            .mem_wb
+            .and_then(|l| l.result.map(|r| (r, l.instruction.is_load())));
+        self.pipeline.executing = Some(Execution {
+            forwarded: previous.filter(|(_, load)| !load).map(|(r, _)| r),
+            merge: previous.map(|(r, _)| r),
+            ..Default::default()
+        });
+        self.execute_instruction(instruction);
+        let execution = self.pipeline.executing.take().unwrap_or_default();
+        if let Some(exception) = self.exception.take() {
+            let vector = self.enter_exception(exception, self.pipeline.in_delay_slot);
+            self.pipeline.flush(vector, latch.prediction.ras);
+            return None;
+        }
+        //a TLB write can map the page of the instruction fetched behind it
+        if instruction.writes_tlb() {
+            self.pipeline.refetch();
+        }
+        self.pipeline.mdu.start(&instruction, self.pipeline.cycles);
+        self.pipeline.fpu.start(&instruction, self.pipeline.cycles);
+        if self.pipeline.in_delay_slot {
+            self.pipeline.delay_slots += 1;
+            if !instruction.is_nop() {
+                self.pipeline.filled_delay_slots += 1;
+            }
+        }
+        self.pipeline.in_delay_slot = instruction.is_branch();
+        self.pipeline.ex_mem = Some(ExMemLatch {
+            seq: latch.seq,
+            pc: latch.pc,
+            instruction,
+            result: execution.result,
+            access: execution.access,
+        });
+        if !instruction.is_branch() {
+            return None;
+        }
+        let taken = std::mem::replace(&mut self.branch, false);
+        let nullify = std::mem::replace(&mut self.nullify, false);
+        let p = &mut self.pipeline;
+        if let Some(predictions) = latch.prediction.directions {
+            p.predictors.resolve(latch.pc, predictions, taken);
+        }
+        if let Some(predicted) = latch.prediction.return_target {
+            p.ras.resolve(predicted, self.branch_target);
+        }
+        if taken {
+            p.btb.update(latch.pc, self.branch_target);
+            Some((latch.pc, self.branch_target, false))
+        } else {
+            Some((latch.pc, latch.pc.wrapping_add(8), nullify))
+        }
+    }
+
+    //Holds the instruction in IF/ID and inserts a bubble into EX while a hazard is pending
+    fn decode_stage(&mut self) {
+        let latch = match self.pipeline.if_id {
+            Some(l) => l,
+            None => return,
+        };
+        let instruction = latch
+            .instruction
+            .map(|w| decode_opcode(OpDecodedInstruction::decode(w), self.isa));
+        self.pipeline.trace(latch.seq, Stage::Decode);
+        if let Some(i) = instruction {
+            let p = &mut self.pipeline;
+            if let Some(d) = p.diagram.as_mut() {
+                d.decoded(latch.seq, || i.to_string());
+            }
+            let in_flight = p.in_flight();
+            if let Some(hazard) = p.hazards.detect(latch.pc, &i, &in_flight) {
+                let producer = in_flight
+                    .iter()
+                    .flatten()
+                    .find(|x| x.pc == hazard.producer_pc)
+                    .unwrap();
+                p.hazards
+                    .stall(hazard, producer.instruction.memonic(), i.memonic());
+                return;
+            }
+            if p.mdu.interlock(&i, p.cycles) {
+                p.mdu.stall_cycles += 1;
+                return;
+            }
+            if p.fpu.interlock(&i, p.cycles) {
+                p.fpu.stall_cycles += 1;
+                return;
+            }
+            p.hazards.issue(&i, &in_flight);
+        }
+        self.pipeline.hazards.proceed();
+        self.pipeline.if_id = None;
+        self.pipeline.id_ex = Some(IdExLatch {
+            seq: latch.seq,
+            pc: latch.pc,
+            instruction,
+            prediction: latch.prediction,
+        });
+    }
+
+    //IF holds while ID is stalled.
+    //Branches are predecoded when fetched, the instruction after the delay slot is fetched from
+    //the predicted target if the branch is unconditional or predicted taken and a target is known.
+    fn fetch_stage(&mut self) {
+        if self.pipeline.if_id.is_some() {
+            return;
+        }
+        let pc = self.pipeline.fetch_pc;
+        let (instruction, addr) = match self.fetch_address(pc) {
+            Ok(addr) if self.bus.is_mapped(addr) => (Some(self.read_word(addr)), addr),
+            _ => (None, pc),
+        };
+        let cached = self.cp0.mmu.cached(pc);
+        let p = &mut self.pipeline;
+        if let Some(cache) = p.icache.as_mut().filter(|_| instruction.is_some()) {
+            //the cache is indexed and tagged with the physical address
+            let traffic = if cached {
+                cache.read(addr)
+            } else {
+                MemoryTraffic {
+                    word_reads: 1,
+                    ..Default::default()
+                }
+            };
+            p.access_memory(traffic);
+        }
+        let seq = p.fetched;
+        p.fetched += 1;
+        if let Some(d) = p.diagram.as_mut() {
+            d.fetched(seq, pc, p.cycles);
+        }
+        p.trace(seq, Stage::Fetch);
+        p.fetch_pc = p.predicted_target.take().unwrap_or(pc.wrapping_add(4));
+        let mut prediction = Prediction {
+            ras: p.ras.checkpoint(),
+            ..Default::default()
+        };
+        if let Some((kind, call)) = instruction.and_then(|w| predecode_branch(pc, w)) {
+            p.predicted_target = match kind {
+                BranchKind::Conditional(target) => {
+                    let m = p.predictors.predict(pc, target);
+                    prediction.directions = Some(m);
+                    let target = p.btb.predict(pc, Some(target));
+                    target.filter(|_| p.predictors.taken(m))
+                }
+                BranchKind::Jump(target) => p.btb.predict(pc, Some(target)),
+                BranchKind::Return => match p.ras.pop() {
+                    Some(target) => {
+                        prediction.return_target = Some(target);
+                        Some(target)
+                    }
+                    None => p.btb.predict(pc, None),
+                },
+                BranchKind::Indirect => p.btb.predict(pc, None),
+            };
+            if call {
+                p.ras.push(pc.wrapping_add(8));
+            }
+        }
+        p.if_id = Some(IfIdLatch {
+            seq,
+            pc,
+            instruction,
+            prediction,
+        });
+    }
+
+    //Branches are resolved in EX while their delay slot is decoded, so the instruction fetched
+    //in the same cycle is the only one that may be on the wrong path.
+    fn redirect_fetch(&mut self, branch_pc: u32, target: u32, nullify: bool) {
+        let delay_slot = branch_pc.wrapping_add(4);
+        let delay_slot_decoded = matches!(self.pipeline.id_ex, Some(l) if l.pc == delay_slot);
+        let p = &mut self.pipeline;
+        //the return address stack goes back to the state before the oldest squashed fetch
+        let mut squashed = None;
+        //a branch likely that is not taken squashes its delay slot wherever it is
+        if nullify {
+            p.flush_cycles += 1;
+            p.in_delay_slot = false;
+            squashed = match delay_slot_decoded {
+                true => p.id_ex.take().map(|l| l.prediction.ras),
+                false => {
+                    p.hazards.proceed();
+                    p.if_id.take().map(|l| l.prediction.ras)
+                }
+            };
+        }
+        let on_path = match p.if_id {
+            Some(l) if delay_slot_decoded && l.pc == target => true,
+            Some(l) if delay_slot_decoded => {
+                //the squashed instruction may have been a predicted branch itself
+                p.predicted_target = None;
+                p.if_id = None;
+                p.flush_cycles += 1;
+                squashed = squashed.or(Some(l.prediction.ras));
+                false
+            }
+            _ => false,
+        };
+        if let Some(checkpoint) = squashed {
+            p.ras.restore(checkpoint);
+        }
+        if !on_path {
+            p.fetch_pc = target;
+        }
+    }
+}
+
+enum BranchKind {
+    //Branches and jumps with the target they encode
+    Conditional(u32),
+    Jump(u32),
+    //jr $ra
+    Return,
+    //Other jr and jalr
+    Indirect,
+}
+
+//Kind of the branch in the fetched word and whether it is a call writing $ra
+fn predecode_branch(pc: u32, word: u32) -> Option<(BranchKind, bool)> {
+    let offset = pc
+        .wrapping_add(4)
+        .wrapping_add(((word as i16 as i32) << 2) as u32);
+    let rs = (word >> 21) & 0x1F;
+    match word >> 26 {
+        //jr, jalr
+        0b000000 => match word & 0x3F {
+            0b001000 if rs == 31 => Some((BranchKind::Return, false)),
+            0b001000 => Some((BranchKind::Indirect, false)),
+            0b001001 => Some((BranchKind::Indirect, true)),
+            _ => None,
+        },
+        //bltz, bgez, bltzal, bgezal and their likely forms
+        0b000001 => match (word >> 16) & 0x1F {
+            0b00000..=0b00011 => Some((BranchKind::Conditional(offset), false)),
+            0b10000..=0b10011 => Some((BranchKind::Conditional(offset), true)),
+            _ => None,
+        },
+        //j, jal
+        0b000010 | 0b000011 => Some((
+            BranchKind::Jump((pc & 0xF000_0000) | ((word & 0x03FF_FFFF) << 2)),
+            word >> 26 == 0b000011,
+        )),
+        //beq, bne, blez, bgtz and their likely forms
+        0b000100..=0b000111 | 0b010100..=0b010111 => Some((BranchKind::Conditional(offset), false)),
+        //bc1f, bc1t, bc1fl, bc1tl
+        0b010001 if rs == 0b01000 => Some((BranchKind::Conditional(offset), false)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::branch_prediction::BranchPredictorKind;
+    use super::super::cache::CacheConfig;
+    use super::super::division::DivideByZero;
+    use super::super::forwarding::Forwarding;
+    use super::super::isa::IsaLevel;
+    use super::super::load_delay::LoadDelay;
+    use super::super::*;
+    use crate::bus_objects::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    //Memory holding the given big endian program at address 0 and some scratch memory behind it.
+    //The handler is placed at its vector, vectors in kseg0 get their own memory at 0x80000000.
+    fn program_memory(program: &[u32], handler: Option<(u32, &[u32])>) -> Vec<Box<dyn BusObject>> {
+        let mut mem = memory::Memory::new(
+            vec![0; 0x2000].into_boxed_slice(),
+            MemoryMapping {
+                start: 0,
+                size: 0x2000,
+            },
+        );
+        let mut vectors = memory::Memory::new(
+            vec![0; 0x200].into_boxed_slice(),
+            MemoryMapping {
+                start: 0x8000_0000,
+                size: 0x200,
+            },
+        );
+        for (index, word) in program.iter().enumerate() {
+            mem.write_w(index as u32 * 4, word.to_be());
+        }
+        if let Some((vector, handler)) = handler {
+            for (index, word) in handler.iter().enumerate() {
+                let addr = vector + index as u32 * 4;
+                match addr >= 0x8000_0000 {
+                    true => vectors.write_w(addr - 0x8000_0000, word.to_be()),
+                    false => mem.write_w(addr, word.to_be()),
+                }
+            }
+        }
+        vec![Box::new(mem), Box::new(vectors)]
+    }
+
+    //Bus with only the program memory, tests with devices add them to program_memory
+    fn program_bus(program: &[u32], handler: Option<(u32, &[u32])>) -> Bus {
+        Bus::new(0, 0, program_memory(program, handler)).unwrap()
+    }
+
+    //Steps or cycles until the condition holds, the pipeline then completes the instructions
+    //still in MEM and WB
+    fn run(cpu: &mut MipsCpu, pipelined: bool, until: impl Fn(&MipsCpu) -> bool) {
+        for _ in 0..1000 {
+            if until(cpu) {
+                if pipelined {
+                    cpu.drain();
+                }
+                return;
+            }
+            match pipelined {
+                true => cpu.cycle(),
+                false => cpu.step(),
+            }
+        }
+        panic!("Condition not reached within 1000 steps");
+    }
+
+    #[test]
+    fn pipeline_matches_single_step() {
+        let program = [
+            0x2408_0005, //addiu t0,zero,5
+            0x2409_0007, //addiu t1,zero,7
+            0x0109_5021, //addu t2,t0,t1
+            0xAC0A_0100, //sw t2,0x100(zero)
+            0x8C0B_0100, //lw t3,0x100(zero)
+            0x016A_6021, //addu t4,t3,t2
+        ];
+        let mut b = program_bus(&program, None);
+        let mut cpu = MipsCpu::new(&mut b, 0);
+        while cpu.pipeline.retired < program.len() as u64 {
+            cpu.cycle();
+        }
+        assert_eq!(
+            cpu.get_register(12),
+            24,
+            "Wrong result after pipelined execution"
+        );
+        assert_eq!(
+            cpu.pipeline.cycles,
+            program.len() as u64 + 4 + cpu.pipeline.stall_cycles()
+        );
+    }
+
+    #[test]
+    fn loads_complete_in_mem_and_write_back_in_wb() {
+        let program = [
+            0x2408_002A, //addiu t0,zero,42
+            0xAC08_0100, //sw t0,0x100(zero)
+            0x8C09_0100, //lw t1,0x100(zero)
+        ];
+        let mut b = program_bus(&program, None);
+        let mut cpu = MipsCpu::new(&mut b, 0);
+        //sw has passed EX
+        for _ in 0..4 {
+            cpu.cycle();
+        }
+        assert!(cpu.pipeline.ex_mem.unwrap().access.is_some());
+        assert_eq!(cpu.bus.read_w(0x100), 0);
+        cpu.cycle();
+        assert_eq!(cpu.bus.read_w(0x100), 42u32.to_be(), "sw stores in MEM");
+        cpu.cycle();
+        assert_eq!(cpu.pipeline.mem_wb.unwrap().result, Some((9, 42)));
+        assert_eq!(cpu.get_register(9), 0);
+        cpu.cycle();
+        assert_eq!(cpu.get_register(9), 42, "lw writes back in WB");
+    }
+
+    #[test]
+    fn taken_branch_squashes_wrong_path() {
+        let program = [
+            0x1000_0003, //beq zero,zero,+3
+            0x2408_0001, //addiu t0,zero,1 (delay slot)
+            0x2409_0001, //addiu t1,zero,1 (wrong path)
+            0x2409_0002, //addiu t1,zero,2 (wrong path)
+            0x240A_0003, //addiu t2,zero,3
+        ];
+        let mut b = program_bus(&program, None);
+        let mut cpu = MipsCpu::new(&mut b, 0);
+        for _ in 0..8 {
+            cpu.cycle();
+        }
+        assert_eq!(cpu.get_register(8), 1, "Delay slot was not executed");
+        assert_eq!(
+            cpu.get_register(9),
+            0,
+            "Wrong path instruction was executed"
+        );
+        assert_eq!(cpu.get_register(10), 3, "Branch target was not executed");
+        assert_eq!(cpu.pipeline.flush_cycles, 1);
+        assert_eq!(cpu.pipeline.delay_slots, 1);
+        assert_eq!(cpu.pipeline.filled_delay_slots, 1);
+    }
+
+    #[test]
+    fn dependent_instruction_stalls_until_write_back() {
+        let program = [
+            0x2408_0005, //addiu t0,zero,5
+            0x2509_0001, //addiu t1,t0,1
+            0x2529_0001, //addiu t1,t1,1
+        ];
+        let mut b = program_bus(&program, None);
+        let mut cpu = MipsCpu::new(&mut b, 0);
+        cpu.set_forwarding(Forwarding::None);
+        for _ in 0..program.len() + 8 {
+            cpu.cycle();
+        }
+        assert_eq!(cpu.get_register(9), 7);
+        assert_eq!(cpu.pipeline.hazards.stall_cycles, 4);
+        assert_eq!(cpu.pipeline.hazards.records.len(), 2);
+        let record = cpu.pipeline.hazards.records.values().next().unwrap();
+        assert_eq!(record.occurrences, 1);
+        assert_eq!(record.stall_cycles, 2);
+    }
+
+    #[test]
+    fn syscall_code_is_no_destination() {
+        //a syscall code with t0 in the bits of rd
+        let stalls = |code: u32| {
+            let program = [
+                0x2402_0009,        //addiu v0,zero,9 (sbrk)
+                0x0000_000C | code, //syscall
+                0x2509_0001,        //addiu t1,t0,1
+            ];
+            let mut b = program_bus(&program, None);
+            let mut cpu = MipsCpu::new(&mut b, 0);
+            cpu.set_forwarding(Forwarding::None);
+            cpu.set_syscalls(syscall::SyscallEmulation::Spim, 0x800, 0x800);
+            while cpu.pipeline.retired < program.len() as u64 {
+                cpu.cycle();
+            }
+            cpu.pipeline.hazards.stall_cycles
+        };
+        assert_eq!(stalls(8 << 11), stalls(0));
+    }
+
+    #[test]
+    fn forwarding_paths() {
+        let program = [
+            0x8C08_0100, //lw t0,0x100(zero)
+            0x2509_0001, //addiu t1,t0,1
+            0x2529_0001, //addiu t1,t1,1
+            0x0128_5021, //addu t2,t1,t0
+        ];
+        let expected = [
+            (Forwarding::None, 6, (4, 0, 0)),
+            (Forwarding::ExOnly, 2, (2, 2, 0)),
+            (Forwarding::Full, 1, (1, 2, 1)),
+        ];
+        for (forwarding, stalls, (register_file, ex_mem, mem_wb)) in expected.iter() {
+            let mut b = program_bus(&program, None);
+            let mut cpu = MipsCpu::new(&mut b, 0);
+            cpu.set_forwarding(*forwarding);
+            while cpu.pipeline.retired < program.len() as u64 {
+                cpu.cycle();
+            }
+            let hazards = &cpu.pipeline.hazards;
+            assert_eq!(hazards.stall_cycles, *stalls, "{:?}", forwarding);
+            //the load reads its base register zero, which is no operand
+            assert_eq!(
+                hazards.forwarded.register_file, *register_file,
+                "{:?}",
+                forwarding
+            );
+            assert_eq!(hazards.forwarded.ex_mem, *ex_mem, "{:?}", forwarding);
+            assert_eq!(hazards.forwarded.mem_wb, *mem_wb, "{:?}", forwarding);
+        }
+    }
+
+    #[test]
+    fn strict_load_delay_slot_sees_old_value() {
+        let mut program = vec![0; 0x42];
+        program[..4].copy_from_slice(&[
+            0x2408_0001, //addiu t0,zero,1
+            0x8C08_0100, //lw t0,0x100(zero)
+            0x0100_4821, //addu t1,t0,zero
+            0x0100_5021, //addu t2,t0,zero
+        ]);
+        program[0x40] = 42;
+        for (mode, delay_slot) in [(LoadDelay::Interlocked, 42), (LoadDelay::Strict, 1)].iter() {
+            let mut b = program_bus(&program, None);
+            let mut cpu = MipsCpu::new(&mut b, 0);
+            cpu.set_load_delay(*mode);
+            while cpu.pipeline.retired < 4 {
+                cpu.cycle();
+            }
+            assert_eq!(cpu.get_register(9), *delay_slot, "{:?}", mode);
+            assert_eq!(cpu.get_register(10), 42, "{:?}", mode);
+        }
+    }
+
+    #[test]
+    fn strict_unaligned_load_pair_merges() {
+        let mut program = vec![0; 0x42];
+        program[..4].copy_from_slice(&[
+            0x8808_0101, //lwl t0,0x101(zero)
+            0x9808_0104, //lwr t0,0x104(zero)
+            0x0100_4821, //addu t1,t0,zero
+            0x0100_5021, //addu t2,t0,zero
+        ]);
+        program[0x40] = 0x1122_3344;
+        program[0x41] = 0x5566_7788;
+        let mut b = program_bus(&program, None);
+        let mut cpu = MipsCpu::new(&mut b, 0);
+        cpu.set_load_delay(LoadDelay::Strict);
+        while cpu.pipeline.retired < 4 {
+            cpu.cycle();
+        }
+        assert_eq!(cpu.get_register(9), 0x2233_4400);
+        assert_eq!(cpu.get_register(10), 0x2233_4455);
+    }
+
+    #[test]
+    fn predicted_loop_branch() {
+        let program = [
+            0x2408_0005, //addiu t0,zero,5
+            0x2508_FFFF, //addiu t0,t0,-1
+            0x1500_FFFE, //bne t0,zero,-2
+            0x0000_0000, //nop
+            0x2409_0001, //addiu t1,zero,1
+        ];
+        for (predictor, flushes) in [
+            (BranchPredictorKind::NotTaken, 4),
+            (BranchPredictorKind::BackwardTaken, 1),
+        ]
+        .iter()
+        {
+            let mut b = program_bus(&program, None);
+            let mut cpu = MipsCpu::new(&mut b, 0);
+            cpu.set_predictor(*predictor, 4);
+            while cpu.pipeline.retired < 17 {
+                cpu.cycle();
+            }
+            assert_eq!(cpu.get_register(9), 1, "{:?}", predictor);
+            assert_eq!(cpu.pipeline.flush_cycles, *flushes, "{:?}", predictor);
+            let statistics = cpu.pipeline.predictors.active_statistics();
+            assert_eq!(statistics.total, 5);
+            assert_eq!(statistics.correct, 5 - *flushes);
+        }
+    }
+
+    #[test]
+    fn return_address_stack() {
+        let program = [
+            0x0C00_0005, //jal 0x14
+            0x0000_0000, //nop
+            0x2409_0001, //addiu t1,zero,1
+            0x0000_0000, //nop
+            0x0000_0000, //nop
+            0x03E0_0008, //jr ra
+            0x2408_0001, //addiu t0,zero,1
+        ];
+        for (entries, flushes) in [(0, 1), (4, 0)].iter() {
+            let mut b = program_bus(&program, None);
+            let mut cpu = MipsCpu::new(&mut b, 0);
+            cpu.set_return_address_stack(*entries);
+            while cpu.pipeline.retired < 5 {
+                cpu.cycle();
+            }
+            assert_eq!(cpu.get_register(9), 1);
+            assert_eq!(cpu.pipeline.flush_cycles, *flushes, "{} entries", entries);
+            assert_eq!(cpu.pipeline.ras.predictions, *entries as u64 / 4);
+            assert_eq!(cpu.pipeline.ras.correct, *entries as u64 / 4);
+        }
+    }
+
+    #[test]
+    fn return_address_stack_is_repaired_after_wrong_path() {
+        let program = [
+            0x0C00_0004, //jal 0x10
+            0x0000_0000, //nop
+            0x2409_0001, //addiu t1,zero,1
+            0x0000_0000, //nop
+            0x1000_0002, //beq zero,zero,+2, predicted not taken
+            0x0000_0000, //nop
+            0x03E0_0008, //jr ra (wrong path, pops the return address)
+            0x03E0_0008, //jr ra
+            0x0000_0000, //nop
+        ];
+        let mut b = program_bus(&program, None);
+        let mut cpu = MipsCpu::new(&mut b, 0);
+        cpu.set_return_address_stack(4);
+        while cpu.pipeline.retired < 7 {
+            cpu.cycle();
+        }
+        assert_eq!(cpu.get_register(9), 1);
+        assert_eq!(cpu.pipeline.ras.predictions, 1);
+        assert_eq!(cpu.pipeline.ras.correct, 1);
+    }
+
+    #[test]
+    fn cache_misses_freeze_pipeline() {
+        let program = [
+            0x8C08_0100, //lw t0,0x100(zero)
+            0x8C09_0104, //lw t1,0x104(zero)
+            0x0000_0000, //nop
+            0x0000_0000, //nop
+        ];
+        let mut b = program_bus(&program, None);
+        let mut cpu = MipsCpu::new(&mut b, 0);
+        let config = "64,16,1".parse::<CacheConfig>().ok();
+        cpu.set_caches(config, config, 10);
+        while cpu.pipeline.retired < program.len() as u64 {
+            cpu.cycle();
+        }
+        let icache = cpu.pipeline.icache.as_ref().unwrap();
+        let dcache = cpu.pipeline.dcache.as_ref().unwrap();
+        //the fetch runs ahead into the next line
+        assert_eq!(icache.statistics.read_misses, 2);
+        assert_eq!(dcache.statistics.read_misses, 1);
+        assert_eq!(dcache.statistics.read_hits, 1);
+        assert_eq!(cpu.pipeline.memory_stall_cycles, 30);
+        assert_eq!(
+            cpu.pipeline.cycles,
+            program.len() as u64 + 4 + cpu.pipeline.stall_cycles()
+        );
+    }
+
+    #[test]
+    fn write_buffer_absorbs_stores() {
+        let program = [
+            0xAC00_0100, //sw zero,0x100(zero)
+            0xAC00_0104, //sw zero,0x104(zero)
+            0xAC00_0108, //sw zero,0x108(zero)
+            0x8C08_0104, //lw t0,0x104(zero)
+        ];
+        for (entries, stalls) in [(4, 0), (2, 3)].iter() {
+            let mut b = program_bus(&program, None);
+            let mut cpu = MipsCpu::new(&mut b, 0);
+            cpu.set_write_buffer(*entries, 5);
+            while cpu.pipeline.retired < program.len() as u64 {
+                cpu.cycle();
+            }
+            let wb = &cpu.pipeline.write_buffer;
+            assert_eq!(wb.stores, 3);
+            assert_eq!(wb.full_stall_cycles, *stalls, "{} entries", entries);
+            assert_eq!(cpu.pipeline.memory_stall_cycles, *stalls);
+            assert_eq!(wb.forwarded_loads, 1);
+        }
+    }
+
+    #[test]
+    fn exception_in_delay_slot() {
+        //the delay slot of a branch that is not taken is still a delay slot
+        for branch in [0x1000_0002, 0x1400_0002].iter() {
+            let program: [u32; 3] = [
+                *branch,     //beq zero,zero,+2 and bne zero,zero,+2
+                0x0000_000C, //syscall
+                0x2409_0001, //addiu t1,zero,1
+            ];
+            //the handler reads Cause
+            let handler = [0x4008_6800]; //mfc0 t0,$13
+            for pipelined in [false, true].iter() {
+                let mut b = program_bus(&program, Some((0x8000_0080, &handler)));
+                let mut cpu = MipsCpu::new(&mut b, 0);
+                cpu.set_cp0(true);
+                run(&mut cpu, *pipelined, |cpu| cpu.get_register(8) != 0);
+                assert_eq!(
+                    cpu.get_register(9),
+                    0,
+                    "Instruction after the slot was executed"
+                );
+                assert_eq!(cpu.get_register(8), (1 << 31) | (8 << 2));
+                assert_eq!(cpu.cp0.epc, 0);
+                assert_eq!(cpu.cp0.exceptions, 1);
+            }
+        }
+    }
+
+    #[test]
+    fn overflow_leaves_destination_unchanged() {
+        let program: [u32; 5] = [
+            0x3C08_7FFF, //lui t0,0x7fff
+            0x3508_FFFF, //ori t0,t0,0xffff
+            0x2409_0007, //addiu t1,zero,7
+            0x0109_5821, //addu t3,t0,t1
+            0x0109_4820, //add t1,t0,t1
+        ];
+        for pipelined in [false, true].iter() {
+            let mut b = program_bus(&program, None);
+            let mut cpu = MipsCpu::new(&mut b, 0);
+            cpu.set_cp0(true);
+            //addu completes before the exception is taken
+            run(&mut cpu, *pipelined, |cpu| cpu.cp0.exceptions > 0);
+            //addu wraps around
+            assert_eq!(cpu.get_register(11), 0x8000_0006);
+            assert_eq!(cpu.get_register(9), 7, "Destination of add was written");
+            assert_eq!(cpu.cp0.cause >> 2 & 0x1F, 12);
+            assert_eq!(cpu.cp0.epc, 0x10);
+        }
+    }
+
+    #[test]
+    fn hi_lo_interlock() {
+        let program = [
+            0x2408_0006, //addiu t0,zero,6
+            0x2409_0007, //addiu t1,zero,7
+            0x0109_0018, //mult t0,t1
+            0x0000_5012, //mflo t2
+        ];
+        let mut b = program_bus(&program, None);
+        let mut cpu = MipsCpu::new(&mut b, 0);
+        cpu.set_mdu_latencies(5, 20);
+        while cpu.pipeline.retired < program.len() as u64 {
+            cpu.cycle();
+        }
+        assert_eq!(cpu.get_register(10), 42);
+        assert_eq!(cpu.pipeline.mdu.stall_cycles, 4);
+        assert_eq!(cpu.pipeline.mdu.multiplications, 1);
+    }
+
+    #[test]
+    fn fpu_interlocks() {
+        let program = [
+            0xC402_0100, //lwc1 $f2,0x100(zero)
+            0xC404_0104, //lwc1 $f4,0x104(zero)
+            0x4604_1182, //mul.s $f6,$f2,$f4
+            0x4602_3200, //add.s $f8,$f6,$f2
+            0x4608_103C, //c.lt.s $f2,$f8
+            0x4501_0002, //bc1t +2
+            0x0000_0000, //nop
+            0x2408_0001, //addiu t0,zero,1 (skipped)
+            0xE408_0108, //swc1 $f8,0x108(zero)
+            0x4409_4000, //mfc1 t1,$f8
+        ];
+        let mut b = program_bus(&program, None);
+        b.write_w(0x100, 1.5f32.to_bits().to_be());
+        b.write_w(0x104, 2.0f32.to_bits().to_be());
+        let mut cpu = MipsCpu::new(&mut b, 0);
+        cpu.set_fpu(true);
+        while cpu.pipeline.retired < program.len() as u64 - 1 {
+            cpu.cycle();
+        }
+        assert_eq!(cpu.get_register(8), 0, "bc1t was not taken");
+        assert_eq!(cpu.get_register(9), 4.5f32.to_bits());
+        assert_eq!(u32::from_be(cpu.bus.read_w(0x108)), 4.5f32.to_bits());
+        //one cycle for the loaded $f4, three for the multiplication and one for the addition
+        assert_eq!(cpu.pipeline.fpu.stall_cycles, 5);
+        assert_eq!(cpu.pipeline.fpu.instructions, 8);
+    }
+
+    #[test]
+    fn timer_interrupt() {
+        let program: [u32; 10] = [
+            0x2408_2000, //addiu t0,zero,0x2000
+            0x2409_0014, //addiu t1,zero,20
+            0xAD09_0004, //sw t1,4(t0)
+            0x2409_0003, //addiu t1,zero,3
+            0xAD09_0008, //sw t1,8(t0)
+            0x240A_0401, //addiu t2,zero,0x401
+            0x408A_6000, //mtc0 t2,$12
+            0x2610_0001, //addiu s0,s0,1
+            0x1000_FFFE, //beq zero,zero,-2
+            0x0000_0000, //nop
+        ];
+        let handler: [u32; 6] = [
+            0x2631_0001, //addiu s1,s1,1
+            0x241A_0007, //addiu k0,zero,7
+            0xAD1A_0008, //sw k0,8(t0)
+            0x401B_7000, //mfc0 k1,$14
+            0x0360_0008, //jr k1
+            0x4200_0010, //rfe
+        ];
+        for pipelined in [false, true].iter() {
+            let mut objects = program_memory(&program, Some((0x8000_0080, &handler)));
+            objects.push(Box::new(timer::Timer::new(0x2000, 0, Endianness::Big)));
+            let mut b = Bus::new(0, 0, objects).unwrap();
+            let mut cpu = MipsCpu::new(&mut b, 0);
+            cpu.set_cp0(true);
+            run(&mut cpu, *pipelined, |cpu| cpu.get_register(17) == 3);
+            assert_eq!(cpu.get_register(17), 3, "Handler did not run three times");
+            assert_eq!(cpu.cp0.exceptions, 3);
+            assert!(
+                cpu.get_register(16) > 3,
+                "Interrupted loop made no progress"
+            );
+            //the handler runs with IEc cleared and the interrupt still pending in IP2
+            assert_eq!(cpu.cp0.status & 0x3F, 0b00_0100);
+            assert_eq!(cpu.cp0.cause & 0xFF7C, 0x0400);
+            assert!((0x1C..=0x24).contains(&cpu.cp0.epc));
+        }
+    }
+
+    #[test]
+    fn tlb_refill() {
+        //kseg0 program storing to and loading from an unmapped kuseg page
+        let program: [u32; 8] = [
+            0x3C08_0040, //lui t0,0x40
+            0x2409_002A, //addiu t1,zero,42
+            0xAD09_0010, //sw t1,16(t0)
+            0x8D0A_0010, //lw t2,16(t0)
+            0x0000_0000, //nop
+            0x2410_0001, //addiu s0,zero,1
+            0x1000_FFFF, //beq zero,zero,-1
+            0x0000_0000, //nop
+        ];
+        //maps the page in EntryHi to the global, writable frame 0x1000
+        let refill: [u32; 7] = [
+            0x241A_1700, //addiu k0,zero,0x1700
+            0x409A_1000, //mtc0 k0,$2
+            0x4200_0006, //tlbwr
+            0x401B_7000, //mfc0 k1,$14
+            0x0000_0000, //nop
+            0x0360_0008, //jr k1
+            0x4200_0010, //rfe
+        ];
+        for pipelined in [false, true].iter() {
+            let mut b = program_bus(&refill, Some((0x100, &program)));
+            let mut cpu = MipsCpu::new(&mut b, 0x8000_0100);
+            cpu.set_cp0(true);
+            cpu.set_mmu(true);
+            run(&mut cpu, *pipelined, |c| c.get_register(16) == 1);
+            assert_eq!(cpu.get_register(10), 42);
+            assert_eq!(u32::from_be(cpu.bus.read_w(0x1010)), 42);
+            assert_eq!((cpu.cp0.exceptions, cpu.cp0.mmu.refills), (1, 1));
+            assert_eq!(cpu.cp0.bad_vaddr, 0x0040_0010);
+            assert_eq!(cpu.cp0.cause & 0x7C, 3 << 2);
+        }
+    }
+
+    #[test]
+    fn kseg0_boundary() {
+        //a load and a branch from kseg0 to just below it, which misses in the TLB
+        let cases: [[u32; 3]; 2] = [
+            [
+                0x3C08_8000, //lui t0,0x8000
+                0x8D09_FFF0, //lw t1,-0x10(t0)
+                0x0000_0000, //nop
+            ],
+            [
+                0x1000_FFFE, //beq zero,zero,-2
+                0x0000_0000, //nop
+                0x0000_0000, //nop
+            ],
+        ];
+        for (program, bad_vaddr) in cases.iter().zip([0x7FFF_FFF0, 0x7FFF_FFFC]) {
+            for pipelined in [false, true].iter() {
+                let mut b = program_bus(program, None);
+                let mut cpu = MipsCpu::new(&mut b, 0x8000_0000);
+                cpu.set_cp0(true);
+                cpu.set_mmu(true);
+                run(&mut cpu, *pipelined, |cpu| cpu.cp0.exceptions > 0);
+                assert_eq!(cpu.cp0.cause >> 2 & 0x1F, 2);
+                assert_eq!(cpu.cp0.bad_vaddr, bad_vaddr);
+            }
+        }
+    }
+
+    #[test]
+    fn caches_use_physical_addresses() {
+        //maps page 0x00400000 onto frame 0 and runs the body through kuseg, then through kseg0
+        let program: [u32; 24] = [
+            0x3C08_0040, //lui t0,0x40
+            0x4088_5000, //mtc0 t0,$10
+            0x2409_0700, //addiu t1,zero,0x700
+            0x4089_1000, //mtc0 t1,$2
+            0x4080_0000, //mtc0 zero,$0
+            0x2508_0020, //addiu t0,t0,0x20
+            0x0100_0008, //jr t0
+            0x4200_0002, //tlbwi
+            0x2631_0001, //addiu s1,s1,1
+            0x3C0A_8000, //lui t2,0x8000
+            0x8D4B_0100, //lw t3,0x100(t2)
+            0x3C0D_0040, //lui t5,0x40
+            0x8DAC_0100, //lw t4,0x100(t5)
+            0x3C0E_A000, //lui t6,0xa000
+            0x8DCF_0100, //lw t7,0x100(t6)
+            0x2409_0002, //addiu t1,zero,2
+            0x1229_0004, //beq s1,t1,4
+            0x0000_0000, //nop
+            0x254A_0020, //addiu t2,t2,0x20
+            0x0140_0008, //jr t2
+            0x0000_0000, //nop
+            0x2410_0001, //addiu s0,zero,1
+            0x1000_FFFF, //beq zero,zero,-1
+            0x0000_0000, //nop
+        ];
+        for pipelined in [false, true].iter() {
+            let mut b = program_bus(&program, None);
+            b.write_w(0x100, 42u32.to_be());
+            let mut cpu = MipsCpu::new(&mut b, 0x8000_0000);
+            cpu.set_mmu(true);
+            let config = "64,16,1".parse::<CacheConfig>().ok();
+            cpu.set_caches(config, config, 10);
+            run(&mut cpu, *pipelined, |c| c.get_register(16) == 1);
+            assert_eq!(cpu.cp0.exceptions, 0);
+            for register in [11, 12, 15] {
+                assert_eq!(cpu.get_register(register), 42);
+            }
+            if *pipelined {
+                //both aliases of the data share a line, the kseg1 load bypasses the cache
+                let dcache = cpu.pipeline.dcache.as_ref().unwrap();
+                assert_eq!(dcache.statistics.read_misses, 1);
+                assert_eq!(dcache.statistics.read_hits, 3);
+                //the second pass through the body hits the lines of the first, the fetch runs
+                //ahead into a seventh line
+                let icache = cpu.pipeline.icache.as_ref().unwrap();
+                assert_eq!(icache.statistics.read_misses, 7);
+            }
+        }
+    }
+
+    #[test]
+    fn tlb_write_maps_next_page() {
+        //maps page 0x00400000 onto frame 0 from kseg0, the tlbwi at its end maps the next page
+        //while the first instruction there is being fetched
+        let program: [u32; 16] = [
+            0x3C08_0040, //lui t0,0x40
+            0x4088_5000, //mtc0 t0,$10
+            0x2409_0700, //addiu t1,zero,0x700
+            0x4089_1000, //mtc0 t1,$2
+            0x4080_0000, //mtc0 zero,$0
+            0x4200_0002, //tlbwi
+            0x2508_1000, //addiu t0,t0,0x1000
+            0x4088_5000, //mtc0 t0,$10
+            0x2409_1700, //addiu t1,zero,0x1700
+            0x4089_1000, //mtc0 t1,$2
+            0x240A_0100, //addiu t2,zero,0x100
+            0x408A_0000, //mtc0 t2,$0
+            0x3C0B_0040, //lui t3,0x40
+            0x256B_0FFC, //addiu t3,t3,0xffc
+            0x0160_0008, //jr t3
+            0x0000_0000, //nop
+        ];
+        let page_end = [
+            0x4200_0002, //tlbwi
+            0x2410_0001, //addiu s0,zero,1
+            0x1000_FFFF, //beq zero,zero,-1
+            0x0000_0000, //nop
+        ];
+        for pipelined in [false, true].iter() {
+            let mut b = program_bus(&program, Some((0xFFC, &page_end)));
+            let mut cpu = MipsCpu::new(&mut b, 0x8000_0000);
+            cpu.set_mmu(true);
+            run(&mut cpu, *pipelined, |c| c.get_register(16) == 1);
+            assert_eq!(cpu.cp0.exceptions, 0);
+        }
+    }
+
+    #[test]
+    fn floating_point_exceptions() {
+        let program: [u32; 8] = [
+            0x3C08_3F80, //lui t0,0x3f80
+            0x4488_0000, //mtc1 t0,$f0
+            0x2409_0400, //addiu t1,zero,0x400
+            0x44C9_F800, //ctc1 t1,$31 (enable division by zero)
+            0x4604_0083, //div.s $f2,$f0,$f4
+            0x2410_0001, //addiu s0,zero,1
+            0x1000_FFFF, //beq zero,zero,-1
+            0x0000_0000, //nop
+        ];
+        let handler = [0x401A_6800]; //mfc0 k0,$13
+        for pipelined in [false, true].iter() {
+            for isa in [IsaLevel::Mips1, IsaLevel::Mips2] {
+                let mut b = program_bus(&program, Some((0x8000_0080, &handler)));
+                let mut cpu = MipsCpu::new(&mut b, 0);
+                cpu.set_cp0(true);
+                cpu.set_fpu(true);
+                cpu.set_isa(isa);
+                //Int3 unmasked and interrupts enabled
+                let status = cp0::STATUS_CU1 | 1 << (8 + 2 + cp1::INTERRUPT_LINE) | 1;
+                cpu.cp0.write(cp0::STATUS, status);
+                run(&mut cpu, *pipelined, |cpu| cpu.cp0.exceptions > 0);
+                assert_eq!(cpu.cp1.read(2), 0, "Trapping division wrote its result");
+                if isa == IsaLevel::Mips1 {
+                    //the R3010 interrupts once div.s has completed
+                    assert_eq!(cpu.cp0.cause >> 2 & 0x1F, 0);
+                    assert_ne!(cpu.cp0.cause & 1 << 13, 0);
+                    assert!(cpu.cp0.epc > 0x10);
+                } else {
+                    assert_eq!(cpu.cp0.cause >> 2 & 0x1F, 15);
+                    assert_eq!(cpu.cp0.epc, 0x10);
+                    assert_eq!(cpu.get_register(16), 0);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn division_traps() {
+        let program: [u32; 5] = [
+            0x3C08_8000, //lui t0,0x8000
+            0x2409_FFFF, //addiu t1,zero,-1
+            0x0109_001A, //div t0,t1
+            0x1000_FFFF, //beq zero,zero,-1
+            0x0000_0000, //nop
+        ];
+        for pipelined in [false, true].iter() {
+            for (isa, code) in [(IsaLevel::Mips1, 9), (IsaLevel::Mips2, 13)] {
+                let mut b = program_bus(&program, None);
+                let mut cpu = MipsCpu::new(&mut b, 0);
+                cpu.set_cp0(true);
+                cpu.set_isa(isa);
+                cpu.set_divide_by_zero(DivideByZero::Trap);
+                run(&mut cpu, *pipelined, |cpu| cpu.cp0.exceptions > 0);
+                assert_eq!(cpu.cp0.cause >> 2 & 0x1F, code);
+                assert_eq!(cpu.cp0.epc, 8);
+                assert_eq!(cpu.lo, 0, "Overflowing division wrote LO");
+            }
+        }
+    }
+
+    #[test]
+    fn privilege_checks() {
+        //user program loading from kseg0 and writing Status without CU0
+        let program: [u32; 6] = [
+            0x3C08_8000, //lui t0,0x8000
+            0x8D09_0000, //lw t1,0(t0)
+            0x4080_6000, //mtc0 zero,$12
+            0x2410_0001, //addiu s0,zero,1
+            0x1000_FFFF, //beq zero,zero,-1
+            0x0000_0000, //nop
+        ];
+        //skips the faulting instruction and returns to user mode
+        let handler: [u32; 6] = [
+            0x2631_0001, //addiu s1,s1,1
+            0x401B_7000, //mfc0 k1,$14
+            0x0000_0000, //nop
+            0x277B_0004, //addiu k1,k1,4
+            0x0360_0008, //jr k1
+            0x4200_0010, //rfe
+        ];
+        for pipelined in [false, true].iter() {
+            let mut b = program_bus(&program, Some((0x8000_0080, &handler)));
+            let mut cpu = MipsCpu::new(&mut b, 0);
+            cpu.set_cp0(true);
+            //KUc
+            cpu.cp0.write(cp0::STATUS, 0b10);
+            run(&mut cpu, *pipelined, |c| c.get_register(16) == 1);
+            assert_eq!((cpu.get_register(17), cpu.get_register(9)), (2, 0));
+            assert_eq!(cpu.cp0.bad_vaddr, 0x8000_0000);
+            //the second fault is CpU for coprocessor 0
+            assert_eq!(cpu.cp0.cause & 0x3000_007C, 11 << 2);
+            assert!(cpu.cp0.user_mode());
+        }
+    }
+
+    #[test]
+    fn address_errors() {
+        //program, exception code, BadVAddr and EPC
+        let cases: [([u32; 3], u32, u32, u32); 3] = [
+            (
+                [
+                    0x2408_0102, //addiu t0,zero,0x102
+                    0x8D09_0000, //lw t1,0(t0)
+                    0x0000_0000, //nop
+                ],
+                4,
+                0x102,
+                4,
+            ),
+            (
+                [
+                    0x2408_0101, //addiu t0,zero,0x101
+                    0xA508_0000, //sh t0,0(t0)
+                    0x0000_0000, //nop
+                ],
+                5,
+                0x101,
+                4,
+            ),
+            (
+                [
+                    0x2408_0012, //addiu t0,zero,0x12
+                    0x0100_0008, //jr t0
+                    0x0000_0000, //nop
+                ],
+                4,
+                0x12,
+                0x12,
+            ),
+        ];
+        for (program, code, bad_vaddr, epc) in cases.iter() {
+            for pipelined in [false, true].iter() {
+                let mut b = program_bus(program, None);
+                let mut cpu = MipsCpu::new(&mut b, 0);
+                cpu.set_cp0(true);
+                run(&mut cpu, *pipelined, |cpu| cpu.cp0.exceptions > 0);
+                assert_eq!(cpu.cp0.cause >> 2 & 0x1F, *code);
+                assert_eq!(cpu.cp0.bad_vaddr, *bad_vaddr);
+                assert_eq!(cpu.cp0.epc, *epc);
+                assert_eq!(cpu.get_register(9), 0, "Misaligned load was written");
+                assert_eq!(cpu.bus.read_w(0x100), 0, "Misaligned store was written");
+                //without coprocessor 0 the simulation stops at the instruction
+                let mut b = program_bus(program, None);
+                let mut cpu = MipsCpu::new(&mut b, 0);
+                run(&mut cpu, *pipelined, |cpu| cpu.fatal.is_some());
+                let fatal = cpu.fatal.as_ref().unwrap();
+                let error = fatal.downcast_ref::<UnhandledException>().unwrap();
+                assert_eq!(error.code as u32, *code);
+                assert_eq!((error.pc, error.bad_vaddr), (*epc, Some(*bad_vaddr)));
+            }
+        }
+    }
+
+    #[test]
+    fn fetch_bus_error() {
+        //jumps to an address nothing on the bus answers
+        let program = [
+            0x3C08_0001, //lui t0,0x1
+            0x0100_0008, //jr t0
+            0x0000_0000, //nop
+        ];
+        for pipelined in [false, true].iter() {
+            let mut b = program_bus(&program, None);
+            let mut cpu = MipsCpu::new(&mut b, 0);
+            cpu.set_cp0(true);
+            run(&mut cpu, *pipelined, |cpu| cpu.cp0.exceptions > 0);
+            assert_eq!(cpu.cp0.cause >> 2 & 0x1F, 6);
+            assert_eq!(cpu.cp0.epc, 0x1_0000);
+            let mut b = program_bus(&program, None);
+            let mut cpu = MipsCpu::new(&mut b, 0);
+            run(&mut cpu, *pipelined, |cpu| cpu.fatal.is_some());
+            let fatal = cpu.fatal.as_ref().unwrap();
+            assert_eq!(
+                fatal.downcast_ref::<UnhandledException>(),
+                Some(&UnhandledException {
+                    code: ExceptionCode::InstructionBus,
+                    pc: 0x1_0000,
+                    bad_vaddr: None,
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn reserved_instruction() {
+        let program: [u32; 5] = [
+            0x0800_0004, //j 0x10
+            0x0000_0000, //nop
+            0xFFFF_FFFF, //data behind the jump (wrong path)
+            0xFFFF_FFFF,
+            0x0000_003F, //SPECIAL funct 0x3F
+        ];
+        for pipelined in [false, true].iter() {
+            let mut b = program_bus(&program, None);
+            let mut cpu = MipsCpu::new(&mut b, 0);
+            cpu.set_cp0(true);
+            run(&mut cpu, *pipelined, |cpu| cpu.cp0.exceptions > 0);
+            assert_eq!(cpu.cp0.cause >> 2 & 0x1F, 10);
+            assert_eq!(cpu.cp0.epc, 0x10);
+        }
+        //without coprocessor 0 the simulation stops with the decoder fields
+        for pipelined in [false, true].iter() {
+            let mut b = program_bus(&program, None);
+            let mut cpu = MipsCpu::new(&mut b, 0);
+            run(&mut cpu, *pipelined, |cpu| cpu.fatal.is_some());
+            let fatal = cpu.fatal.as_ref().unwrap();
+            assert_eq!(
+                fatal.downcast_ref::<DecodeError>(),
+                Some(&DecodeError::new(0x10, 0x0000_003F))
+            );
+        }
+        let error = DecodeError::new(0x10, 0x0000_003F);
+        assert_eq!(error.path, "SPECIAL funct 0x3F");
+        assert_eq!(
+            DecodeError::new(0, 0x4600_0008).path,
+            "COP1 rs 0x10 funct 0x08"
+        );
+    }
+
+    #[test]
+    fn isa_levels() {
+        let program: [u32; 19] = [
+            0x2408_0005, //addiu t0,zero,5
+            0x2409_0006, //addiu t1,zero,6
+            0x5109_0004, //beql t0,t1,4
+            0x2410_0001, //addiu s0,zero,1 (nullified)
+            0x5509_0002, //bnel t0,t1,2
+            0x2411_0001, //addiu s1,zero,1
+            0x2412_0001, //addiu s2,zero,1 (skipped)
+            0x7109_5002, //mul t2,t0,t1
+            0x710B_5820, //clz t3,t0
+            0x7109_0000, //madd t0,t1
+            0x0100_600A, //movz t4,t0,zero
+            0xC00D_0100, //ll t5,0x100(zero)
+            0x25AD_0001, //addiu t5,t5,1
+            0xE00D_0100, //sc t5,0x100(zero)
+            0x0109_0034, //teq t0,t1
+            0x0000_000F, //sync
+            0x2413_0001, //addiu s3,zero,1
+            0x1000_FFFF, //beq zero,zero,-1
+            0x0000_0000, //nop
+        ];
+        let run_isa = |isa: IsaLevel, pipelined: bool, check: &dyn Fn(&MipsCpu)| {
+            let mut b = program_bus(&program, None);
+            b.write_w(0x100, 41u32.to_be());
+            let mut cpu = MipsCpu::new(&mut b, 0);
+            cpu.set_cp0(true);
+            cpu.set_isa(isa);
+            run(&mut cpu, pipelined, |c| {
+                c.get_register(19) == 1 || c.cp0.exceptions > 0
+            });
+            check(&cpu);
+        };
+        for pipelined in [false, true].iter() {
+            run_isa(IsaLevel::Mips32R1, *pipelined, &|cpu| {
+                assert_eq!(cpu.get_register(19), 1, "Program did not finish");
+                assert_eq!(cpu.cp0.exceptions, 0);
+                let branches = [16, 17, 18].map(|r| cpu.get_register(r));
+                assert_eq!(branches, [0, 1, 0]);
+                assert_eq!((cpu.get_register(10), cpu.get_register(11)), (30, 29));
+                assert_eq!((cpu.hi, cpu.lo), (0, 30));
+                assert_eq!((cpu.get_register(12), cpu.get_register(13)), (5, 1));
+                assert_eq!(u32::from_be(cpu.bus.read_w(0x100)), 42);
+            });
+            //beql is the first instruction MIPS I does not know
+            run_isa(IsaLevel::Mips1, *pipelined, &|cpu| {
+                assert_eq!(cpu.cp0.cause >> 2 & 0x1F, 10);
+                assert_eq!(cpu.cp0.epc, 0x08);
+            });
+            //mul needs MIPS32
+            run_isa(IsaLevel::Mips2, *pipelined, &|cpu| {
+                assert_eq!(cpu.cp0.cause >> 2 & 0x1F, 10);
+                assert_eq!(cpu.cp0.epc, 0x1C);
+                assert_eq!(cpu.get_register(17), 1);
+            });
+        }
+    }
+
+    #[test]
+    fn release2_instructions() {
+        let program: [u32; 17] = [
+            0x3C08_1234, //lui t0,0x1234
+            0x3508_5678, //ori t0,t0,0x5678
+            0x7D09_3900, //ext t1,t0,4,8
+            0x240A_FFFF, //addiu t2,zero,-1
+            0x7D0A_5A04, //ins t2,t0,8,4
+            0x7C08_58A0, //wsbh t3,t0
+            0x7C08_6420, //seb t4,t0
+            0x7C0A_6E20, //seh t5,t2
+            0x0028_7202, //rotr t6,t0,8
+            0x0128_7846, //rotrv t7,t0,t1
+            0x4170_6020, //ei s0
+            0x4171_6000, //di s1
+            0x7C12_E83B, //rdhwr s2,$29
+            0x2413_0001, //addiu s3,zero,1
+            0x1000_FFFF, //beq zero,zero,-1
+            0x0000_0000, //nop
+            0x0000_0000, //nop
+        ];
+        for (isa, pipelined) in [
+            (IsaLevel::Mips32R2, false),
+            (IsaLevel::Mips32R2, true),
+            (IsaLevel::Mips32R1, false),
+        ] {
+            let mut b = program_bus(&program, None);
+            let mut cpu = MipsCpu::new(&mut b, 0);
+            cpu.set_cp0(true);
+            cpu.set_isa(isa);
+            cpu.syscalls.thread_pointer = 0x7000;
+            run(&mut cpu, pipelined, |c| {
+                c.get_register(19) == 1 || c.cp0.exceptions > 0
+            });
+            if isa == IsaLevel::Mips32R1 {
+                //ext is the first Release 2 instruction
+                assert_eq!(cpu.cp0.cause >> 2 & 0x1F, 10);
+                assert_eq!(cpu.cp0.epc, 0x08);
+                continue;
+            }
+            assert_eq!(cpu.get_register(19), 1, "Program did not finish");
+            let registers = (9..=15).map(|r| cpu.get_register(r)).collect::<Vec<_>>();
+            assert_eq!(
+                registers,
+                [
+                    0x67,
+                    0xFFFF_F8FF,
+                    0x3412_7856,
+                    0x78,
+                    0xFFFF_F8FF,
+                    0x7812_3456,
+                    0xF024_68AC
+                ]
+            );
+            let status = [16, 17, 18].map(|r| cpu.get_register(r));
+            assert_eq!(status, [0, 1, 0x7000]);
+            assert_eq!(cpu.cp0.status & 1, 0);
+        }
+    }
+
+    #[test]
+    fn little_endian_memory() {
+        let program: [u32; 15] = [
+            0x8C08_0100, //lw t0,0x100(zero)
+            0x8409_0102, //lh t1,0x102(zero)
+            0x900A_0101, //lbu t2,0x101(zero)
+            0x980B_0101, //lwr t3,0x101(zero)
+            0x880B_0104, //lwl t3,0x104(zero)
+            0x3C0C_AABB, //lui t4,0xAABB
+            0x358C_CCDD, //ori t4,t4,0xCCDD
+            0xA80C_0109, //swl t4,0x109(zero)
+            0xB80C_010E, //swr t4,0x10E(zero)
+            0xA40C_0110, //sh t4,0x110(zero)
+            0xAC0C_0114, //sw t4,0x114(zero)
+            0x2413_0001, //addiu s3,zero,1
+            0x1000_FFFF, //beq zero,zero,-1
+            0x0000_0000, //nop
+            0x0000_0000, //nop
+        ];
+        for pipelined in [false, true] {
+            let mut b = program_bus(&program.map(u32::swap_bytes), None);
+            for (index, byte) in [0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88]
+                .iter()
+                .enumerate()
+            {
+                b.write_byte(0x100 + index as u32, *byte);
+            }
+            let mut cpu = MipsCpu::new(&mut b, 0);
+            cpu.set_endianness(Endianness::Little);
+            run(&mut cpu, pipelined, |c| c.get_register(19) == 1);
+            let registers = (8..=11).map(|r| cpu.get_register(r)).collect::<Vec<_>>();
+            assert_eq!(registers, [0x4433_2211, 0x4433, 0x22, 0x5544_3322]);
+            let stored = (0x108..0x118)
+                .map(|addr| cpu.bus.read_byte(addr))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                stored,
+                [0xBB, 0xAA, 0, 0, 0, 0, 0xDD, 0xCC, 0xDD, 0xCC, 0, 0, 0xDD, 0xCC, 0xBB, 0xAA]
+            );
+        }
+    }
+
+    //Device register that only takes byte writes, like a transmit FIFO
+    struct WriteOnly(Rc<RefCell<[u8; 8]>>);
+
+    impl BusObject for WriteOnly {
+        fn mapping(&self) -> MemoryMapping {
+            MemoryMapping {
+                start: 0x3000,
+                size: 8,
+            }
+        }
+        fn read_byte(&self, _addr: u32) -> u8 {
+            panic!("Device read");
+        }
+        fn read_hw(&self, _addr: u32) -> u16 {
+            panic!("Device read");
+        }
+        fn read_w(&self, _addr: u32) -> u32 {
+            panic!("Device read");
+        }
+        fn write_byte(&mut self, addr: u32, val: u8) {
+            self.0.borrow_mut()[addr as usize] = val;
+        }
+        fn write_hw(&mut self, _addr: u32, _val: u16) {
+            panic!("Halfword write");
+        }
+        fn write_w(&mut self, _addr: u32, _val: u32) {
+            panic!("Word write");
+        }
+    }
+
+    #[test]
+    fn partial_stores_write_only_their_bytes() {
+        let program: [u32; 7] = [
+            0x3C0C_AABB, //lui t4,0xAABB
+            0x358C_CCDD, //ori t4,t4,0xCCDD
+            0xA80C_3001, //swl t4,0x3001(zero)
+            0xB80C_3006, //swr t4,0x3006(zero)
+            0x2413_0001, //addiu s3,zero,1
+            0x1000_FFFF, //beq zero,zero,-1
+            0x0000_0000, //nop
+        ];
+        for pipelined in [false, true] {
+            for (endianness, expected) in [
+                (Endianness::Big, [0, 0xAA, 0xBB, 0xCC, 0xBB, 0xCC, 0xDD, 0]),
+                (Endianness::Little, [0xBB, 0xAA, 0, 0, 0, 0, 0xDD, 0xCC]),
+            ] {
+                let bytes = Rc::new(RefCell::new([0; 8]));
+                let program = match endianness {
+                    Endianness::Big => program,
+                    Endianness::Little => program.map(u32::swap_bytes),
+                };
+                let mut objects = program_memory(&program, None);
+                objects.push(Box::new(WriteOnly(bytes.clone())));
+                let mut b = Bus::new(0, 0, objects).unwrap();
+                let mut cpu = MipsCpu::new(&mut b, 0);
+                cpu.set_endianness(endianness);
+                run(&mut cpu, pipelined, |c| c.get_register(19) == 1);
+                assert_eq!(*bytes.borrow(), expected);
+            }
+        }
+    }
+}
@@ -0,0 +1,896 @@
+//Services behind the syscall instruction, so programs written for other simulators run unmodified.
+//Without emulation syscall raises a System Call exception like on the real processor.
+use super::cp1::Float;
+use super::mmu;
+use super::MipsCpu;
+use crate::bus_objects::{BusObject, Endianness};
+use std::error::Error;
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const V0: u8 = 2;
+//Floating point results go to $f0, arguments come in $f12
+const F0: u8 = 0;
+const F12: u8 = 12;
+const A0: u8 = 4;
+const A1: u8 = 5;
+const A2: u8 = 6;
+const A3: u8 = 7;
+
+//First descriptor handed out for opened files, 0 to 2 are the standard streams
+const FIRST_FILE: u32 = 3;
+//Largest host buffer a read service allocates at once
+const READ_CHUNK: u32 = 0x1_0000;
+
+//o32 Linux system call numbers
+const LINUX_EXIT: u32 = 4001;
+const LINUX_READ: u32 = 4003;
+const LINUX_WRITE: u32 = 4004;
+const LINUX_OPEN: u32 = 4005;
+const LINUX_CLOSE: u32 = 4006;
+const LINUX_GETPID: u32 = 4020;
+const LINUX_BRK: u32 = 4045;
+const LINUX_IOCTL: u32 = 4054;
+const LINUX_MMAP: u32 = 4090;
+const LINUX_MUNMAP: u32 = 4091;
+const LINUX_FSTAT: u32 = 4108;
+const LINUX_UNAME: u32 = 4122;
+const LINUX_WRITEV: u32 = 4146;
+const LINUX_RT_SIGACTION: u32 = 4194;
+const LINUX_RT_SIGPROCMASK: u32 = 4195;
+const LINUX_MMAP2: u32 = 4210;
+const LINUX_FSTAT64: u32 = 4215;
+const LINUX_GETTID: u32 = 4222;
+const LINUX_EXIT_GROUP: u32 = 4246;
+const LINUX_SET_TID_ADDRESS: u32 = 4252;
+const LINUX_CLOCK_GETTIME: u32 = 4263;
+const LINUX_SET_THREAD_AREA: u32 = 4283;
+
+//MIPS errno values
+const ENOENT: u32 = 2;
+const EBADF: u32 = 9;
+const ENOMEM: u32 = 12;
+const EFAULT: u32 = 14;
+const ENODEV: u32 = 19;
+const EINVAL: u32 = 22;
+const ENOTTY: u32 = 25;
+const ENOSYS: u32 = 89;
+
+const PAGE_SIZE: u32 = 4096;
+const MAP_ANONYMOUS: u32 = 0x800;
+//Character device for the standard streams, regular file otherwise
+const S_IFCHR: u32 = 0o020_620;
+const S_IFREG: u32 = 0o100_644;
+
+//Requests the emulation cannot serve, they end the simulation like decode errors
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SyscallError {
+    UnknownService { pc: u32, service: u32 },
+    //A buffer argument the program cannot access
+    BadAddress { pc: u32, service: u32, addr: u32 },
+    //sbrk would move the break out of the memory reserved for the heap
+    Heap { pc: u32, increment: i32 },
+}
+
+impl fmt::Display for SyscallError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SyscallError::UnknownService { pc, service } => write!(
+                f,
+                "Unknown syscall service {} at {:#010X?}",
+                *service as i32, pc
+            ),
+            SyscallError::BadAddress { pc, service, addr } => write!(
+                f,
+                "Syscall service {} at {:#010X?} accesses the unmapped buffer at {:#010X?}",
+                *service as i32, pc, addr
+            ),
+            SyscallError::Heap { pc, increment } if *increment < 0 => write!(
+                f,
+                "sbrk of {} bytes at {:#010X?} moves the break below the start of the heap",
+                increment, pc
+            ),
+            SyscallError::Heap { pc, increment } => write!(
+                f,
+                "sbrk of {} bytes at {:#010X?} exceeds the heap, increase it with --heapsize",
+                increment, pc
+            ),
+        }
+    }
+}
+
+impl Error for SyscallError {}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum SyscallEmulation {
+    #[default]
+    None,
+    //Service number in $v0, arguments in $a0-$a2 as in SPIM and MARS
+    Spim,
+    //User mode o32 ABI of Linux, enough for statically linked C programs
+    Linux,
+}
+
+impl FromStr for SyscallEmulation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(SyscallEmulation::None),
+            "spim" => Ok(SyscallEmulation::Spim),
+            "linux" => Ok(SyscallEmulation::Linux),
+            _ => Err(format!("Unknown syscall emulation {}", s)),
+        }
+    }
+}
+
+pub struct SyscallServices {
+    pub emulation: SyscallEmulation,
+    //Set by the exit services, the simulation stops afterwards
+    pub exit_code: Option<i32>,
+    //Program break moved by sbrk and brk and the end of the memory reserved for the heap
+    heap_start: u32,
+    heap_break: u32,
+    heap_end: u32,
+    //Anonymous mappings are handed out from the end of the heap downwards
+    mmap_bottom: u32,
+    //Set by set_thread_area
+    pub thread_pointer: u32,
+    files: Vec<Option<File>>,
+    input: Box<dyn BufRead>,
+    output: Box<dyn Write>,
+}
+
+impl Default for SyscallServices {
+    fn default() -> Self {
+        SyscallServices {
+            emulation: SyscallEmulation::None,
+            exit_code: None,
+            heap_start: 0,
+            heap_break: 0,
+            heap_end: 0,
+            mmap_bottom: 0,
+            thread_pointer: 0,
+            files: vec![],
+            input: Box::new(BufReader::new(io::stdin())),
+            output: Box::new(io::stdout()),
+        }
+    }
+}
+
+impl SyscallServices {
+    //Replaces the host standard streams
+    #[cfg(test)]
+    pub fn redirect(&mut self, input: Box<dyn BufRead>, output: Box<dyn Write>) {
+        self.input = input;
+        self.output = output;
+    }
+
+    //The bytes of a line need not be UTF-8, a failed read is the end of the input
+    fn read_line(&mut self) -> Vec<u8> {
+        self.output.flush().unwrap();
+        let mut line = vec![];
+        if self.input.read_until(b'\n', &mut line).is_err() {
+            line.clear();
+        }
+        line
+    }
+
+    fn read_byte(&mut self) -> Option<u8> {
+        self.output.flush().unwrap();
+        let mut byte = [0];
+        match self.input.read(&mut byte) {
+            Ok(1) => Some(byte[0]),
+            _ => None,
+        }
+    }
+
+    //Returns the descriptor of the opened file
+    fn open(&mut self, name: &str, options: &OpenOptions) -> Option<u32> {
+        let f = options.open(name).ok()?;
+        let fd = match self.files.iter().position(|x| x.is_none()) {
+            Some(free) => free,
+            None => {
+                self.files.push(None);
+                self.files.len() - 1
+            }
+        };
+        self.files[fd] = Some(f);
+        Some(fd as u32 + FIRST_FILE)
+    }
+
+    fn file(&mut self, fd: u32) -> Option<&mut File> {
+        let index = fd.checked_sub(FIRST_FILE)?;
+        self.files.get_mut(index as usize)?.as_mut()
+    }
+
+    //Returns the number of bytes read
+    fn read(&mut self, fd: u32, buffer: &mut [u8]) -> Option<u32> {
+        self.output.flush().unwrap();
+        let result = match fd {
+            0 => self.input.read(buffer),
+            _ => self.file(fd)?.read(buffer),
+        };
+        result.ok().map(|n| n as u32)
+    }
+
+    //Returns the number of bytes written
+    fn write(&mut self, fd: u32, buffer: &[u8]) -> Option<u32> {
+        let result = match fd {
+            1 => self.output.write_all(buffer),
+            2 => io::stderr().write_all(buffer),
+            _ => self.file(fd)?.write_all(buffer),
+        };
+        result.ok().map(|_| buffer.len() as u32)
+    }
+
+    //Returns whether fd was open
+    fn close(&mut self, fd: u32) -> bool {
+        let index = match fd.checked_sub(FIRST_FILE) {
+            Some(index) => index as usize,
+            None => return false,
+        };
+        matches!(self.files.get_mut(index).map(|f| f.take()), Some(Some(_)))
+    }
+
+    fn print(&mut self, s: &str) {
+        self.output.write_all(s.as_bytes()).unwrap();
+    }
+}
+
+impl<'a> MipsCpu<'a> {
+    //heap_start is the first byte behind the program, heap_size the bytes sbrk can hand out
+    pub fn set_syscalls(&mut self, emulation: SyscallEmulation, heap_start: u32, heap_size: u32) {
+        self.syscalls.emulation = emulation;
+        self.syscalls.heap_start = heap_start;
+        self.syscalls.heap_break = heap_start;
+        self.syscalls.heap_end = heap_start + heap_size;
+        self.syscalls.mmap_bottom = heap_start + heap_size;
+    }
+
+    //Bus address of a byte of a buffer passed to a service, EFAULT if the program could not
+    //access it itself
+    fn buffer_address(&self, vaddr: u32, access: mmu::Access) -> Result<u32, u32> {
+        match self.physical_address(vaddr, access) {
+            Ok(addr) if self.bus.is_mapped(addr) => Ok(addr),
+            _ => Err(EFAULT),
+        }
+    }
+
+    fn read_string(&self, mut addr: u32) -> Result<String, u32> {
+        let mut s = String::new();
+        loop {
+            match self
+                .bus
+                .read_byte(self.buffer_address(addr, mmu::Access::Load)?)
+            {
+                0 => return Ok(s),
+                c => s.push(c as char),
+            }
+            addr = addr.checked_add(1).ok_or(EFAULT)?;
+        }
+    }
+
+    fn read_memory(&self, addr: u32, length: u32) -> Result<Vec<u8>, u32> {
+        (0..length)
+            .map(|i| {
+                let vaddr = addr.checked_add(i).ok_or(EFAULT)?;
+                Ok(self
+                    .bus
+                    .read_byte(self.buffer_address(vaddr, mmu::Access::Load)?))
+            })
+            .collect()
+    }
+
+    fn write_memory(&mut self, addr: u32, bytes: &[u8]) -> Result<(), u32> {
+        for (i, b) in bytes.iter().enumerate() {
+            let vaddr = addr.checked_add(i as u32).ok_or(EFAULT)?;
+            let physical = self.buffer_address(vaddr, mmu::Access::Store)?;
+            self.bus.write_byte(physical, *b);
+        }
+        Ok(())
+    }
+
+    //Reads up to length bytes from fd to addr and returns how many were read, None if fd is not
+    //open. The program chooses the length, so the file is read in chunks of bounded size until
+    //a read comes up short.
+    fn read_file(&mut self, fd: u32, addr: u32, length: u32) -> Result<Option<u32>, u32> {
+        let mut chunk = vec![0; length.min(READ_CHUNK) as usize];
+        let mut total = 0;
+        while total < length {
+            let size = (length - total).min(READ_CHUNK) as usize;
+            let n = match self.syscalls.read(fd, &mut chunk[..size]) {
+                Some(n) => n,
+                None if total == 0 => return Ok(None),
+                None => break,
+            };
+            let vaddr = addr.checked_add(total).ok_or(EFAULT)?;
+            self.write_memory(vaddr, &chunk[..n as usize])?;
+            total += n;
+            if (n as usize) < size {
+                break;
+            }
+        }
+        Ok(Some(total))
+    }
+
+    //Words of the structures passed to a service, in the byte order of the program
+    fn load_word(&self, addr: u32) -> Result<u32, u32> {
+        if !addr.is_multiple_of(4) {
+            return Err(EFAULT);
+        }
+        Ok(self.read_word(self.buffer_address(addr, mmu::Access::Load)?))
+    }
+
+    fn store_word(&mut self, addr: u32, value: u32) -> Result<(), u32> {
+        if !addr.is_multiple_of(4) {
+            return Err(EFAULT);
+        }
+        let physical = self.buffer_address(addr, mmu::Access::Store)?;
+        self.write_word(physical, value);
+        Ok(())
+    }
+
+    //Returns the old program break, the break stays between the start of the heap and the mappings
+    fn sbrk(&mut self, increment: i32) -> Result<u32, SyscallError> {
+        let old = self.syscalls.heap_break;
+        //keep the break word aligned
+        let new = (old as i64 + increment as i64 + 3) & !3;
+        if new < self.syscalls.heap_start as i64 || new > self.syscalls.mmap_bottom as i64 {
+            return Err(SyscallError::Heap {
+                pc: self.pc,
+                increment,
+            });
+        }
+        self.syscalls.heap_break = new as u32;
+        Ok(old)
+    }
+
+    pub(super) fn emulate_syscall(&mut self) {
+        self.serialize();
+        match self.syscalls.emulation {
+            SyscallEmulation::None => unreachable!(),
+            SyscallEmulation::Spim => self.spim_syscall(),
+            SyscallEmulation::Linux => self.linux_syscall(),
+        }
+    }
+
+    fn spim_syscall(&mut self) {
+        if let Err(error) = self.spim_service() {
+            self.fatal = Some(Box::new(error));
+        }
+    }
+
+    fn spim_service(&mut self) -> Result<(), SyscallError> {
+        let a0 = self.get_register(A0);
+        let a1 = self.get_register(A1);
+        let a2 = self.get_register(A2);
+        let (pc, service) = (self.pc, self.get_register(V0));
+        let bad_address = |addr| move |_| SyscallError::BadAddress { pc, service, addr };
+        match service {
+            //print_int
+            1 => self.syscalls.print(&(a0 as i32).to_string()),
+            //print_float
+            2 => self.syscalls.print(&f32::read(&self.cp1, F12).to_string()),
+            //print_double
+            3 => self.syscalls.print(&f64::read(&self.cp1, F12).to_string()),
+            //print_string
+            4 => {
+                let s = self.read_string(a0).map_err(bad_address(a0))?;
+                self.syscalls.print(&s);
+            }
+            //read_int, 0 if the line is not a number
+            5 => {
+                let line = self.syscalls.read_line();
+                let value = String::from_utf8_lossy(&line)
+                    .trim()
+                    .parse::<i32>()
+                    .unwrap_or(0);
+                self.set_register(V0, value as u32);
+            }
+            //read_float and read_double, 0 if the line is not a number
+            6 => {
+                let line = self.syscalls.read_line();
+                let value = String::from_utf8_lossy(&line)
+                    .trim()
+                    .parse::<f32>()
+                    .unwrap_or(0.0);
+                f32::write(&mut self.cp1, F0, value);
+            }
+            7 => {
+                let line = self.syscalls.read_line();
+                let value = String::from_utf8_lossy(&line)
+                    .trim()
+                    .parse::<f64>()
+                    .unwrap_or(0.0);
+                f64::write(&mut self.cp1, F0, value);
+            }
+            //read_string, at most a1 - 1 characters including the newline, terminated by 0
+            8 => {
+                if a1 > 0 {
+                    let mut line = self.syscalls.read_line();
+                    line.truncate(a1 as usize - 1);
+                    line.push(0);
+                    self.write_memory(a0, &line).map_err(bad_address(a0))?;
+                }
+            }
+            //sbrk
+            9 => {
+                let old = self.sbrk(a0 as i32)?;
+                self.set_register(V0, old);
+            }
+            //exit
+            10 => self.syscalls.exit_code = Some(0),
+            //print_char
+            11 => self.syscalls.print(&(a0 as u8 as char).to_string()),
+            //read_char
+            12 => {
+                let c = self.syscalls.read_byte().unwrap_or(0);
+                self.set_register(V0, c as u32);
+            }
+            //open, flags 0 reads, 1 writes a new file and 9 appends as in MARS, -1 on failure
+            13 => {
+                let name = self.read_string(a0).map_err(bad_address(a0))?;
+                let mut options = OpenOptions::new();
+                match a1 {
+                    0 => options.read(true),
+                    1 => options.write(true).create(true).truncate(true),
+                    9 => options.append(true).create(true),
+                    _ => &mut options,
+                };
+                let fd = self.syscalls.open(&name, &options);
+                self.set_register(V0, fd.unwrap_or(u32::MAX));
+            }
+            //read
+            14 => {
+                let n = self.read_file(a0, a1, a2).map_err(bad_address(a1))?;
+                self.set_register(V0, n.unwrap_or(u32::MAX));
+            }
+            //write
+            15 => {
+                let buffer = self.read_memory(a1, a2).map_err(bad_address(a1))?;
+                let n = self.syscalls.write(a0, &buffer);
+                self.set_register(V0, n.unwrap_or(u32::MAX));
+            }
+            //close
+            16 => {
+                self.syscalls.close(a0);
+            }
+            //exit2
+            17 => self.syscalls.exit_code = Some(a0 as i32),
+            //MARS: system time in milliseconds, low word in a0 and high word in a1
+            30 => {
+                let ms = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_millis() as u64;
+                self.set_register(A0, ms as u32);
+                self.set_register(A1, (ms >> 32) as u32);
+            }
+            //MARS: sleep a0 milliseconds
+            32 => {
+                self.syscalls.output.flush().unwrap();
+                std::thread::sleep(Duration::from_millis(a0 as u64));
+            }
+            //MARS: print_hex, print_binary and print_unsigned
+            34 => self.syscalls.print(&format!("{:#010x}", a0)),
+            35 => self.syscalls.print(&format!("{:032b}", a0)),
+            36 => self.syscalls.print(&a0.to_string()),
+            service => return Err(SyscallError::UnknownService { pc, service }),
+        }
+        Ok(())
+    }
+
+    //Fills the struct stat or struct stat64 at addr
+    fn write_stat(&mut self, fd: u32, addr: u32, stat64: bool) -> Result<u32, u32> {
+        let (mode, size) = match fd {
+            0..=2 => (S_IFCHR, 0),
+            _ => match self.syscalls.file(fd).map(|f| f.metadata()) {
+                Some(Ok(m)) => (S_IFREG, m.len()),
+                _ => return Err(EBADF),
+            },
+        };
+        //offsets of st_mode, st_size and st_blksize
+        let (length, offsets) = if stat64 {
+            (104, (24, 56, 88))
+        } else {
+            (144, (20, 48, 80))
+        };
+        self.write_memory(addr, &vec![0; length])?;
+        self.store_word(addr + offsets.0, mode)?;
+        if stat64 {
+            //the 64 bit st_size keeps its words in the byte order of the program
+            let words = match self.endianness {
+                Endianness::Big => [(size >> 32) as u32, size as u32],
+                Endianness::Little => [size as u32, (size >> 32) as u32],
+            };
+            self.store_word(addr + offsets.1, words[0])?;
+            self.store_word(addr + offsets.1 + 4, words[1])?;
+        } else {
+            self.store_word(addr + offsets.1, size as u32)?;
+        }
+        self.store_word(addr + offsets.2, PAGE_SIZE)?;
+        Ok(0)
+    }
+
+    //Anonymous mappings only, they are never given back
+    fn mmap(&mut self, length: u32, flags: u32) -> Result<u32, u32> {
+        if flags & MAP_ANONYMOUS == 0 {
+            return Err(ENODEV);
+        }
+        let length = length.checked_add(PAGE_SIZE - 1).ok_or(ENOMEM)? & !(PAGE_SIZE - 1);
+        let start = self.syscalls.mmap_bottom.wrapping_sub(length);
+        if length > self.syscalls.mmap_bottom - self.syscalls.heap_break {
+            return Err(ENOMEM);
+        }
+        //brk may have used the memory before, the program may not have mapped it
+        self.write_memory(start, &vec![0; length as usize])
+            .map_err(|_| ENOMEM)?;
+        self.syscalls.mmap_bottom = start;
+        Ok(start)
+    }
+
+    //Number in $v0, arguments in $a0-$a3 and on the stack, the result in $v0 and $a3 set on error
+    fn linux_syscall(&mut self) {
+        let (value, error) = match self.linux_service() {
+            Ok(value) => (value, 0),
+            Err(errno) => (errno, 1),
+        };
+        self.set_register(V0, value);
+        self.set_register(A3, error);
+    }
+
+    //Result or errno of the service
+    fn linux_service(&mut self) -> Result<u32, u32> {
+        let a0 = self.get_register(A0);
+        let a1 = self.get_register(A1);
+        let a2 = self.get_register(A2);
+        let a3 = self.get_register(A3);
+        match self.get_register(V0) {
+            LINUX_EXIT | LINUX_EXIT_GROUP => {
+                self.syscalls.exit_code = Some(a0 as i32);
+                Ok(0)
+            }
+            LINUX_READ => self.read_file(a0, a1, a2)?.ok_or(EBADF),
+            LINUX_WRITE => {
+                let buffer = self.read_memory(a1, a2)?;
+                self.syscalls.write(a0, &buffer).ok_or(EBADF)
+            }
+            //the iovec array must not wrap around the address space
+            LINUX_WRITEV => match a2.checked_mul(8).and_then(|size| a1.checked_add(size)) {
+                Some(end) => {
+                    let mut buffer = vec![];
+                    for iov in (a1..end).step_by(8) {
+                        let (base, length) = (self.load_word(iov)?, self.load_word(iov + 4)?);
+                        buffer.extend(self.read_memory(base, length)?);
+                    }
+                    self.syscalls.write(a0, &buffer).ok_or(EBADF)
+                }
+                None => Err(EINVAL),
+            },
+            LINUX_OPEN => {
+                let name = self.read_string(a0)?;
+                let mut options = OpenOptions::new();
+                options
+                    .read(a1 & 3 != 1)
+                    .write(a1 & 3 != 0)
+                    .append(a1 & 0x8 != 0)
+                    .create(a1 & 0x100 != 0)
+                    .truncate(a1 & 0x200 != 0);
+                self.syscalls.open(&name, &options).ok_or(ENOENT)
+            }
+            LINUX_CLOSE => match a0 < FIRST_FILE || self.syscalls.close(a0) {
+                true => Ok(0),
+                false => Err(EBADF),
+            },
+            LINUX_GETPID | LINUX_GETTID | LINUX_SET_TID_ADDRESS => Ok(1),
+            LINUX_BRK => {
+                if a0 >= self.syscalls.heap_start && a0 <= self.syscalls.mmap_bottom {
+                    self.syscalls.heap_break = a0;
+                }
+                Ok(self.syscalls.heap_break)
+            }
+            //there is no terminal, so output is fully buffered
+            LINUX_IOCTL => Err(ENOTTY),
+            LINUX_MMAP | LINUX_MMAP2 => {
+                //flags are the fourth argument, fd and offset on the stack don't matter
+                self.mmap(a1, a3)
+            }
+            LINUX_MUNMAP => Ok(0),
+            LINUX_FSTAT => self.write_stat(a0, a1, false),
+            LINUX_FSTAT64 => self.write_stat(a0, a1, true),
+            LINUX_UNAME => {
+                let fields = ["Linux", "mips", "4.19.0", "#1", "mips", ""];
+                for (n, field) in fields.iter().enumerate() {
+                    let mut bytes = field.as_bytes().to_vec();
+                    bytes.resize(65, 0);
+                    let field = a0.checked_add(n as u32 * 65).ok_or(EFAULT)?;
+                    self.write_memory(field, &bytes)?;
+                }
+                Ok(0)
+            }
+            LINUX_RT_SIGACTION | LINUX_RT_SIGPROCMASK => Ok(0),
+            LINUX_CLOCK_GETTIME => {
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+                self.store_word(a1, now.as_secs() as u32)?;
+                self.store_word(a1.checked_add(4).ok_or(EFAULT)?, now.subsec_nanos())?;
+                Ok(0)
+            }
+            LINUX_SET_THREAD_AREA => {
+                self.syscalls.thread_pointer = a0;
+                Ok(0)
+            }
+            number => {
+                eprintln!(
+                    "Unimplemented Linux syscall {} at {:#010X?}",
+                    number, self.pc
+                );
+                Err(ENOSYS)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::*;
+    use super::*;
+    use crate::bus_objects::*;
+    use std::cell::RefCell;
+    use std::io::Cursor;
+    use std::rc::Rc;
+
+    //Output the test can still read after handing it to the cpu
+    #[derive(Clone, Default)]
+    struct SharedOutput(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedOutput {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    //Memory at 0 holding the program in the byte order of the cpu and data at 0x100
+    fn program_bus(program: &[u32], data: &[u8]) -> Bus {
+        let mut mem = memory::Memory::new(
+            vec![0; 0x1000].into_boxed_slice(),
+            MemoryMapping {
+                start: 0,
+                size: 0x1000,
+            },
+        );
+        let endianness = Endianness::default();
+        for (index, word) in program.iter().enumerate() {
+            mem.write_w(index as u32 * 4, endianness.word(*word));
+        }
+        for (index, byte) in data.iter().enumerate() {
+            mem.write_byte(0x100 + index as u32, *byte);
+        }
+        Bus::new(0, 0, vec![Box::new(mem)]).unwrap()
+    }
+
+    #[test]
+    fn spim_services() {
+        let program: [u32; 12] = [
+            0x2402_0005, //li v0,5 (read_int)
+            0x0000_000C, //syscall
+            0x0040_2021, //move a0,v0
+            0x2484_0001, //addiu a0,a0,1
+            0x2402_0001, //li v0,1 (print_int)
+            0x0000_000C, //syscall
+            0x2404_0100, //li a0,0x100
+            0x2402_0004, //li v0,4 (print_string)
+            0x0000_000C, //syscall
+            0x2404_0007, //li a0,7
+            0x2402_0011, //li v0,17 (exit2)
+            0x0000_000C, //syscall
+        ];
+        let mut b = program_bus(&program, b" done\n\0");
+        let mut cpu = MipsCpu::new(&mut b, 0);
+        let output = SharedOutput::default();
+        cpu.set_syscalls(SyscallEmulation::Spim, 0x800, 0x800);
+        cpu.syscalls
+            .redirect(Box::new(Cursor::new("-42\n")), Box::new(output.clone()));
+        while cpu.syscalls.exit_code.is_none() {
+            cpu.step();
+        }
+        assert_eq!(String::from_utf8_lossy(&output.0.borrow()), "-41 done\n");
+        assert_eq!(cpu.syscalls.exit_code, Some(7));
+        assert_eq!(cpu.sbrk(6), Ok(0x800));
+        assert_eq!(cpu.sbrk(0), Ok(0x808));
+        //the break neither drops below the heap nor wraps around
+        let below = SyscallError::Heap {
+            pc: cpu.pc,
+            increment: -0x10,
+        };
+        assert_eq!(cpu.sbrk(-0x10), Err(below));
+        assert!(cpu.sbrk(i32::MIN).is_err());
+        assert_eq!(cpu.sbrk(-8), Ok(0x808));
+        assert_eq!(cpu.sbrk(0), Ok(0x800));
+    }
+
+    #[test]
+    fn spim_input() {
+        let program: [u32; 13] = [
+            0x2404_0300, //li a0,0x300
+            0x2405_0010, //li a1,16
+            0x2402_0008, //li v0,8 (read_string)
+            0x0000_000C, //syscall
+            0x2404_0000, //li a0,0
+            0x2405_0200, //li a1,0x200
+            0x2406_FFFF, //li a2,-1
+            0x2402_000E, //li v0,14 (read)
+            0x0000_000C, //syscall
+            0x0040_8021, //move s0,v0
+            0x2404_0000, //li a0,0
+            0x2402_0011, //li v0,17 (exit2)
+            0x0000_000C, //syscall
+        ];
+        let mut b = program_bus(&program, b"");
+        let mut cpu = MipsCpu::new(&mut b, 0);
+        cpu.set_syscalls(SyscallEmulation::Spim, 0x800, 0x800);
+        //a line that is not UTF-8, then a read far larger than the input
+        let input = Cursor::new(b"\xFF\xFE\nabc".to_vec());
+        cpu.syscalls
+            .redirect(Box::new(input), Box::new(SharedOutput::default()));
+        while cpu.syscalls.exit_code.is_none() {
+            cpu.step();
+        }
+        assert!(cpu.fatal.is_none());
+        assert_eq!(cpu.read_memory(0x300, 4), Ok(vec![0xFF, 0xFE, b'\n', 0]));
+        assert_eq!(cpu.get_register(16), 3);
+        assert_eq!(cpu.read_memory(0x200, 4), Ok(b"abc\0".to_vec()));
+    }
+
+    #[test]
+    fn unknown_spim_service() {
+        let program: [u32; 2] = [
+            0x2402_0063, //li v0,99
+            0x0000_000C, //syscall
+        ];
+        let mut b = program_bus(&program, b"");
+        let mut cpu = MipsCpu::new(&mut b, 0);
+        cpu.set_syscalls(SyscallEmulation::Spim, 0x800, 0x800);
+        while cpu.fatal.is_none() {
+            cpu.step();
+        }
+        let fatal = cpu.fatal.as_ref().unwrap();
+        assert_eq!(
+            fatal.downcast_ref::<SyscallError>(),
+            Some(&SyscallError::UnknownService { pc: 4, service: 99 })
+        );
+    }
+
+    #[test]
+    fn linux_error_convention() {
+        let program: [u32; 12] = [
+            0x2404_0007, //li a0,7
+            0x2402_0FA4, //li v0,4004 (write)
+            0x0000_000C, //syscall
+            0x0040_8021, //move s0,v0
+            0x0007_8821, //move s1,a3
+            0x2404_0000, //li a0,0
+            0x2402_0FCD, //li v0,4045 (brk)
+            0x0000_000C, //syscall
+            0x0040_9021, //move s2,v0
+            0x2404_0005, //li a0,5
+            0x2402_1096, //li v0,4246 (exit_group)
+            0x0000_000C, //syscall
+        ];
+        let mut b = program_bus(&program, b"");
+        let mut cpu = MipsCpu::new(&mut b, 0);
+        cpu.set_syscalls(SyscallEmulation::Linux, 0x800, 0x800);
+        while cpu.syscalls.exit_code.is_none() {
+            cpu.step();
+        }
+        //EBADF with the error flag in a3
+        assert_eq!(cpu.get_register(16), 9);
+        assert_eq!(cpu.get_register(17), 1);
+        assert_eq!(cpu.get_register(18), 0x800);
+        assert_eq!(cpu.get_register(A3), 0);
+        assert_eq!(cpu.syscalls.exit_code, Some(5));
+        //less than a page is left for mappings
+        assert_eq!(cpu.mmap(0x100, MAP_ANONYMOUS), Err(ENOMEM));
+        assert_eq!(cpu.mmap(0x100, 0), Err(ENODEV));
+    }
+
+    #[test]
+    fn buffers_are_translated() {
+        let program: [u32; 20] = [
+            0x2404_0001, //li a0,1
+            0x3C05_8000, //lui a1,0x8000
+            0x34A5_0100, //ori a1,a1,0x100
+            0x2406_0003, //li a2,3
+            0x2402_0FA4, //li v0,4004 (write)
+            0x0000_000C, //syscall
+            0x0040_8021, //move s0,v0
+            0x0007_8821, //move s1,a3
+            0x2405_3000, //li a1,0x3000 (not in the TLB)
+            0x2402_0FA4, //li v0,4004 (write)
+            0x0000_000C, //syscall
+            0x0040_9021, //move s2,v0
+            0x0007_A021, //move s4,a3
+            0x2404_0000, //li a0,0
+            0x3C05_8000, //lui a1,0x8000
+            0x34A5_0200, //ori a1,a1,0x200
+            0x2406_FFFF, //li a2,-1
+            0x2402_0FA3, //li v0,4003 (read)
+            0x0000_000C, //syscall
+            0x0040_9821, //move s3,v0
+        ];
+        let mut b = program_bus(&program, b"hi\n");
+        //kseg0 maps to physical address 0
+        let mut cpu = MipsCpu::new(&mut b, 0x8000_0000);
+        let output = SharedOutput::default();
+        cpu.set_mmu(true);
+        cpu.set_syscalls(SyscallEmulation::Linux, 0x800, 0x800);
+        cpu.syscalls
+            .redirect(Box::new(Cursor::new("xy")), Box::new(output.clone()));
+        for _ in 0..program.len() {
+            cpu.step();
+        }
+        assert_eq!(String::from_utf8_lossy(&output.0.borrow()), "hi\n");
+        assert_eq!((cpu.get_register(16), cpu.get_register(17)), (3, 0));
+        assert_eq!((cpu.get_register(18), cpu.get_register(20)), (EFAULT, 1));
+        //the read is far larger than the input
+        assert_eq!(cpu.get_register(19), 2);
+        assert_eq!(cpu.read_memory(0x8000_0200, 3), Ok(b"xy\0".to_vec()));
+
+        //SPIM has no error convention, the simulation stops
+        let program: [u32; 3] = [
+            0x2404_4000, //li a0,0x4000
+            0x2402_0004, //li v0,4 (print_string)
+            0x0000_000C, //syscall
+        ];
+        let mut b = program_bus(&program, b"");
+        let mut cpu = MipsCpu::new(&mut b, 0);
+        cpu.set_syscalls(SyscallEmulation::Spim, 0x800, 0x800);
+        for _ in 0..program.len() {
+            cpu.step();
+        }
+        let error = SyscallError::BadAddress {
+            pc: 8,
+            service: 4,
+            addr: 0x4000,
+        };
+        let fatal = cpu.fatal.as_ref().unwrap();
+        assert_eq!(fatal.downcast_ref::<SyscallError>(), Some(&error));
+    }
+
+    #[test]
+    fn linux_thread_pointer() {
+        let program: [u32; 9] = [
+            0x2404_7000, //li a0,0x7000
+            0x2402_10BB, //li v0,4283 (set_thread_area)
+            0x0000_000C, //syscall
+            0x7C03_E83B, //rdhwr v1,$29
+            0x2405_FFF8, //li a1,-8
+            0x2406_2000, //li a2,0x2000
+            0x2402_1032, //li v0,4146 (writev)
+            0x0000_000C, //syscall
+            0x0040_8021, //move s0,v0
+        ];
+        //the kernel emulates rdhwr $29 on processors before Release 2
+        for isa in [isa::IsaLevel::Mips1, isa::IsaLevel::Mips32R2] {
+            let mut b = program_bus(&program, b"");
+            let mut cpu = MipsCpu::new(&mut b, 0);
+            cpu.set_isa(isa);
+            cpu.set_syscalls(SyscallEmulation::Linux, 0x800, 0x800);
+            for _ in 0..program.len() {
+                cpu.step();
+            }
+            assert!(cpu.fatal.is_none());
+            assert_eq!(cpu.get_register(3), 0x7000);
+            //an iovec array wrapping around the address space
+            assert_eq!(cpu.get_register(16), EINVAL);
+            assert_eq!(cpu.get_register(A3), 1);
+        }
+    }
+}
@@ -0,0 +1,121 @@
+//Buffer between the pipeline and memory holding stores until the bus has written them, so a store
+//only stalls when every entry is still waiting for the bus
+use std::collections::VecDeque;
+use std::fmt;
+
+#[derive(Default)]
+pub struct WriteBuffer {
+    //0 disables the buffer
+    capacity: usize,
+    //Cycles the bus needs to write one entry
+    drain_rate: u64,
+    //Word address of every buffered store and the cycle its write completes
+    entries: VecDeque<(u32, u64)>,
+
+    pub stores: u64,
+    pub forwarded_loads: u64,
+    pub full_stalls: u64,
+    pub full_stall_cycles: u64,
+    //Sum of the occupancy over all sampled cycles
+    occupancy: u64,
+    full_cycles: u64,
+    sampled_cycles: u64,
+}
+
+impl WriteBuffer {
+    pub fn new(capacity: usize, drain_rate: u64) -> Self {
+        WriteBuffer {
+            capacity,
+            drain_rate,
+            ..Default::default()
+        }
+    }
+
+    #[inline]
+    pub fn enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    //Drops the entries the bus has written before cycle
+    fn retire(&mut self, cycle: u64) {
+        while matches!(self.entries.front(), Some((_, done)) if *done <= cycle) {
+            self.entries.pop_front();
+        }
+    }
+
+    //Buffers a store issued in cycle, returns the cycles it stalls because the buffer is full
+    pub fn push(&mut self, addr: u32, cycle: u64) -> u64 {
+        self.retire(cycle);
+        let mut stall = 0;
+        if self.entries.len() == self.capacity {
+            stall = self.entries.front().unwrap().1 - cycle;
+            self.entries.pop_front();
+            self.full_stalls += 1;
+            self.full_stall_cycles += stall;
+        }
+        //the bus writes one entry after the other
+        let start = self.entries.back().map_or(cycle + stall, |(_, done)| *done);
+        self.entries.push_back((addr & !3, start + self.drain_rate));
+        self.stores += 1;
+        stall
+    }
+
+    //A load of a word still in the buffer takes the value from there instead of memory
+    pub fn forward(&mut self, addr: u32, cycle: u64) -> bool {
+        self.retire(cycle);
+        let found = self.entries.iter().any(|(a, _)| *a == addr & !3);
+        self.forwarded_loads += found as u64;
+        found
+    }
+
+    pub fn sample(&mut self, cycle: u64) {
+        if self.enabled() {
+            self.retire(cycle);
+            self.sampled_cycles += 1;
+            self.occupancy += self.entries.len() as u64;
+            self.full_cycles += (self.entries.len() == self.capacity) as u64;
+        }
+    }
+}
+
+impl fmt::Display for WriteBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let cycles = self.sampled_cycles.max(1) as f64;
+        writeln!(
+            f,
+            "Write buffer: {} stores buffered, {} loads forwarded",
+            self.stores, self.forwarded_loads
+        )?;
+        writeln!(
+            f,
+            "  Average occupancy: {:.2} of {} entries, full in {:.1}% of the cycles",
+            self.occupancy as f64 / cycles,
+            self.capacity,
+            100.0 * self.full_cycles as f64 / cycles
+        )?;
+        writeln!(
+            f,
+            "  Stores stalled on a full buffer: {} ({} cycles)",
+            self.full_stalls, self.full_stall_cycles
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn full_buffer_stalls_until_head_is_written() {
+        let mut wb = WriteBuffer::new(2, 10);
+        assert_eq!(wb.push(0x100, 1), 0);
+        assert_eq!(wb.push(0x104, 2), 0);
+        //the first entry is written in cycle 11
+        assert_eq!(wb.push(0x108, 3), 8);
+        assert!(wb.forward(0x106, 12));
+        //the second one in cycle 21
+        assert!(!wb.forward(0x104, 21));
+        assert_eq!(wb.full_stall_cycles, 8);
+        assert_eq!(wb.forwarded_loads, 1);
+    }
+}
@@ -14,32 +14,60 @@ fn main() {
     //let mem = Box::new(memory::Memory::new(Box::new([0; 1000]), mem_mapping));
     //let mut b = bus_objects::Bus::new(0, 0, vec![mem]).unwrap();
     let (entry, mut b) = prepare_bus(&c);
+    let stack_start = match c.syscalls() {
+        cpu::syscall::SyscallEmulation::Linux => build_initial_stack(&mut b, &c, &entry),
+        _ => c.stack_overwrite(),
+    };
     let mut cpu = cpu::MipsCpu::new(&mut b, entry.entry_point);
-    cpu.set_stack_start(c.stack_overwrite());
+    cpu.set_stack_start(stack_start);
     cpu.init_gp(entry.init_gp);
+    cpu.set_cp0(c.cp0());
+    cpu.set_fpu(c.fpu());
+    cpu.set_mmu(c.mmu());
+    cpu.set_isa(c.isa());
+    cpu.set_endianness(entry.endianness);
+    cpu.set_syscalls(c.syscalls(), entry.heap_start, c.heap_size() * 1024);
+    cpu.set_forwarding(c.forwarding());
+    cpu.set_load_delay(c.load_delay());
+    cpu.set_divide_by_zero(c.divide_by_zero());
+    cpu.set_mdu_latencies(c.mult_latency(), c.div_latency());
+    cpu.set_predictor(c.predictor(), c.predictor_bits());
+    cpu.set_branch_target_buffer(c.btb_entries());
+    cpu.set_return_address_stack(c.ras_entries());
+    cpu.set_caches(c.icache(), c.dcache(), c.miss_penalty());
+    cpu.set_write_buffer(c.write_buffer(), c.drain_rate());
+    if let Some(selection) = c.diagram() {
+        cpu.set_diagram(selection);
+    }
 
     let exit_device = cpu.bus.get_bus_obj_index(c.exit_pos());
+    //the program exits through the exit device or an exit syscall, the simulation stops on errors
+    let running = |cpu: &cpu::MipsCpu<'_>| {
+        cpu.fatal.is_none()
+            && cpu.syscalls.exit_code.is_none()
+            && cpu
+                .bus
+                .get_bus_obj_by_index(exit_device)
+                .read_byte(c.exit_pos())
+                == 0
+    };
 
     let mut instruction_counter: u32 = 0;
     let start_time = Instant::now();
     //add(&mut cpu, RTypeInstruction::new());
     if c.disassemble() {
-        while cpu
-            .bus
-            .get_bus_obj_by_index(exit_device)
-            .read_byte(c.exit_pos())
-            == 0
-        {
+        while running(&cpu) {
             instruction_counter += 1;
             cpu.step_disassemble();
         }
+    } else if c.pipeline() {
+        while running(&cpu) {
+            cpu.cycle();
+        }
+        cpu.drain();
+        instruction_counter = cpu.pipeline.retired as u32;
     } else {
-        while cpu
-            .bus
-            .get_bus_obj_by_index(exit_device)
-            .read_byte(c.exit_pos())
-            == 0
-        {
+        while running(&cpu) {
             instruction_counter += 1;
             cpu.step();
         }
@@ -54,43 +82,209 @@ fn main() {
         (elapsed as f64) / 1000.0,
         (instruction_counter as f64) / ((elapsed as f64) / 1000.0)
     );
+    if c.load_delay() == cpu::load_delay::LoadDelay::Warn {
+        println!(
+            "Load delay slots reading the loaded register: {}",
+            cpu.load_delay.warnings
+        );
+    }
+    if c.divide_by_zero() == cpu::division::DivideByZero::Warn {
+        println!(
+            "Divisions with unpredictable results: {}",
+            cpu.division.warnings
+        );
+    }
+    if c.cp0() {
+        println!("Exceptions taken: {}", cpu.cp0.exceptions);
+    }
+    if c.mmu() {
+        println!("TLB refills: {}", cpu.cp0.mmu.refills);
+    }
+    if c.pipeline() {
+        print!("{}", cpu.pipeline);
+        if c.hazards() {
+            print!("{}", cpu.pipeline.hazards);
+        }
+        print!("{}", cpu.pipeline.hazards.forwarded);
+        print!("{}", cpu.pipeline.predictors);
+        if c.btb_entries() > 0 {
+            print!("{}", cpu.pipeline.btb);
+        }
+        if c.ras_entries() > 0 {
+            print!("{}", cpu.pipeline.ras);
+        }
+        if let Some(cache) = &cpu.pipeline.icache {
+            print!("{}", cache);
+        }
+        if let Some(cache) = &cpu.pipeline.dcache {
+            print!("{}", cache);
+        }
+        if c.write_buffer() > 0 {
+            print!("{}", cpu.pipeline.write_buffer);
+        }
+        if let Some(diagram) = &cpu.pipeline.diagram {
+            print!("{}", diagram);
+        }
+    }
+    if let Some(error) = &cpu.fatal {
+        eprintln!("{}", error);
+        std::process::exit(1);
+    }
+    if let Some(code) = cpu.syscalls.exit_code {
+        std::process::exit(code);
+    }
 }
 
 struct ElfInfo {
     entry_point: u32,
     init_gp: u32,
+    //Byte order of the program, the command line overrides the ELF header
+    endianness: Endianness,
+    //First virtual page behind the loaded segments
+    heap_start: u32,
+    //Program header table as stored in the file, for the auxiliary vector
+    program_headers: Vec<u8>,
+    program_header_size: u32,
 }
 
 fn prepare_bus(c: &CommandLineArguments) -> (ElfInfo, Bus) {
-    let (entry, mut ram) = load_elf_into_ram(c.executable());
+    let (mut entry, mut ram) = load_elf_into_ram(c.executable(), c.mmu());
+    if let Some(endianness) = c.endianness() {
+        entry.endianness = endianness;
+    }
+    //heap_start and the stack stay virtual for the program
+    if c.syscalls() != cpu::syscall::SyscallEmulation::None {
+        ram.push(Box::new(memory::Memory::new(
+            vec![0; (c.heap_size() * 1024) as usize].into_boxed_slice(),
+            bus_objects::MemoryMapping {
+                start: physical_address(c, entry.heap_start),
+                size: c.heap_size() * 1024,
+            },
+        )));
+    }
     ram.push(Box::new(memory::Memory::new(
         vec![0; (c.stack_size() * 1024) as usize].into_boxed_slice(),
         bus_objects::MemoryMapping {
-            start: c.stack_overwrite() - (c.stack_size() * 1024),
+            start: physical_address(c, c.stack_overwrite() - (c.stack_size() * 1024)),
             size: c.stack_size() * 1024,
         },
     )));
-    ram.push(Box::new(printer::Printer::new(c.printer_pos())));
-    ram.push(Box::new(exit::Exit::new(c.exit_pos())));
+    ram.push(Box::new(printer::Printer::new(
+        c.printer_pos(),
+        entry.endianness,
+    )));
+    ram.push(Box::new(exit::Exit::new(c.exit_pos(), entry.endianness)));
+    if let Some(addr) = c.timer_pos() {
+        ram.push(Box::new(timer::Timer::new(
+            addr,
+            c.timer_line(),
+            entry.endianness,
+        )));
+    }
     (entry, bus_objects::Bus::new(0, 0, ram).unwrap())
 }
-fn load_elf_into_ram(filename: &str) -> (ElfInfo, vec::Vec<Box<dyn bus_objects::BusObject>>) {
+
+//With an MMU memory behind kseg0 and kseg1 addresses is placed at their physical address
+fn physical_address(c: &CommandLineArguments, addr: u32) -> u32 {
+    match c.mmu() {
+        true => cpu::mmu::unmapped_physical_address(addr),
+        false => addr,
+    }
+}
+//Lays out argc, argv, envp and the auxiliary vector below the stack start like Linux does for a
+//new process and returns the initial stack pointer
+fn build_initial_stack(b: &mut Bus, c: &CommandLineArguments, entry: &ElfInfo) -> u32 {
+    let mut top = c.stack_overwrite() & !0xF;
+    let mut push = |b: &mut Bus, bytes: &[u8]| {
+        top -= bytes.len() as u32;
+        for (i, byte) in bytes.iter().enumerate() {
+            b.write_byte(physical_address(c, top + i as u32), *byte);
+        }
+        top
+    };
+    let mut argv = vec![];
+    for arg in std::iter::once(c.executable()).chain(c.arguments().iter().map(|x| x.as_str())) {
+        argv.push(push(b, format!("{}\0", arg).as_bytes()));
+    }
+    //seeds the stack protector, fixed to keep runs reproducible
+    let random = push(b, &[0x5A; 16]);
+    let program_headers = push(b, &entry.program_headers) & !0xF;
+
+    let mut words = vec![argv.len() as u32];
+    words.extend(&argv);
+    //no environment
+    words.extend(&[0, 0]);
+    let auxv = [
+        (3, program_headers), //AT_PHDR
+        (4, entry.program_header_size),
+        (
+            5,
+            entry.program_headers.len() as u32 / entry.program_header_size.max(1),
+        ),
+        (6, 4096), //AT_PAGESZ
+        (9, entry.entry_point),
+        (11, 0), //AT_UID, AT_EUID, AT_GID and AT_EGID
+        (12, 0),
+        (13, 0),
+        (14, 0),
+        (17, 100), //AT_CLKTCK
+        (23, 0),   //AT_SECURE
+        (25, random),
+        (31, argv[0]), //AT_EXECFN
+        (0, 0),
+    ];
+    for (key, value) in auxv.iter() {
+        words.extend(&[*key, *value]);
+    }
+    let sp = (program_headers - words.len() as u32 * 4) & !0xF;
+    for (i, word) in words.iter().enumerate() {
+        b.write_w(
+            physical_address(c, sp + i as u32 * 4),
+            entry.endianness.word(*word),
+        );
+    }
+    sp
+}
+
+//With an MMU the segments are placed at their physical address, the entry point and heap_start
+//stay virtual
+fn load_elf_into_ram(
+    filename: &str,
+    mmu: bool,
+) -> (ElfInfo, vec::Vec<Box<dyn bus_objects::BusObject>>) {
     let elf = std::fs::read(filename).expect("Failed to read file");
     let mut vec: Vec<Box<dyn bus_objects::BusObject>> = vec![];
     let mut info: ElfInfo = ElfInfo {
         entry_point: 0,
         init_gp: 0,
+        endianness: Endianness::default(),
+        heap_start: 0,
+        program_headers: vec![],
+        program_header_size: 0,
     };
     match goblin::elf::Elf::parse(&elf) {
         Ok(binary) => {
             info.entry_point = binary.entry as u32;
+            info.endianness = match binary.little_endian {
+                true => Endianness::Little,
+                false => Endianness::Big,
+            };
+            let table = binary.header.e_phoff as usize;
+            info.program_header_size = binary.header.e_phentsize as u32;
+            info.program_headers = elf[table
+                ..table + binary.header.e_phnum as usize * binary.header.e_phentsize as usize]
+                .to_vec();
             for ph in binary.program_headers {
                 //println!("{:?}", ph);
                 if ph.p_type == elf32::program_header::PT_LOAD {
+                    let start = match mmu {
+                        true => cpu::mmu::unmapped_physical_address(ph.p_vaddr as u32),
+                        false => ph.p_vaddr as u32,
+                    };
                     let mut mem = Box::new(memory::Memory::new(
                         vec![0; ph.p_memsz as usize].into_boxed_slice(),
                         bus_objects::MemoryMapping {
-                            start: ph.p_vaddr as u32,
+                            start,
                             size: ph.p_memsz as u32,
                         },
                     ));
@@ -98,16 +292,18 @@ fn load_elf_into_ram(filename: &str) -> (ElfInfo, vec::Vec<Box<dyn bus_objects::
                         mem.write_byte(i as u32, elf[(i + ph.p_offset) as usize])
                     }
                     vec.push(mem);
+                    let end = ph.p_vaddr as u32 + ph.p_memsz as u32;
+                    info.heap_start = info.heap_start.max((end + 0xFFF) & !0xFFF);
                 }
             }
             for sh in binary.section_headers {
                 if sh.sh_type == 0x7000_0006 {
                     //if type MIPS_REGINFO
+                    //ri_gp_value follows the five register masks
+                    let gp = sh.sh_offset as usize + 20;
                     let mut arr: [u8; 4] = [0; 4];
-                    for i in 0..3 {
-                        arr[i] = elf[sh.sh_offset as usize + i + 20];
-                    }
-                    info.init_gp = u32::from_le_bytes(arr);
+                    arr.copy_from_slice(&elf[gp..gp + 4]);
+                    info.init_gp = info.endianness.word(u32::from_ne_bytes(arr));
                 }
             }
         }